@@ -0,0 +1,115 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+const ABSOLUTE_FORMATS: [&str; 4] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y.%m.%d %H:%M:%S"];
+const RELATIVE_UNITS: [(&str, i64); 7] = [
+    ("second", 1),
+    ("minute", 60),
+    ("hour", 3600),
+    ("day", 86400),
+    ("week", 7 * 86400),
+    ("month", 30 * 86400),
+    ("year", 365 * 86400),
+];
+
+/// Parses the dates accepted by options like `--since` or reflog selectors like `@{yesterday}`,
+/// returning a number of seconds since the epoch.
+///
+/// Supported forms are `now`, `yesterday`, relative dates such as `3 days ago` or `2.weeks.ago`,
+/// git's raw format (`1500000000 +0200` or `@1500000000`), RFC 3339, RFC 2822 and `YYYY-MM-DD [HH:MM[:SS]]`.
+pub fn parse(input: &str, now: DateTime<Local>) -> Option<i64> {
+    let input = input.trim();
+    match input {
+        "now" => return Some(now.timestamp()),
+        "yesterday" => return Some(now.timestamp() - 86400),
+        _ => {}
+    }
+    if let Some(timestamp) = parse_raw(input) {
+        return Some(timestamp);
+    }
+    if let Some(timestamp) = parse_relative(input, now) {
+        return Some(timestamp);
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Some(date.timestamp());
+    }
+    if let Ok(date) = DateTime::parse_from_rfc2822(input) {
+        return Some(date.timestamp());
+    }
+    for format in ABSOLUTE_FORMATS.iter() {
+        if let Ok(date) = NaiveDateTime::parse_from_str(input, format) {
+            return Local.from_local_datetime(&date).earliest().map(|date| date.timestamp());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Local.from_local_datetime(&date.and_hms(0, 0, 0)).earliest().map(|date| date.timestamp());
+    }
+    None
+}
+
+fn parse_raw(input: &str) -> Option<i64> {
+    let input = input.strip_prefix('@').unwrap_or(input);
+    let mut parts = input.split_whitespace();
+    let seconds = parts.next()?;
+    let timezone = parts.next();
+    if parts.next().is_some() || seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Some(timezone) = timezone {
+        if timezone.len() != 5 || !(timezone.starts_with('+') || timezone.starts_with('-')) {
+            return None;
+        }
+    }
+    seconds.parse().ok()
+}
+
+fn parse_relative(input: &str, now: DateTime<Local>) -> Option<i64> {
+    let words: Vec<&str> = input.split(|c: char| c.is_whitespace() || c == '.')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.len() < 3 || *words.last()? != "ago" {
+        return None;
+    }
+    let pairs = words[..words.len() - 1].chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    let mut seconds = 0;
+    for pair in pairs {
+        let count: i64 = pair[0].parse().ok()?;
+        let unit = pair[1].trim_end_matches('s');
+        let (_, unit_seconds) = RELATIVE_UNITS.iter().find(|(name, _)| *name == unit)?;
+        seconds += count * unit_seconds;
+    }
+    Some(now.timestamp() - seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Local> {
+        Local.timestamp(1_500_000_000, 0)
+    }
+
+    #[test]
+    fn test_parse_keywords_and_relative_dates() {
+        assert_eq!(Some(1_500_000_000), parse("now", now()));
+        assert_eq!(Some(1_500_000_000 - 86400), parse("yesterday", now()));
+        assert_eq!(Some(1_500_000_000 - 3 * 86400), parse("3 days ago", now()));
+        assert_eq!(Some(1_500_000_000 - 2 * 7 * 86400), parse("2.weeks.ago", now()));
+        assert_eq!(Some(1_500_000_000 - 3600 - 120), parse("1 hour 2 minutes ago", now()));
+        assert_eq!(None, parse("3 fortnights ago", now()));
+        assert_eq!(None, parse("3 days", now()));
+    }
+
+    #[test]
+    fn test_parse_absolute_dates() {
+        assert_eq!(Some(1_234_567_890), parse("1234567890 +0200", now()));
+        assert_eq!(Some(1_234_567_890), parse("@1234567890", now()));
+        assert_eq!(Some(1_500_000_000), parse("2017-07-14T02:40:00+00:00", now()));
+        assert_eq!(Some(1_500_000_000), parse("Fri, 14 Jul 2017 04:40:00 +0200", now()));
+        let local = Local.from_local_datetime(&NaiveDate::from_ymd(2017, 7, 14).and_hms(2, 40, 0)).unwrap();
+        assert_eq!(Some(local.timestamp()), parse("2017-07-14 02:40:00", now()));
+        assert_eq!(None, parse("not a date", now()));
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::pathspec::wildmatch;
+
+/// The state of an attribute for a path, as set by `attr`, `-attr`, `attr=value` or `!attr` in `.gitattributes`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AttributeState {
+    Set,
+    Unset,
+    Value(String),
+    Unspecified,
+}
+
+struct Rule {
+    /// The directory of the `.gitattributes` file, relative to the top of the workspace.
+    directory: String,
+    pattern: String,
+    attributes: Vec<(String, AttributeState)>,
+}
+
+/// The attributes assigned to paths by the `.gitattributes` files of the workspace and `.git/info/attributes`.
+///
+/// Rules are kept from the lowest to the highest precedence: deeper files override their parents, and
+/// `.git/info/attributes` overrides them all. Within a file, later lines override earlier ones.
+pub struct Attributes {
+    rules: Vec<Rule>,
+}
+
+impl Attributes {
+    /// Loads the given `.gitattributes` files, whose paths are relative to the workspace root.
+    pub fn load(root: &Path, files: &[PathBuf]) -> MinigitResult<Attributes> {
+        let mut files: Vec<&PathBuf> = files.iter().collect();
+        files.sort_by_key(|file| file.components().count());
+        let mut attributes = Attributes { rules: Vec::new() };
+        for file in files {
+            let directory = file.parent().map(|parent| parent.to_string_lossy().into_owned()).unwrap_or_default();
+            attributes.add_file(&root.join(file), &directory)?;
+        }
+        attributes.add_file(&root.join(".git").join("info").join("attributes"), "")?;
+        Ok(attributes)
+    }
+
+    fn add_file(&mut self, path: &Path, directory: &str) -> MinigitResult<()> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                self.add_rules(&content, directory);
+                Ok(())
+            }
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", path.display(), e), e)),
+        }
+    }
+
+    pub fn add_rules(&mut self, content: &str, directory: &str) {
+        for line in content.lines() {
+            let mut words = line.split_whitespace();
+            let pattern = match words.next() {
+                // Negative patterns are forbidden in attributes files.
+                Some(pattern) if !pattern.starts_with('#') && !pattern.starts_with('!') => pattern,
+                _ => continue,
+            };
+            let mut attributes = Vec::new();
+            for word in words {
+                let (name, state) = parse_state(word);
+                // The builtin `binary` macro also unsets `diff`, `merge` and `text`.
+                if name == "binary" && state == AttributeState::Set {
+                    for unset in ["diff", "merge", "text"].iter() {
+                        attributes.push((String::from(*unset), AttributeState::Unset));
+                    }
+                }
+                attributes.push((name, state));
+            }
+            self.rules.push(Rule { directory: String::from(directory), pattern: String::from(pattern), attributes });
+        }
+    }
+
+    /// Returns the state of an attribute for a path relative to the top of the workspace.
+    pub fn get(&self, path: &str, name: &str) -> AttributeState {
+        let mut state = AttributeState::Unspecified;
+        for rule in self.rules.iter() {
+            if let Some(value) = rule.attributes.iter().rev().find(|(attribute, _)| attribute == name).map(|(_, value)| value) {
+                if rule.matches(path) {
+                    state = value.clone();
+                }
+            }
+        }
+        state
+    }
+}
+
+impl Rule {
+    /// A pattern without slash matches the file name at any depth, otherwise the path relative to the directory of the rule.
+    fn matches(&self, path: &str) -> bool {
+        let relative_path = match self.directory.is_empty() {
+            true => path,
+            false => match path.strip_prefix(&self.directory).and_then(|rest| rest.strip_prefix('/')) {
+                Some(relative_path) => relative_path,
+                None => return false,
+            },
+        };
+        match self.pattern.contains('/') {
+            true => wildmatch(self.pattern.trim_start_matches('/').as_bytes(), relative_path.as_bytes(), true),
+            false => wildmatch(self.pattern.as_bytes(), relative_path.rsplit('/').next().unwrap().as_bytes(), true),
+        }
+    }
+}
+
+pub fn parse_state(word: &str) -> (String, AttributeState) {
+    if let Some(name) = word.strip_prefix('-') {
+        (String::from(name), AttributeState::Unset)
+    } else if let Some(name) = word.strip_prefix('!') {
+        (String::from(name), AttributeState::Unspecified)
+    } else if let Some(index) = word.find('=') {
+        (String::from(&word[..index]), AttributeState::Value(String::from(&word[index + 1..])))
+    } else {
+        (String::from(word), AttributeState::Set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_precedence() {
+        let mut attributes = Attributes { rules: Vec::new() };
+        attributes.add_rules("# comment\n*.txt text eol=lf\n*.png binary\n/docs/*.txt -text\n", "");
+        attributes.add_rules("*.txt !eol\n", "sub");
+
+        assert_eq!(AttributeState::Set, attributes.get("a.txt", "text"));
+        assert_eq!(AttributeState::Value(String::from("lf")), attributes.get("dir/a.txt", "eol"));
+        assert_eq!(AttributeState::Unset, attributes.get("docs/a.txt", "text"));
+        assert_eq!(AttributeState::Set, attributes.get("other/docs/a.txt", "text"));
+        assert_eq!(AttributeState::Unspecified, attributes.get("sub/a.txt", "eol"));
+        assert_eq!(AttributeState::Unset, attributes.get("image.png", "diff"));
+        assert_eq!(AttributeState::Unspecified, attributes.get("a.rs", "text"));
+    }
+}
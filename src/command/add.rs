@@ -1,45 +1,155 @@
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
 use crate::command::{Command, Runtime};
-use crate::gitobject::{Blob, GitObject};
+use crate::command::add_patch::add_patch;
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{Blob, GITLINK_MODE};
 use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::pathspec::Pathspec;
 use crate::repository::Repository;
 
 pub struct AddCommand {}
 
+struct Options {
+    dry_run: bool,
+    verbose: bool,
+    force: bool,
+    patch: bool,
+    update: bool,
+    all: bool,
+}
+
 impl Command for AddCommand {
+    const USAGE: Usage = Usage::new(&["minigit add [<options>] [--] <pathspec>..."], &[
+        Opt::flag('n', "dry-run", "dry run"),
+        Opt::flag('v', "verbose", "be verbose"),
+        Opt::flag('p', "patch", "select hunks interactively"),
+        Opt::flag('f', "force", "allow adding otherwise ignored files"),
+        Opt::flag('u', "update", "update tracked files"),
+        Opt::flag('A', "all", "add changes from all tracked and untracked files"),
+    ]);
+    const NEEDS_WORK_TREE: bool = true;
+
     fn execute(runtime: &mut Runtime) -> Result<(), MinigitError> {
-        if runtime.args.len() <= 2 {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let options = Options {
+            dry_run: parsed.is_set("dry-run"),
+            verbose: parsed.is_set("verbose"),
+            force: parsed.is_set("force"),
+            patch: parsed.is_set("patch"),
+            update: parsed.is_set("update"),
+            all: parsed.is_set("all"),
+        };
+        let pathspecs = parsed.args;
+        if options.update && options.all {
+            return Err(MinigitError::fatal(String::from("fatal: -A and -u are mutually incompatible")));
+        }
+        if options.patch && (options.update || options.all || options.dry_run) {
+            return Err(MinigitError::fatal(String::from("fatal: --patch is incompatible with --all, --update and --dry-run")));
+        }
+        // Without pathspec, -p, -u and -A operate on the whole workspace.
+        if pathspecs.is_empty() && !options.patch && !options.update && !options.all {
             return Err(MinigitError::new(String::from("Nothing specified, nothing added.\nMaybe you wanted to say 'minigit add .'?")));
         }
 
-        let mut repository = Repository::new(runtime.dir.join(".git"));
+        let mut repository = runtime.repository()?;
         repository.index()?.load_for_update()?;
 
-        let mut added_file_paths: Vec<PathBuf> = Vec::new();
-        for added_paths in runtime.args[2..].iter()
-            .map(Path::new)
-            .map(|added_path| repository.workspace().list_files_from_path(added_path)) {
-            added_file_paths.extend(added_paths?);
+        let pathspec = Pathspec::parse(&pathspecs, repository.workspace())?;
+        if options.patch {
+            return add_patch(runtime, &mut repository, &pathspec);
+        }
+        let index_paths: BTreeSet<PathBuf> = repository.index()?.get_entries().iter().map(|entry| PathBuf::from(entry.get_path())).collect();
+        // Tracked files are never ignored.
+        let mut workspace_paths: BTreeSet<PathBuf> = match options.force {
+            true => repository.workspace().list_all_files_from_path(Path::new(""))?,
+            false => repository.workspace().list_files_from_path(Path::new(""))?,
+        }.into_iter().collect();
+        for path in index_paths.iter() {
+            if repository.workspace().exists(path) && (repository.workspace().is_file(path)? || repository.workspace().is_gitlink(path)) {
+                workspace_paths.insert(path.clone());
+            }
         }
 
-        if let Err(mut error) = store_in_database_and_update_index(added_file_paths, &mut repository) {
+        let ignored_pathspecs = check_unmatched(&mut repository, &pathspec, &workspace_paths, &index_paths)?;
+
+        let removed_paths: Vec<&PathBuf> = index_paths.iter().filter(|path| !workspace_paths.contains(*path) && pathspec.matches(path)).collect();
+        for path in removed_paths {
+            if options.dry_run || options.verbose {
+                writeln!(&mut runtime.stdout, "remove '{}'", path.display()).unwrap();
+            }
+            if !options.dry_run {
+                repository.index()?.remove(path);
+            }
+        }
+        let added_file_paths: Vec<PathBuf> = workspace_paths.into_iter()
+            .filter(|path| pathspec.matches(path) && (!options.update || index_paths.contains(path)))
+            .collect();
+        if let Err(mut error) = store_in_database_and_update_index(added_file_paths, &mut repository, runtime, &options) {
             error.message = format!("{}\nfatal: adding files failed", error.message);
             return Err(error);
         }
 
-        repository.index()?.write_updates()?;
-        Ok(())
+        if !options.dry_run {
+            repository.index()?.write_updates()?;
+        }
+        match ignored_pathspecs.is_empty() {
+            true => Ok(()),
+            false => Err(MinigitError::new(format!("The following paths are ignored:\n{}\nhint: Use -f if you really want to add them.",
+                                                   ignored_pathspecs.join("\n")))),
+        }
+    }
+}
+
+/// Fails on the pathspecs matching no file, except the ones only matching ignored files which are returned.
+fn check_unmatched(repository: &mut Repository, pathspec: &Pathspec, workspace_paths: &BTreeSet<PathBuf>, index_paths: &BTreeSet<PathBuf>) -> MinigitResult<Vec<String>> {
+    let unmatched = pathspec.find_unmatched(workspace_paths.iter().chain(index_paths.iter()).map(PathBuf::as_path));
+    if unmatched.is_empty() {
+        return Ok(Vec::new());
+    }
+    let ignored_paths: Vec<PathBuf> = repository.workspace().list_all_files_from_path(Path::new(""))?.into_iter()
+        .filter(|path| repository.workspace().is_ignored(path))
+        .collect();
+    let mut ignored_pathspecs = Vec::new();
+    for unmatched in unmatched {
+        let single_pathspec = Pathspec::parse(&[String::from(unmatched)], repository.workspace())?;
+        match ignored_paths.iter().any(|path| single_pathspec.matches(path)) {
+            true => ignored_pathspecs.push(String::from(unmatched)),
+            false => return Err(MinigitError::fatal(format!("fatal: pathspec '{}' did not match any files", unmatched))),
+        }
     }
+    Ok(ignored_pathspecs)
 }
 
-fn store_in_database_and_update_index(added_file_paths: Vec<PathBuf>, repository: &mut Repository) -> MinigitResult<()> {
+/// Stores the files and updates their index entries, reporting the ones whose content or mode changed.
+fn store_in_database_and_update_index(added_file_paths: Vec<PathBuf>, repository: &mut Repository, runtime: &mut Runtime, options: &Options) -> MinigitResult<()> {
     for added_file_path in added_file_paths {
-        let data = repository.workspace().read_file(&added_file_path)?;
-        let mut blob = Blob::new(data);
-        repository.database().store(&mut blob)?;
         let metadata = repository.workspace().get_metadata(&added_file_path)?;
-        repository.index()?.add(&added_file_path, blob.get_oid(), metadata);
+        // A nested repository is recorded as the commit checked out in it, without storing anything.
+        let mut blob = None;
+        let oid = match metadata.mode {
+            GITLINK_MODE => repository.submodule(&added_file_path).refs().read_head()?
+                .ok_or_else(|| MinigitError::new(format!("error: '{}' does not have a commit checked out", added_file_path.display())))?,
+            _ => {
+                let file_blob = Blob::new(repository.workspace().read_file(&added_file_path)?);
+                let oid = repository.database().hash_object(&file_blob);
+                blob = Some(file_blob);
+                oid
+            }
+        };
+        let changed = repository.index()?.get_entry(&added_file_path)
+            .is_none_or(|entry| *entry.get_oid() != oid || entry.get_mode() != metadata.mode);
+        if changed && (options.dry_run || options.verbose) {
+            writeln!(&mut runtime.stdout, "add '{}'", added_file_path.display()).unwrap();
+        }
+        if !options.dry_run {
+            if let Some(blob) = blob.as_mut() {
+                repository.database().store(blob)?;
+            }
+            repository.index()?.add(&added_file_path, oid, metadata);
+        }
     }
     Ok(())
 }
@@ -50,6 +160,8 @@ mod tests {
     #[cfg(unix)]
     use std::os::unix::fs::PermissionsExt;
 
+    use crate::minigiterror::ErrorKind;
+
     #[test]
     fn test_add_one_file() {
         crate::tests::run_test(|repo_path| {
@@ -146,13 +258,29 @@ mod tests {
         });
     }
 
+    #[test]
+    #[cfg(unix)]
+    fn test_add_symlinks() {
+        crate::tests::run_test(|repo_path| {
+            let outside = format!("{}_outside", repo_path);
+            fs::create_dir_all(&outside).unwrap();
+            fs::write(format!("{}/secret.txt", outside), "Secret").unwrap();
+            std::os::unix::fs::symlink(&outside, format!("{}/outside", repo_path)).unwrap();
+            std::os::unix::fs::symlink("missing.txt", format!("{}/dangling", repo_path)).unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(".")));
+            // Links are stored as blobs of their target, and never traversed.
+            crate::tests::assert_index(repo_path, vec!((0o120000, String::from("dangling")), (0o120000, String::from("outside"))));
+            fs::remove_dir_all(&outside).unwrap();
+        });
+    }
+
     #[test]
     fn test_add_non_existent_file() {
         crate::tests::run_test(|repo_path| {
             crate::tests::execute_and_expect_error_message(
                 repo_path,
                 vec!(String::new(), String::from("add"), String::from("bad_path.txt")),
-                format!("fatal: pathspec '{}/bad_path.txt' did not match any files", repo_path));
+                String::from("fatal: pathspec 'bad_path.txt' did not match any files"));
         });
     }
 
@@ -175,9 +303,78 @@ mod tests {
     fn test_add_index_lock_already_created() {
         crate::tests::run_test(|repo_path| {
             fs::write(format!("{}/.git/index.lock", repo_path), "Random Content").unwrap();
-            crate::tests::execute_and_expect_error(
+            let error = crate::tests::execute_and_get_result(repo_path, vec!(String::new(), String::from("add"), String::from("bad_path.txt")));
+            assert_eq!(ErrorKind::LockHeld, error.err().unwrap().kind);
+        });
+    }
+
+    fn add(repo_path: &str, args: &[&str]) -> String {
+        let mut command = vec!(String::new(), String::from("add"));
+        command.extend(args.iter().map(|arg| String::from(*arg)));
+        crate::tests::execute_and_get_stdout(repo_path, command)
+    }
+
+    #[test]
+    fn test_add_wildcards() {
+        crate::tests::run_test(|repo_path| {
+            fs::create_dir(format!("{}/nested", repo_path)).unwrap();
+            fs::write(format!("{}/alice.txt", repo_path), "Alice").unwrap();
+            fs::write(format!("{}/bob.rs", repo_path), "Bob").unwrap();
+            fs::write(format!("{}/nested/carol.txt", repo_path), "Carol").unwrap();
+            add(repo_path, &["*.txt", ":!nested"]);
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_add_update_and_all() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/alice.txt", repo_path), "Alice").unwrap();
+            fs::write(format!("{}/bob.txt", repo_path), "Bob").unwrap();
+            add(repo_path, &["."]);
+            fs::write(format!("{}/alice.txt", repo_path), "Modified").unwrap();
+            fs::remove_file(format!("{}/bob.txt", repo_path)).unwrap();
+            fs::write(format!("{}/carol.txt", repo_path), "Carol").unwrap();
+
+            assert_eq!("remove 'bob.txt'\nadd 'alice.txt'\n", add(repo_path, &["-u", "-v"]));
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt"))));
+            assert_eq!("add 'carol.txt'\n", add(repo_path, &["--all", "--verbose"]));
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt")), (0o100644, String::from("carol.txt"))));
+            crate::tests::execute_and_expect_error_message(
                 repo_path,
-                vec!(String::new(), String::from("add"), String::from("bad_path.txt")));
+                vec!(String::new(), String::from("add"), String::from("-u"), String::from("-A")),
+                String::from("fatal: -A and -u are mutually incompatible"));
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_add_dry_run() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/alice.txt", repo_path), "Alice").unwrap();
+            add(repo_path, &["alice.txt"]);
+            fs::remove_file(format!("{}/alice.txt", repo_path)).unwrap();
+            fs::write(format!("{}/bob.txt", repo_path), "Bob").unwrap();
+            assert_eq!("remove 'alice.txt'\nadd 'bob.txt'\n", add(repo_path, &["-n", "."]));
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_add_ignored_files() {
+        crate::tests::run_test(|repo_path| {
+            fs::create_dir(format!("{}/target", repo_path)).unwrap();
+            fs::write(format!("{}/target/build.log", repo_path), "Log").unwrap();
+            fs::write(format!("{}/alice.txt", repo_path), "Alice").unwrap();
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("add"), String::from("alice.txt"), String::from("target")),
+                String::from("The following paths are ignored:\ntarget\nhint: Use -f if you really want to add them."));
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt"))));
+            add(repo_path, &["-f", "target"]);
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("alice.txt")), (0o100644, String::from("target/build.log"))));
+            // Tracked files are updated even when they are ignored.
+            fs::write(format!("{}/target/build.log", repo_path), "Modified").unwrap();
+            assert_eq!("add 'target/build.log'\n", add(repo_path, &["-v", "-u"]));
+        });
+    }
+}
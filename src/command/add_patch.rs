@@ -0,0 +1,378 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::command::Runtime;
+use crate::diff::{self, Edit, EditKind, Hunk};
+use crate::gitobject::{Blob, GitObject};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::repository::Repository;
+use crate::workspace::MinigitMetadata;
+
+const CONTEXT: usize = 3;
+const HELP: &str = "y - stage this hunk
+n - do not stage this hunk
+q - quit; do not stage this hunk or any of the remaining ones
+a - stage this hunk and all later hunks in the file
+d - do not stage this hunk or any of the later hunks in the file
+s - split the current hunk into smaller hunks
+e - manually edit the current hunk
+? - print help";
+const EDIT_FILE: &str = "ADD_EDIT.patch";
+const EDIT_INSTRUCTIONS: &str = "# ---
+# To remove '-' lines, make them ' ' lines (context).
+# To remove '+' lines, delete them.
+# Lines starting with # will be removed.
+# If it does not apply cleanly, you will be given an opportunity to
+# edit again.  If all lines of the hunk are removed, then the edit is
+# aborted and the hunk is left unchanged.
+";
+
+#[derive(Clone)]
+enum Decision {
+    Undecided,
+    Stage,
+    Skip,
+    /// The edits from `start` to `end` are replaced by the lines of the edited hunk.
+    Edited { start: usize, end: usize, lines: Vec<String> },
+}
+
+/// Stages parts of the changes of the tracked files matching the pathspec, asking on stdin whether to stage each hunk.
+pub fn add_patch(runtime: &mut Runtime, repository: &mut Repository, pathspec: &Pathspec) -> MinigitResult<()> {
+    let entries: Vec<(PathBuf, ObjectId, u32)> = repository.index()?.get_entries().iter()
+        .filter(|entry| entry.get_stage() == 0 && pathspec.matches(entry.get_path()))
+        .map(|entry| (PathBuf::from(entry.get_path()), *entry.get_oid(), entry.get_mode()))
+        .collect();
+    let mut changed = false;
+    for (path, oid, mode) in entries {
+        if !repository.workspace().exists(&path) || !repository.workspace().is_file(&path)? {
+            continue;
+        }
+        let old_data = repository.database().load_blob(&oid)?.get_data().to_vec();
+        let new_data = repository.workspace().read_file(&path)?;
+        // Binary files can't be split in lines.
+        let (old_text, new_text) = match (String::from_utf8(old_data), String::from_utf8(new_data)) {
+            (Ok(old_text), Ok(new_text)) if old_text != new_text && !old_text.contains('\0') && !new_text.contains('\0') => (old_text, new_text),
+            _ => continue,
+        };
+        changed = true;
+        let file = FileChanges { path: &path, oid, mode, new_oid: repository.database().hash_object(&Blob::new(new_text.clone().into_bytes())) };
+        if !stage_file_hunks(runtime, repository, &file, &old_text, &new_text)? {
+            break;
+        }
+    }
+    if !changed {
+        writeln!(&mut runtime.stdout, "No changes.").unwrap();
+    }
+    repository.index()?.write_updates()?;
+    Ok(())
+}
+
+struct FileChanges<'a> {
+    path: &'a Path,
+    oid: ObjectId,
+    mode: u32,
+    new_oid: ObjectId,
+}
+
+/// Asks about each hunk of a file, then stages the accepted ones. Returns false when the user quits.
+fn stage_file_hunks(runtime: &mut Runtime, repository: &mut Repository, file: &FileChanges, old_text: &str, new_text: &str) -> MinigitResult<bool> {
+    let (old_lines, new_lines) = (diff::split_lines(old_text), diff::split_lines(new_text));
+    let edits = diff::diff(&old_lines, &new_lines);
+    let mut hunks = diff::hunks(&edits, CONTEXT);
+    let mut decisions = vec![Decision::Undecided; hunks.len()];
+    let path = file.path.display();
+    write!(&mut runtime.stdout, "diff --git a/{} b/{}\nindex {}..{} {:o}\n--- a/{}\n+++ b/{}\n",
+           path, path, file.oid.abbreviate(7), file.new_oid.abbreviate(7), file.mode, path, path).unwrap();
+
+    let mut current = 0;
+    let mut quit = false;
+    while current < hunks.len() {
+        if !matches!(decisions[current], Decision::Undecided) {
+            current += 1;
+            continue;
+        }
+        let hunk = hunks[current].clone();
+        let splittable = hunk.is_splittable(&edits);
+        write!(&mut runtime.stdout, "{}({}/{}) Stage this hunk [y,n,q,a,d{},e,?]? ",
+               hunk.format(&edits), current + 1, hunks.len(), if splittable { ",s" } else { "" }).unwrap();
+        runtime.stdout.flush().unwrap();
+        let answer = match runtime.read_line_from_stdin()? {
+            Some(answer) => answer,
+            None => {
+                writeln!(&mut runtime.stdout).unwrap();
+                quit = true;
+                break;
+            }
+        };
+        match answer.trim().chars().next() {
+            Some('y') => decisions[current] = Decision::Stage,
+            Some('n') => decisions[current] = Decision::Skip,
+            Some('q') => {
+                quit = true;
+                break;
+            }
+            Some(answer @ 'a') | Some(answer @ 'd') => {
+                for decision in decisions[current..].iter_mut().filter(|decision| matches!(decision, Decision::Undecided)) {
+                    *decision = if answer == 'a' { Decision::Stage } else { Decision::Skip };
+                }
+            }
+            Some('s') if splittable => {
+                let split = diff::split_hunk(&edits, &hunk);
+                writeln!(&mut runtime.stdout, "Split into {} hunks.", split.len()).unwrap();
+                decisions.splice(current..current + 1, vec![Decision::Undecided; split.len()]);
+                hunks.splice(current..current + 1, split);
+            }
+            Some('e') => {
+                let git_path = repository.get_path().to_path_buf();
+                if let Some(decision) = edit_hunk(runtime, repository, &git_path, &edits, &hunk)? {
+                    decisions[current] = decision;
+                }
+            }
+            _ => writeln!(&mut runtime.stdout, "{}", HELP).unwrap(),
+        }
+    }
+
+    if decisions.iter().any(|decision| matches!(decision, Decision::Stage | Decision::Edited { .. })) {
+        let mut blob = Blob::new(apply_decisions(&edits, &hunks, &decisions).into_bytes());
+        repository.database().store(&mut blob)?;
+        repository.index()?.add(file.path, *blob.get_oid(), MinigitMetadata::from_mode(file.mode));
+    }
+    Ok(!quit)
+}
+
+/// Builds the staged content: the old lines, with the changes of the staged hunks and the lines of the edited ones.
+fn apply_decisions(edits: &[Edit], hunks: &[Hunk], decisions: &[Decision]) -> String {
+    let mut selected = vec![false; edits.len()];
+    for (hunk, decision) in hunks.iter().zip(decisions) {
+        if let Decision::Stage = decision {
+            selected[hunk.start..hunk.end].iter_mut().for_each(|selected| *selected = true);
+        }
+    }
+    let mut content = String::new();
+    let mut index = 0;
+    while index < edits.len() {
+        let replacement = decisions.iter().find_map(|decision| match decision {
+            Decision::Edited { start, end, lines } if *start == index => Some((*end, lines)),
+            _ => None,
+        });
+        if let Some((end, lines)) = replacement {
+            lines.iter().for_each(|line| content.push_str(line));
+            index = end;
+            continue;
+        }
+        match (edits[index].kind, selected[index]) {
+            (EditKind::Equal, _) | (EditKind::Delete, false) | (EditKind::Insert, true) => content.push_str(edits[index].text),
+            _ => {}
+        }
+        index += 1;
+    }
+    content
+}
+
+/// Lets the user edit the hunk in the editor. Returns none when the edit is aborted, leaving the hunk undecided.
+fn edit_hunk(runtime: &mut Runtime, repository: &mut Repository, git_path: &Path, edits: &[Edit], hunk: &Hunk) -> MinigitResult<Option<Decision>> {
+    let edit_path = git_path.join(EDIT_FILE);
+    let mut text = format!("# Manual hunk edit mode -- see bottom for a quick guide.\n{}{}", hunk.format(edits), EDIT_INSTRUCTIONS);
+    loop {
+        if let Err(e) = fs::write(&edit_path, &text) {
+            return Err(MinigitError::new(format!("error: could not write '{}': {}", edit_path.display(), e)));
+        }
+        run_editor(runtime, repository, &edit_path)?;
+        text = match fs::read_to_string(&edit_path) {
+            Ok(text) => text,
+            Err(e) => return Err(MinigitError::new(format!("error: could not read '{}': {}", edit_path.display(), e))),
+        };
+        let _ = fs::remove_file(&edit_path);
+        let lines = parse_edited_hunk(&text);
+        if lines.is_empty() {
+            return Ok(None);
+        }
+        if let Some(decision) = edited_decision(edits, hunk, &lines) {
+            return Ok(Some(decision));
+        }
+        write!(&mut runtime.stdout, "Your edited hunk does not apply. Edit again (saying \"no\" discards!) [y/n]? ").unwrap();
+        runtime.stdout.flush().unwrap();
+        match runtime.read_line_from_stdin()? {
+            Some(answer) if answer.trim().starts_with('y') => continue,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// Runs `GIT_EDITOR`, `core.editor`, `VISUAL` or `EDITOR`, in this order of preference, through the shell.
+fn run_editor(runtime: &Runtime, repository: &mut Repository, path: &Path) -> MinigitResult<()> {
+    let editor = match runtime.env.get("GIT_EDITOR") {
+        Some(editor) => editor.clone(),
+        None => match repository.config()?.get("core.editor") {
+            Some(editor) => String::from(editor),
+            None => runtime.env.get("VISUAL").or_else(|| runtime.env.get("EDITOR")).cloned().unwrap_or_else(|| String::from("vi")),
+        },
+    };
+    let status = process::Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .current_dir(&runtime.dir)
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(MinigitError::new(format!("error: there was a problem with the editor '{}'", editor))),
+    }
+}
+
+/// Returns the lines of the edited hunk as their kind and text, without the comments and the header, which is recounted.
+fn parse_edited_hunk(text: &str) -> Vec<(EditKind, String)> {
+    let mut lines: Vec<(EditKind, String)> = Vec::new();
+    for line in diff::split_lines(text) {
+        let kind = match line.chars().next() {
+            Some('#') | Some('@') => continue,
+            Some('\\') => {
+                // The previous line has no line terminator.
+                if let Some((_, previous)) = lines.last_mut() {
+                    if previous.ends_with('\n') {
+                        previous.pop();
+                    }
+                }
+                continue;
+            }
+            Some('-') => EditKind::Delete,
+            Some('+') => EditKind::Insert,
+            _ => EditKind::Equal,
+        };
+        let text = match line {
+            "\n" => line,
+            line => &line[1..],
+        };
+        lines.push((kind, String::from(text)));
+    }
+    lines
+}
+
+/// Checks that the edited hunk still applies to the old lines, then trims the unchanged context around its changes.
+fn edited_decision(edits: &[Edit], hunk: &Hunk, lines: &[(EditKind, String)]) -> Option<Decision> {
+    let edits = &edits[hunk.start..hunk.end];
+    let old_lines = edits.iter().filter(|edit| edit.kind != EditKind::Insert).map(|edit| edit.text);
+    let edited_old_lines = lines.iter().filter(|(kind, _)| *kind != EditKind::Insert).map(|(_, text)| text.as_str());
+    if !old_lines.eq(edited_old_lines) {
+        return None;
+    }
+    let leading = |kinds: &mut dyn Iterator<Item = EditKind>| kinds.take_while(|kind| *kind == EditKind::Equal).count();
+    let start = usize::min(leading(&mut edits.iter().map(|edit| edit.kind)), leading(&mut lines.iter().map(|(kind, _)| *kind)));
+    let end = usize::min(leading(&mut edits.iter().rev().map(|edit| edit.kind)), leading(&mut lines.iter().rev().map(|(kind, _)| *kind)));
+    let end = usize::min(end, usize::min(edits.len(), lines.len()) - start);
+    Some(Decision::Edited {
+        start: hunk.start + start,
+        end: hunk.end - end,
+        lines: lines[start..lines.len() - end].iter()
+            .filter(|(kind, _)| *kind != EditKind::Delete)
+            .map(|(_, text)| text.clone())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::tests::*;
+
+    use super::*;
+
+    fn add_patch(repo_path: &str, args: &[&str], answers: &str, editor: Option<&str>) -> String {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("add"), String::from("-p"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            runtime.stdin = Box::new(Cursor::new(String::from(answers).into_bytes()));
+            if let Some(editor) = editor {
+                runtime.env.insert(String::from("GIT_EDITOR"), String::from(editor));
+            }
+            crate::command::execute(&mut runtime).unwrap();
+        }
+        String::from_utf8(stdout).unwrap()
+    }
+
+    fn write_and_add(repo_path: &str, path: &str, content: &str) {
+        fs::write(PathBuf::from(repo_path).join(path), content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(path)));
+    }
+
+    fn staged_content(repo_path: &str, path: &str) -> String {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let entries = repository.index_take().unwrap().load_and_get_entries().unwrap();
+        let oid = *entries.iter().find(|entry| entry.get_path_as_str() == path).unwrap().get_oid();
+        String::from_utf8(repository.database().load_blob(&oid).unwrap().get_data().to_vec()).unwrap()
+    }
+
+    const LINES: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+
+    #[test]
+    fn test_stage_some_hunks() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", LINES);
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\neleven\n12\n").unwrap();
+
+            let output = add_patch(repo_path, &[], "n\ny\n", None);
+            assert!(output.starts_with("diff --git a/a.txt b/a.txt\nindex "));
+            assert!(output.ends_with(" 100644\n--- a/a.txt\n+++ b/a.txt\n\
+                                      @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n(1/2) Stage this hunk [y,n,q,a,d,e,?]? \
+                                      @@ -8,5 +8,5 @@\n 8\n 9\n 10\n-11\n+eleven\n 12\n(2/2) Stage this hunk [y,n,q,a,d,e,?]? "));
+            assert_eq!("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\neleven\n12\n", staged_content(repo_path, "a.txt"));
+            // The remaining change is the only one left to stage.
+            assert!(add_patch(repo_path, &["a.txt"], "a\n", None).contains("(1/1) Stage this hunk"));
+            assert_eq!("1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\neleven\n12\n", staged_content(repo_path, "a.txt"));
+            assert_eq!("No changes.\n", add_patch(repo_path, &[], "", None));
+        });
+    }
+
+    #[test]
+    fn test_split_hunk() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "1\n2\n3\n4\n5\n");
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "one\n2\n3\n4\nfive\n").unwrap();
+
+            let output = add_patch(repo_path, &[], "s\nn\ny\n", None);
+            assert!(output.contains("(1/1) Stage this hunk [y,n,q,a,d,s,e,?]? Split into 2 hunks.\n\
+                                     @@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n(1/2) Stage this hunk [y,n,q,a,d,e,?]? \
+                                     @@ -2,4 +2,4 @@\n 2\n 3\n 4\n-5\n+five\n(2/2) Stage this hunk [y,n,q,a,d,e,?]? "));
+            assert_eq!("1\n2\n3\n4\nfive\n", staged_content(repo_path, "a.txt"));
+        });
+    }
+
+    #[test]
+    fn test_edit_hunk() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", LINES);
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n").unwrap();
+            add_patch(repo_path, &[], "e\n", Some("sed -i -e 's/^+two$/+edited/'"));
+            assert_eq!("1\nedited\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n", staged_content(repo_path, "a.txt"));
+
+            // An edit which no longer matches the staged lines is refused.
+            let output = add_patch(repo_path, &[], "e\nn\nq\n", Some("sed -i -e 's/^ 1$/ changed/'"));
+            assert!(output.contains("Your edited hunk does not apply. Edit again (saying \"no\" discards!) [y/n]? "));
+            assert_eq!("1\nedited\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n", staged_content(repo_path, "a.txt"));
+        });
+    }
+
+    #[test]
+    fn test_quit_and_skip_files() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a\n");
+            write_and_add(repo_path, "b.txt", "b\n");
+            write_and_add(repo_path, "c.txt", "c\n");
+            for path in ["a.txt", "b.txt", "c.txt"].iter() {
+                fs::write(PathBuf::from(repo_path).join(path), "changed\n").unwrap();
+            }
+            let output = add_patch(repo_path, &[], "y\nd\nq\n", None);
+            assert!(output.contains("diff --git a/c.txt b/c.txt"));
+            assert_eq!("changed\n", staged_content(repo_path, "a.txt"));
+            assert_eq!("b\n", staged_content(repo_path, "b.txt"));
+            assert_eq!("c\n", staged_content(repo_path, "c.txt"));
+        });
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use crate::command::{Command, Runtime};
+use crate::command::init::{DEFAULT_BRANCH, create_git_directory};
+use crate::command::parseopt::{Opt, Usage};
+use crate::command::reset::reset_index;
+use crate::config::Config;
+use crate::database::DatabaseObject;
+use crate::gitobject::GITLINK_MODE;
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+
+pub struct CloneCommand;
+
+/// How `clone_repository` copies a repository.
+pub struct CloneOptions<'a> {
+    pub bare: bool,
+    /// The branch, or the tag, to check out instead of the branch of the source HEAD.
+    pub branch: Option<&'a str>,
+    /// The number of commits of history to copy from each ref, for a shallow clone.
+    pub depth: Option<usize>,
+    pub quiet: bool,
+}
+
+impl Command for CloneCommand {
+    const USAGE: Usage = Usage::new(&["minigit clone [<options>] [--] <repo> [<dir>]"], &[
+        Opt::flag('q', "quiet", "be more quiet"),
+        Opt::flag('n', "no-checkout", "don't create a checkout"),
+        Opt::long("bare", "create a bare repository"),
+        Opt::flag('b', "branch", "checkout <branch> instead of the remote's HEAD").value("<branch>"),
+        Opt::long("depth", "create a shallow clone of that depth").value("<depth>"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let (url, directory) = match parsed.args.as_slice() {
+            [url] => match guess_directory_name(url, parsed.is_set("bare")) {
+                Some(directory) => (url.clone(), directory),
+                None => return Err(MinigitError::fatal(String::from("fatal: No directory name could be guessed.\n\
+                                                                   Please specify a directory on the command line"))),
+            },
+            [url, directory] => (url.clone(), directory.clone()),
+            [] => return Err(MinigitError::usage(format!("fatal: You must specify a repository to clone.\n\n{}",
+                                                                              Self::USAGE.help()))),
+            _ => return Err(MinigitError::usage(format!("fatal: Too many arguments.\n\n{}", Self::USAGE.help()))),
+        };
+        let depth = match parsed.value("depth") {
+            Some(depth) => match depth.parse::<usize>() {
+                Ok(depth) if depth > 0 => Some(depth),
+                _ => return Err(MinigitError::fatal(format!("fatal: depth {} is not a positive number", depth))),
+            },
+            None => None,
+        };
+        let options = CloneOptions { bare: parsed.is_set("bare"), branch: parsed.value("branch"), depth, quiet: parsed.is_set("quiet") };
+
+        let workspace_path = runtime.dir.join(&directory);
+        let created = !workspace_path.exists();
+        if !created && fs::read_dir(&workspace_path).map_or(true, |mut entries| entries.next().is_some()) {
+            return Err(MinigitError::fatal(format!("fatal: destination path '{}' already exists and is not an empty directory.", directory)));
+        }
+        let git_path = match options.bare {
+            true => workspace_path.clone(),
+            false => workspace_path.join(".git"),
+        };
+        if !options.quiet {
+            match options.bare {
+                true => writeln!(&mut runtime.stderr, "Cloning into bare repository '{}'...", directory).unwrap(),
+                false => writeln!(&mut runtime.stderr, "Cloning into '{}'...", directory).unwrap(),
+            }
+        }
+
+        let result = clone_repository(runtime, &url, &git_path, &options).and_then(|head| match head {
+            Some(head) if !options.bare && !parsed.is_set("no-checkout") => checkout(&git_path, &head),
+            _ => Ok(()),
+        });
+        // Like git, nothing is left behind by a failed clone.
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&workspace_path);
+            if !created {
+                let _ = fs::create_dir(&workspace_path);
+            }
+        }
+        result
+    }
+}
+
+/// Clones the repository at a path or a `file://` URL into a new git directory, and returns the commit of its HEAD,
+/// which is left for the caller to check out. From a plain path, all the objects are hard linked, or copied across
+/// filesystems. From a URL, like over a real transport, only the objects reachable from the cloned refs are, which
+/// allows a shallow clone.
+///
+/// The branches become the remote-tracking branches of `origin`, and HEAD a local branch following its namesake, unless
+/// the clone is bare, in which case the branches are kept as they are.
+pub fn clone_repository(runtime: &mut Runtime, url: &str, git_path: &Path, options: &CloneOptions) -> MinigitResult<Option<ObjectId>> {
+    let (source_path, local) = match url.strip_prefix("file://") {
+        Some(path) => (runtime.dir.join(path), false),
+        None => (runtime.dir.join(url), true),
+    };
+    let mut source = Repository::new(source_path);
+    if !source.get_path().join(HEAD).is_file() {
+        return Err(MinigitError::fatal(format!("fatal: repository '{}' does not exist", url)));
+    }
+    // Like git, a local clone remembers the absolute path of its source, and a URL is kept as it is.
+    let url = match local {
+        true => match runtime.dir.join(url).canonicalize() {
+            Ok(path) => path.to_string_lossy().into_owned(),
+            Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot resolve '{}': {}", url, e), e)),
+        },
+        false => String::from(url),
+    };
+    let mut depth = options.depth;
+    if depth.is_some() && local {
+        writeln!(&mut runtime.stderr, "warning: --depth is ignored in local clones; use file:// instead.").unwrap();
+        depth = None;
+    }
+
+    // The branch of HEAD, or `None` to detach it at a tag or at the detached HEAD of the source.
+    let heads = source.refs().list_refs("refs/heads/")?;
+    let (branch, head) = match options.branch {
+        Some(name) => match (heads.iter().find(|(full_name, _)| full_name["refs/heads/".len()..] == *name),
+                             source.refs().resolve_ref(&format!("refs/tags/{}", name))?) {
+            (Some((_, oid)), _) => (Some(String::from(name)), Some(*oid)),
+            (None, Some(oid)) => (None, Some(source.database().peel(&oid)?)),
+            (None, None) => return Err(MinigitError::fatal(format!("fatal: Remote branch {} not found in upstream origin", name))),
+        },
+        None => match source.refs().current_ref()?.strip_prefix("refs/heads/") {
+            Some(name) => (Some(String::from(name)), source.refs().read_head()?),
+            None => (None, source.refs().read_head()?),
+        },
+    };
+    // A shallow clone only gets a single branch, and the tags pointing into its history.
+    let heads: Vec<(String, ObjectId)> = match (depth, &branch) {
+        (Some(_), Some(branch)) => heads.into_iter().filter(|(full_name, _)| full_name["refs/heads/".len()..] == *branch).collect(),
+        (Some(_), None) => Vec::new(),
+        (None, _) => heads,
+    };
+    let mut tags = source.refs().list_refs("refs/tags/")?;
+    if heads.is_empty() && head.is_none() {
+        writeln!(&mut runtime.stderr, "warning: You appear to have cloned an empty repository.").unwrap();
+    }
+
+    create_git_directory(runtime, git_path, options.bare, branch.as_deref().unwrap_or(DEFAULT_BRANCH), None)?;
+    let mut repository = Repository::new(git_path.to_path_buf());
+    let shallow = match depth {
+        Some(depth) => {
+            let mut copied = HashSet::new();
+            let tips: Vec<ObjectId> = heads.iter().map(|(_, oid)| *oid).chain(head).collect();
+            let shallow = fetch_objects(&mut source, &mut repository, &tips, depth, &mut copied)?;
+            tags.retain(|(_, oid)| source.database().peel(oid).is_ok_and(|target| copied.contains(&target)));
+            let tips: Vec<ObjectId> = tags.iter().map(|(_, oid)| *oid).collect();
+            fetch_objects(&mut source, &mut repository, &tips, depth, &mut copied)?;
+            shallow
+        }
+        None if local => {
+            link_dir(source.get_objects_path(), &git_path.join("objects"))?;
+            Vec::new()
+        }
+        None => {
+            let tips: Vec<ObjectId> = heads.iter().chain(tags.iter()).map(|(_, oid)| *oid).chain(head).collect();
+            fetch_objects(&mut source, &mut repository, &tips, usize::MAX, &mut HashSet::new())?
+        }
+    };
+    if !shallow.is_empty() {
+        let content: String = shallow.iter().map(|oid| format!("{}\n", oid)).collect();
+        if let Err(e) = fs::write(git_path.join("shallow"), content) {
+            return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot write {}: {}", git_path.join("shallow").display(), e), e));
+        }
+    }
+
+    let identity = runtime.reflog_identity();
+    let message = format!("clone: from {}", url);
+    let mut transaction = repository.refs().transaction(&identity);
+    for (full_name, oid) in heads.iter() {
+        match options.bare {
+            true => transaction.update(full_name, *oid, None, &message),
+            false => transaction.update(&format!("refs/remotes/origin/{}", &full_name["refs/heads/".len()..]), *oid, None, &message),
+        }
+    }
+    for (full_name, oid) in tags.iter() {
+        transaction.update(full_name, *oid, None, &message);
+    }
+    match (&branch, head) {
+        (Some(branch), Some(oid)) if !options.bare => transaction.update(&format!("refs/heads/{}", branch), oid, None, &message),
+        (None, Some(oid)) => {
+            transaction.set_deref(false);
+            transaction.update(HEAD, oid, None, &message);
+        }
+        _ => {}
+    }
+    transaction.commit()?;
+
+    let config_path = git_path.join("config");
+    Config::set_value(&config_path, "remote.origin.url", &url)?;
+    if !options.bare {
+        Config::set_value(&config_path, "remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*")?;
+        if let Some(branch) = &branch {
+            if heads.iter().any(|(full_name, _)| full_name["refs/heads/".len()..] == **branch) {
+                Config::set_value(&config_path, &format!("branch.{}.remote", branch), "origin")?;
+                Config::set_value(&config_path, &format!("branch.{}.merge", branch), &format!("refs/heads/{}", branch))?;
+            }
+        }
+        // The default branch of the remote is remembered by a symbolic ref.
+        if let Some(name) = source.refs().current_ref()?.strip_prefix("refs/heads/") {
+            if heads.iter().any(|(full_name, _)| full_name["refs/heads/".len()..] == *name) {
+                let path = git_path.join("refs/remotes/origin/HEAD");
+                if let Err(e) = fs::write(&path, format!("ref: refs/remotes/origin/{}\n", name)) {
+                    return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot write {}: {}", path.display(), e), e));
+                }
+            }
+        }
+    }
+    Ok(head)
+}
+
+/// Copies the objects reachable from the tips which are not in `copied` yet, following at most `depth` commits of
+/// history from each tip, and returns the commits whose parents were left out.
+fn fetch_objects(source: &mut Repository, target: &mut Repository, tips: &[ObjectId], depth: usize, copied: &mut HashSet<ObjectId>)
+                 -> MinigitResult<Vec<ObjectId>> {
+    let mut shallow = Vec::new();
+    let mut queue: VecDeque<(ObjectId, usize)> = tips.iter().map(|oid| (*oid, 1)).collect();
+    while let Some((oid, level)) = queue.pop_front() {
+        if !copied.insert(oid) {
+            continue;
+        }
+        source.database().link_object(&oid, target.database())?;
+        match source.database().load(&oid)? {
+            DatabaseObject::Commit(commit) => {
+                queue.push_back((*commit.get_tree(), level));
+                match level >= depth && !commit.get_parents().is_empty() {
+                    true => shallow.push(oid),
+                    false => queue.extend(commit.get_parents().iter().map(|parent| (*parent, level + 1))),
+                }
+            }
+            // The commits of submodules are in their own repositories.
+            DatabaseObject::Tree(tree) => {
+                queue.extend(tree.get_entries().iter().filter(|entry| entry.mode != GITLINK_MODE).map(|entry| (entry.oid, level)));
+            }
+            DatabaseObject::Tag(tag) => queue.push_back((*tag.get_object(), level)),
+            DatabaseObject::Blob(_) => {}
+        }
+    }
+    shallow.sort();
+    Ok(shallow)
+}
+
+/// Hard links all the files of a directory into another one, or copies them when they are on different filesystems.
+pub fn link_dir(source: &Path, target: &Path) -> MinigitResult<()> {
+    let entries = match fs::read_dir(source) {
+        Ok(entries) => entries,
+        Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot read {}: {}", source.display(), e), e)),
+    };
+    for entry in entries {
+        let entry = entry.map_err(|e| MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot read {}: {}", source.display(), e), e))?;
+        let target_path = target.join(entry.file_name());
+        if entry.path().is_dir() {
+            if let Err(e) = fs::create_dir_all(&target_path) {
+                return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot mkdir {}: {}", target_path.display(), e), e));
+            }
+            link_dir(&entry.path(), &target_path)?;
+        } else if let Err(e) = fs::hard_link(entry.path(), &target_path).or_else(|_| fs::copy(entry.path(), &target_path).map(|_| ())) {
+            return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: failed to copy '{}': {}", entry.path().display(), e), e));
+        }
+    }
+    Ok(())
+}
+
+/// Fills the index and the workspace of a new clone with the files of a commit.
+fn checkout(git_path: &Path, commit_oid: &ObjectId) -> MinigitResult<()> {
+    let mut repository = Repository::new(git_path.to_path_buf());
+    let tree_oid = *repository.database().load_commit(commit_oid)?.get_tree();
+    let target = repository.database().load_tree_list(&tree_oid)?;
+    reset_index(&mut repository, &target, true)
+}
+
+/// Names the clone after the last component of the URL, like `repo` for `../repo.git` or `file:///src/repo/.git`.
+fn guess_directory_name(url: &str, bare: bool) -> Option<String> {
+    let path = url.strip_prefix("file://").unwrap_or(url).trim_end_matches('/');
+    let path = path.strip_suffix("/.git").unwrap_or(path).trim_end_matches('/');
+    let name = Path::new(path).file_name()?.to_string_lossy().into_owned();
+    let name = name.strip_suffix(".git").unwrap_or(&name);
+    match (name.is_empty(), bare) {
+        (true, _) => None,
+        (false, true) => Some(format!("{}.git", name)),
+        (false, false) => Some(String::from(name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::command::{Runtime, execute};
+    use crate::minigiterror::MinigitResult;
+    use crate::objectid::ObjectId;
+    use crate::repository::Repository;
+    use crate::tests::*;
+
+    /// Returns what the command wrote to stderr.
+    fn clone(dir: &str, args: &[&str]) -> MinigitResult<String> {
+        let mut stderr = Vec::new();
+        let result = {
+            let mut runtime = Runtime { dir: PathBuf::from(dir), stdout: Box::new(std::io::sink()), stderr: Box::new(&mut stderr), ..Runtime::default() };
+            runtime.args = ["", "clone"].iter().chain(args.iter()).map(|arg| String::from(*arg)).collect();
+            // Cloning doesn't need an identity.
+            runtime.env.remove("GIT_AUTHOR_NAME");
+            runtime.env.remove("GIT_AUTHOR_EMAIL");
+            execute(&mut runtime)
+        };
+        result.map(|_| String::from_utf8(stderr).unwrap())
+    }
+
+    /// Writes two commits on `master`, the second one being tagged `v1.0`, and a branch `other` at the first one.
+    fn write_history(repo_path: &str) -> (ObjectId, ObjectId) {
+        let first = write_commit(repo_path, vec!(), "first", 1_600_000_000, &[("a.txt", "A")]);
+        let second = write_commit(repo_path, vec!(first), "second", 1_600_000_100, &[("a.txt", "A"), ("dir/b.txt", "B")]);
+        write_tag(repo_path, "v1.0", second, "Version 1.0", 1_600_000_200);
+        fs::write(format!("{}/.git/refs/heads/master", repo_path), format!("{}\n", second)).unwrap();
+        fs::write(format!("{}/.git/refs/heads/other", repo_path), format!("{}\n", first)).unwrap();
+        (first, second)
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn test_clone_local_repository() {
+        run_test(|repo_path| {
+            let (first, second) = write_history(repo_path);
+            assert_eq!("Cloning into 'copy'...\n", clone(repo_path, &[".", "copy"]).unwrap());
+            let clone_path = Path::new(repo_path).join("copy");
+            assert_eq!("A", read(&clone_path.join("a.txt")));
+            assert_eq!("B", read(&clone_path.join("dir/b.txt")));
+            assert_eq!("ref: refs/heads/master\n", read(&clone_path.join(".git/HEAD")));
+            assert_eq!("ref: refs/remotes/origin/master\n", read(&clone_path.join(".git/refs/remotes/origin/HEAD")));
+            let mut repository = Repository::new(clone_path.join(".git"));
+            assert_eq!(Some(second), repository.refs().resolve_ref("refs/heads/master").unwrap());
+            assert_eq!(Some(first), repository.refs().resolve_ref("refs/remotes/origin/other").unwrap());
+            assert!(repository.refs().resolve_ref("refs/heads/other").unwrap().is_none());
+            assert!(repository.refs().resolve_ref("refs/tags/v1.0").unwrap().is_some());
+            let source_path = Path::new(repo_path).canonicalize().unwrap();
+            assert_eq!(format!("clone: from {}", source_path.display()), repository.refs().read_reflog("refs/heads/master").unwrap()[0].message);
+            let config = read(&clone_path.join(".git/config"));
+            assert!(config.contains(&format!("[remote \"origin\"]\n\turl = {}\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n", source_path.display())));
+            assert!(config.contains("[branch \"master\"]\n\tremote = origin\n\tmerge = refs/heads/master\n"));
+            execute_and_expect_success_message(clone_path.to_str().unwrap(), vec!(String::new(), String::from("status")), String::new());
+
+            assert_eq!("fatal: destination path 'copy' already exists and is not an empty directory.",
+                       clone(repo_path, &[".", "copy"]).err().unwrap().message);
+            assert_eq!("fatal: repository 'missing' does not exist", clone(repo_path, &["missing"]).err().unwrap().message);
+            assert_eq!(Some(String::from("repo")), super::guess_directory_name("file:///src/repo/.git/", false));
+            assert_eq!(Some(String::from("repo.git")), super::guess_directory_name("../repo.git", true));
+            assert_eq!(None, super::guess_directory_name(".", false));
+            assert!(!Path::new(repo_path).join("missing").exists());
+        });
+    }
+
+    #[test]
+    fn test_clone_bare_or_other_branch() {
+        run_test(|repo_path| {
+            let (first, second) = write_history(repo_path);
+            assert_eq!("", clone(repo_path, &["--bare", "-q", ".", "copy.git"]).unwrap());
+            let bare_path = Path::new(repo_path).join("copy.git");
+            assert_eq!("ref: refs/heads/master\n", read(&bare_path.join("HEAD")));
+            let mut repository = Repository::new(bare_path.clone());
+            assert_eq!(Some(first), repository.refs().resolve_ref("refs/heads/other").unwrap());
+            assert!(repository.refs().resolve_ref("refs/remotes/origin/master").unwrap().is_none());
+            assert_eq!(format!("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = true\n[remote \"origin\"]\n\turl = {}\n",
+                               Path::new(repo_path).canonicalize().unwrap().display()),
+                       read(&bare_path.join("config")));
+
+            clone(repo_path, &["--branch", "other", "--no-checkout", ".", "other"]).unwrap();
+            let other_path = Path::new(repo_path).join("other");
+            assert_eq!("ref: refs/heads/other\n", read(&other_path.join(".git/HEAD")));
+            assert!(!other_path.join("a.txt").exists());
+            assert!(!other_path.join(".git/index").exists());
+
+            clone(repo_path, &["-b", "v1.0", ".", "tagged"]).unwrap();
+            assert_eq!(format!("{}\n", second), read(&Path::new(repo_path).join("tagged/.git/HEAD")));
+            assert_eq!("B", read(&Path::new(repo_path).join("tagged/dir/b.txt")));
+            assert_eq!("fatal: Remote branch missing not found in upstream origin", clone(repo_path, &["-b", "missing", ".", "x"]).err().unwrap().message);
+            assert!(!Path::new(repo_path).join("x").exists());
+        });
+    }
+
+    #[test]
+    fn test_shallow_clone() {
+        run_test(|repo_path| {
+            let (_, second) = write_history(repo_path);
+            let url = format!("file://{}", repo_path);
+            assert_eq!("", clone(repo_path, &["-q", "--depth", "1", &url, "shallow"]).unwrap());
+            let shallow_path = Path::new(repo_path).join("shallow");
+            assert_eq!(format!("{}\n", second), read(&shallow_path.join(".git/shallow")));
+            assert_eq!(format!("{}\n", second), execute_and_get_stdout(shallow_path.to_str().unwrap(),
+                                                                   vec!(String::new(), String::from("rev-list"), String::from("HEAD"))));
+            let mut repository = Repository::new(shallow_path.join(".git"));
+            assert!(repository.refs().resolve_ref("refs/remotes/origin/other").unwrap().is_none());
+            assert!(repository.refs().resolve_ref("refs/tags/v1.0").unwrap().is_some());
+            assert!(read(&shallow_path.join(".git/config")).contains(&format!("\turl = {}\n", url)));
+
+            // Only a transport honors the depth.
+            assert_eq!("warning: --depth is ignored in local clones; use file:// instead.\n",
+                       clone(repo_path, &["-q", "--depth", "1", repo_path, "full"]).unwrap());
+            assert!(!Path::new(repo_path).join("full/.git/shallow").exists());
+            assert_eq!("fatal: depth 0 is not a positive number", clone(repo_path, &["--depth", "0", &url, "none"]).err().unwrap().message);
+        });
+    }
+}
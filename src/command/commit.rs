@@ -1,17 +1,32 @@
 use chrono::Local;
 
 use crate::command::{Command, Runtime};
+use crate::command::parseopt::Usage;
 use crate::gitobject::{Author, Commit, GitObject, Tree};
-use crate::minigiterror::MinigitResult;
-use crate::repository::Repository;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::refs::HEAD;
 
 pub struct CommitCommand {}
 
 impl Command for CommitCommand {
+    const USAGE: Usage = Usage::new(&["minigit commit < <message>"], &[]);
+    const NEEDS_WORK_TREE: bool = true;
+
     fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
-        let mut repository = Repository::new(runtime.dir.join(".git"));
+        let args = runtime.args[2..].to_vec();
+        if !Self::USAGE.parse(runtime, &args)?.args.is_empty() {
+            return Err(Self::USAGE.error());
+        }
+        let mut repository = runtime.repository()?;
 
-        let entries = repository.index_take()?.load_and_get_entries()?;
+        let mut index = repository.index_take()?;
+        index.load_for_update()?;
+        if index.has_unmerged_entries() {
+            return Err(MinigitError::new(String::from("error: Committing is not possible because you have unmerged files.\n\
+                                                       fatal: Exiting because of an unresolved conflict.")));
+        }
+        let entries = index.load_and_get_entries()?;
 
         let mut tree = Tree::build(entries);
         tree.traverse(&mut |tree| repository.database().store(tree))?;
@@ -21,15 +36,23 @@ impl Command for CommitCommand {
         let author_email = runtime.get_env_var("GIT_AUTHOR_EMAIL")?;
         let author = Author::new(author_name, author_email, Local::now());
         let commit_message = runtime.read_from_stdin()?;
-        let mut commit = Commit::new(&parent, author, &commit_message, tree.get_oid());
+        let mut commit = Commit::new(parent.iter().cloned().collect(), author, &commit_message, tree.get_oid());
         repository.database().store(&mut commit)?;
-        repository.refs().update_head(commit.get_oid())?;
+        let title_line = commit.get_title_line().to_string();
+        let reflog_message = match parent {
+            Some(_) => format!("commit: {}", title_line),
+            None => format!("commit (initial): {}", title_line),
+        };
+        // Fails instead of dropping a commit made concurrently since HEAD was read.
+        let mut transaction = repository.refs().transaction(commit.get_committer());
+        transaction.update(HEAD, *commit.get_oid(), Some(parent.unwrap_or_else(ObjectId::null)), &reflog_message);
+        transaction.commit()?;
 
         let root_message = match parent {
             Some(_) => "",
             None => "(root-commit) ",
         };
-        writeln!(&mut runtime.stdout, "[{}{}] {}", root_message, commit.get_oid(), commit_message.lines().next().unwrap()).unwrap();
+        writeln!(&mut runtime.stdout, "[{}{}] {}", root_message, commit.get_oid(), title_line).unwrap();
         Ok(())
     }
 }
\ No newline at end of file
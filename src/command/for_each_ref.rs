@@ -0,0 +1,482 @@
+use std::cmp::Ordering;
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{Author, Commit, Tag};
+use crate::mergebase::MergeBase;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::wildmatch;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+use crate::revision::Revision;
+
+pub struct ForEachRefCommand;
+
+const DEFAULT_FORMAT: &str = "%(objectname) %(objecttype)\t%(refname)";
+const DEFAULT_SORT: &str = "refname";
+const SHORT_OID_LEN: usize = 7;
+const ATOMS: [&str; 19] = [
+    "refname", "objectname", "objecttype", "tree", "parent", "subject", "body", "contents",
+    "authorname", "authoremail", "authordate", "committername", "committeremail", "committerdate",
+    "taggername", "taggeremail", "taggerdate", "upstream", "HEAD",
+];
+
+/// A `%(name:modifier)` placeholder of a `--format` string, or a sort key.
+struct Atom {
+    name: String,
+    modifier: Option<String>,
+}
+
+enum FormatPart {
+    Literal(String),
+    Atom(Atom),
+}
+
+struct SortKey {
+    atom: Atom,
+    reverse: bool,
+}
+
+/// The value of an atom for one ref. Dates are sorted by their timestamp rather than by their text.
+struct AtomValue {
+    text: String,
+    number: Option<i64>,
+}
+
+struct RefItem {
+    name: String,
+    oid: ObjectId,
+    object_type: String,
+    commit: Option<Commit>,
+    tag: Option<Tag>,
+    /// The commit the ref points to, through annotated tags.
+    peeled_commit: Option<ObjectId>,
+}
+
+struct Filters {
+    contains: Vec<(String, bool)>,
+    merged: Vec<(String, bool)>,
+}
+
+impl Command for ForEachRefCommand {
+    const USAGE: Usage = Usage::new(&["minigit for-each-ref [<options>] [<pattern>...]"], &[
+        Opt::long("count", "show only <n> matched refs").value("<n>"),
+        Opt::long("format", "format to use for the output").value("<format>"),
+        Opt::long("sort", "field name to sort on").value("<key>"),
+        Opt::long("merged", "print only refs which are merged").value("<commit>").last_arg_default(HEAD),
+        Opt::long("no-merged", "print only refs which are not merged").value("<commit>").last_arg_default(HEAD),
+        Opt::long("contains", "print only refs which contain the commit").value("<commit>").last_arg_default(HEAD),
+        Opt::long("no-contains", "print only refs which don't contain the commit").value("<commit>").last_arg_default(HEAD),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let format = parsed.value("format").unwrap_or(DEFAULT_FORMAT);
+        let mut sort_keys: Vec<String> = parsed.values("sort").into_iter().map(String::from).collect();
+        let count = match parsed.value("count").map(str::parse) {
+            Some(Ok(n)) => Some(n),
+            Some(Err(_)) => return Err(Self::USAGE.error_with("option `count' expects a numerical value")),
+            None => None,
+        };
+        let filter = |names: &[&str]| -> Vec<(String, bool)> { parsed.occurrences(names).into_iter()
+            .map(|(name, value)| (String::from(value.unwrap_or(HEAD)), !name.starts_with("no-")))
+            .collect() };
+        let filters = Filters { contains: filter(&["contains", "no-contains"]), merged: filter(&["merged", "no-merged"]) };
+        let patterns = &parsed.args;
+
+        let format = parse_format(format)?;
+        if sort_keys.is_empty() {
+            sort_keys.push(String::from(DEFAULT_SORT));
+        }
+        let sort_keys = sort_keys.iter().map(|key| parse_sort_key(key)).collect::<MinigitResult<Vec<SortKey>>>()?;
+
+        let mut repository = runtime.repository()?;
+        let mut items = Vec::new();
+        for (name, oid) in repository.refs().list_refs("refs/")? {
+            if !patterns.is_empty() && !patterns.iter().any(|pattern| matches_pattern(&name, pattern)) {
+                continue;
+            }
+            let object_type = repository.database().read_type(&oid)?;
+            let (commit, tag) = match object_type.as_str() {
+                "commit" => (Some(repository.database().load_commit(&oid)?), None),
+                "tag" => (None, Some(repository.database().load_tag(&oid)?)),
+                _ => (None, None),
+            };
+            let peeled = repository.database().peel(&oid)?;
+            let peeled_commit = match repository.database().read_type(&peeled)?.as_str() {
+                "commit" => Some(peeled),
+                _ => None,
+            };
+            items.push(RefItem { name, oid, object_type, commit, tag, peeled_commit });
+        }
+        let items = apply_filters(&mut repository, items, &filters)?;
+
+        let mut rows = Vec::new();
+        for item in items {
+            let mut keys = Vec::new();
+            for sort_key in sort_keys.iter() {
+                keys.push(atom_value(&mut repository, &item, &sort_key.atom)?);
+            }
+            let mut line = String::new();
+            for part in format.iter() {
+                match part {
+                    FormatPart::Literal(text) => line.push_str(text),
+                    FormatPart::Atom(atom) => line.push_str(&atom_value(&mut repository, &item, atom)?.text),
+                }
+            }
+            rows.push((keys, line));
+        }
+        // Like git, the last --sort option is the primary key.
+        for (index, sort_key) in sort_keys.iter().enumerate() {
+            rows.sort_by(|(left, _), (right, _)| {
+                let ordering = compare_values(&left[index], &right[index]);
+                if sort_key.reverse { ordering.reverse() } else { ordering }
+            });
+        }
+        for (_, line) in rows.iter().take(count.unwrap_or(rows.len())) {
+            writeln!(&mut runtime.stdout, "{}", line).unwrap();
+        }
+        Ok(())
+    }
+}
+
+fn parse_format(format: &str) -> MinigitResult<Vec<FormatPart>> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = format;
+    while let Some(index) = rest.find('%') {
+        literal.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if rest.starts_with("%%") {
+            literal.push('%');
+            rest = &rest[2..];
+        } else if rest.starts_with("%(") {
+            let close = match rest.find(')') {
+                Some(close) => close,
+                None => return Err(MinigitError::fatal(format!("fatal: malformed format string {}", rest))),
+            };
+            if !literal.is_empty() {
+                parts.push(FormatPart::Literal(literal.clone()));
+                literal.clear();
+            }
+            parts.push(FormatPart::Atom(parse_atom(&rest[2..close])?));
+            rest = &rest[close + 1..];
+        } else if let Some(byte) = rest.get(1..3).and_then(|hex| u8::from_str_radix(hex, 16).ok()).filter(u8::is_ascii) {
+            literal.push(byte as char);
+            rest = &rest[3..];
+        } else {
+            literal.push('%');
+            rest = &rest[1..];
+        }
+    }
+    literal.push_str(rest);
+    if !literal.is_empty() {
+        parts.push(FormatPart::Literal(literal));
+    }
+    Ok(parts)
+}
+
+fn parse_atom(text: &str) -> MinigitResult<Atom> {
+    let (name, modifier) = match text.find(':') {
+        Some(index) => (&text[..index], Some(String::from(&text[index + 1..]))),
+        None => (text, None),
+    };
+    if !ATOMS.contains(&name) {
+        return Err(MinigitError::fatal(format!("fatal: unknown field name: {}", text)));
+    }
+    Ok(Atom { name: String::from(name), modifier })
+}
+
+fn parse_sort_key(key: &str) -> MinigitResult<SortKey> {
+    match key.strip_prefix('-') {
+        Some(key) => Ok(SortKey { atom: parse_atom(key)?, reverse: true }),
+        None => Ok(SortKey { atom: parse_atom(key)?, reverse: false }),
+    }
+}
+
+fn atom_value(repository: &mut Repository, item: &RefItem, atom: &Atom) -> MinigitResult<AtomValue> {
+    let modifier = atom.modifier.as_deref();
+    let text = match (atom.name.as_str(), modifier) {
+        ("refname", None) => item.name.clone(),
+        ("refname", Some("short")) => repository.refs().short_name(&item.name)?,
+        ("objectname", None) => item.oid.to_hex(),
+        ("objectname", Some("short")) => item.oid.abbreviate(SHORT_OID_LEN),
+        ("objecttype", None) => item.object_type.clone(),
+        ("upstream", _) => match upstream(repository, &item.name)? {
+            Some(upstream) if modifier == Some("short") => repository.refs().short_name(&upstream)?,
+            Some(upstream) if modifier.is_none() => upstream,
+            None if modifier.is_none() || modifier == Some("short") => String::new(),
+            _ => return Err(unsupported_modifier(atom)),
+        },
+        ("HEAD", None) => match repository.refs().current_ref()? == item.name {
+            true => String::from("*"),
+            false => String::from(" "),
+        },
+        (name, _) if item.tag.is_some() => return tag_atom_value(item.tag.as_ref().unwrap(), name, modifier).ok_or_else(|| unsupported_modifier(atom)),
+        (name, _) => return commit_atom_value(item.commit.as_ref(), name, modifier).ok_or_else(|| unsupported_modifier(atom)),
+    };
+    Ok(AtomValue { text, number: None })
+}
+
+/// Returns the value of an atom only defined for commits, which is empty for other objects.
+fn commit_atom_value(commit: Option<&Commit>, name: &str, modifier: Option<&str>) -> Option<AtomValue> {
+    let commit = match commit {
+        Some(commit) => commit,
+        None => return Some(AtomValue { text: String::new(), number: None }),
+    };
+    let text = |text: String| Some(AtomValue { text, number: None });
+    match (name, modifier) {
+        ("taggername" | "taggeremail" | "taggerdate", _) => text(String::new()),
+        ("tree", None) => text(commit.get_tree().to_hex()),
+        ("parent", None) => text(commit.get_parents().iter().map(|parent| parent.to_hex()).collect::<Vec<String>>().join(" ")),
+        ("subject", None) => text(String::from(commit.get_title_line())),
+        ("body", None) => text(message_body(commit.get_message())),
+        ("contents", None) => text(String::from(commit.get_message())),
+        ("authorname", None) => text(String::from(commit.get_author().get_name())),
+        ("authoremail", None) => text(format!("<{}>", commit.get_author().get_email())),
+        ("authordate", _) => format_date(commit.get_author(), modifier),
+        ("committername", None) => text(String::from(commit.get_committer().get_name())),
+        ("committeremail", None) => text(format!("<{}>", commit.get_committer().get_email())),
+        ("committerdate", _) => format_date(commit.get_committer(), modifier),
+        _ => None,
+    }
+}
+
+/// Returns the value of an atom for an annotated tag, where the commit atoms are empty.
+fn tag_atom_value(tag: &Tag, name: &str, modifier: Option<&str>) -> Option<AtomValue> {
+    let text = |text: String| Some(AtomValue { text, number: None });
+    match (name, modifier, tag.get_tagger()) {
+        ("subject", None, _) => text(String::from(tag.get_title_line())),
+        ("body", None, _) => text(message_body(tag.get_message())),
+        ("contents", None, _) => text(String::from(tag.get_message())),
+        ("taggername", None, Some(tagger)) => text(String::from(tagger.get_name())),
+        ("taggeremail", None, Some(tagger)) => text(format!("<{}>", tagger.get_email())),
+        ("taggerdate", _, Some(tagger)) => format_date(tagger, modifier),
+        _ => commit_atom_value(None, name, modifier),
+    }
+}
+
+/// Everything after the paragraph of the subject.
+fn message_body(message: &str) -> String {
+    match message.find("\n\n") {
+        Some(index) => String::from(message[index + 2..].trim_start_matches('\n')),
+        None => String::new(),
+    }
+}
+
+fn format_date(author: &Author, modifier: Option<&str>) -> Option<AtomValue> {
+    let date = author.get_timestamp();
+    let text = match modifier {
+        None => date.format("%a %b %-d %H:%M:%S %Y %z").to_string(),
+        Some("iso") => date.format("%Y-%m-%d %H:%M:%S %z").to_string(),
+        Some("iso-strict") => date.to_rfc3339(),
+        Some("short") => date.format("%Y-%m-%d").to_string(),
+        Some("unix") => date.timestamp().to_string(),
+        Some("raw") => date.format("%s %z").to_string(),
+        Some(_) => return None,
+    };
+    Some(AtomValue { text, number: Some(date.timestamp()) })
+}
+
+fn unsupported_modifier(atom: &Atom) -> MinigitError {
+    MinigitError::fatal(format!("fatal: unrecognized %({}) argument: {}", atom.name, atom.modifier.as_deref().unwrap_or("")))
+}
+
+/// The branch configured as upstream of a local branch with `branch.<name>.remote` and `branch.<name>.merge`.
+fn upstream(repository: &mut Repository, full_name: &str) -> MinigitResult<Option<String>> {
+    let branch = match full_name.strip_prefix("refs/heads/") {
+        Some(branch) => branch,
+        None => return Ok(None),
+    };
+    let config = repository.config()?;
+    let remote = config.get(&format!("branch.{}.remote", branch));
+    let merge = config.get(&format!("branch.{}.merge", branch));
+    match (remote, merge) {
+        (Some("."), Some(merge)) => Ok(Some(String::from(merge))),
+        (Some(remote), Some(merge)) => Ok(merge.strip_prefix("refs/heads/").map(|branch| format!("refs/remotes/{}/{}", remote, branch))),
+        _ => Ok(None),
+    }
+}
+
+fn compare_values(left: &AtomValue, right: &AtomValue) -> Ordering {
+    match (left.number, right.number) {
+        (Some(left), Some(right)) => left.cmp(&right),
+        _ => left.text.cmp(&right.text),
+    }
+}
+
+fn apply_filters(repository: &mut Repository, items: Vec<RefItem>, filters: &Filters) -> MinigitResult<Vec<RefItem>> {
+    let mut contains = Vec::new();
+    for (revision, wanted) in filters.contains.iter() {
+        contains.push((Revision::parse(revision)?.resolve_commit(repository)?, *wanted));
+    }
+    let mut merged = Vec::new();
+    for (revision, wanted) in filters.merged.iter() {
+        merged.push((Revision::parse(revision)?.resolve_commit(repository)?, *wanted));
+    }
+    if contains.is_empty() && merged.is_empty() {
+        return Ok(items);
+    }
+
+    let mut merge_base = MergeBase::new(repository);
+    let mut result = Vec::new();
+    for item in items {
+        // Only commits, possibly behind annotated tags, can contain or be merged into other commits.
+        let oid = match item.peeled_commit {
+            Some(oid) => oid,
+            None => continue,
+        };
+        // Like git, a ref must match one of the `--contains` and one of the `--merged`, but none of their negations.
+        let mut contained = Vec::new();
+        for (commit, wanted) in contains.iter() {
+            contained.push((merge_base.is_ancestor(*commit, oid)?, *wanted));
+        }
+        let mut merged_into = Vec::new();
+        for (commit, wanted) in merged.iter() {
+            merged_into.push((merge_base.is_ancestor(oid, *commit)?, *wanted));
+        }
+        if matches_filter(&contained) && matches_filter(&merged_into) {
+            result.push(item);
+        }
+    }
+    Ok(result)
+}
+
+/// Whether the results of a filter for each of its commits, with whether the filter was negated, accept a ref.
+fn matches_filter(results: &[(bool, bool)]) -> bool {
+    let mut positives = results.iter().filter(|(_, wanted)| *wanted).peekable();
+    let any_positive = positives.peek().is_none() || positives.any(|(result, _)| *result);
+    any_positive && results.iter().filter(|(_, wanted)| !*wanted).all(|(result, _)| !*result)
+}
+
+/// Patterns match a whole ref name, a leading sequence of its components like `refs/heads`, or as a glob.
+fn matches_pattern(name: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    if name == pattern || name.starts_with(&format!("{}/", pattern)) {
+        return true;
+    }
+    wildmatch(pattern.as_bytes(), name.as_bytes(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::tests::*;
+
+    use super::*;
+
+    fn for_each_ref(repo_path: &str, args: &[&str]) -> String {
+        let mut command = vec!(String::new(), String::from("for-each-ref"));
+        command.extend(args.iter().map(|arg| String::from(*arg)));
+        execute_and_get_stdout(repo_path, command)
+    }
+
+    fn create_ref(repo_path: &str, full_name: &str, oid: ObjectId) {
+        let path = PathBuf::from(repo_path).join(".git").join(full_name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", oid)).unwrap();
+    }
+
+    //   a - b - c  master
+    //        \
+    //         d    topic
+    fn create_history(repo_path: &str) -> (ObjectId, ObjectId, ObjectId, ObjectId) {
+        let a = write_commit(repo_path, vec!(), "a", 1_500_000_000, &[]);
+        let b = write_commit(repo_path, vec!(a), "b\n\nbody of b", 1_500_000_100, &[]);
+        let c = write_commit(repo_path, vec!(b), "c", 1_500_000_200, &[]);
+        let d = write_commit(repo_path, vec!(b), "d", 1_400_000_000, &[]);
+        create_ref(repo_path, "refs/heads/master", c);
+        create_ref(repo_path, "refs/heads/topic", d);
+        create_ref(repo_path, "refs/tags/v1", b);
+        (a, b, c, d)
+    }
+
+    #[test]
+    fn test_default_format() {
+        run_test(|repo_path| {
+            let (_, b, c, d) = create_history(repo_path);
+            assert_eq!(format!("{} commit\trefs/heads/master\n{} commit\trefs/heads/topic\n{} commit\trefs/tags/v1\n", c, d, b),
+                       for_each_ref(repo_path, &[]));
+            assert_eq!(format!("{} commit\trefs/tags/v1\n", b), for_each_ref(repo_path, &["refs/tags"]));
+            assert_eq!(format!("{} commit\trefs/heads/topic\n", d), for_each_ref(repo_path, &["refs/*/t*"]));
+            assert_eq!("", for_each_ref(repo_path, &["refs/*"]));
+        });
+    }
+
+    #[test]
+    fn test_format_atoms() {
+        run_test(|repo_path| {
+            let (_, b, _, _) = create_history(repo_path);
+            let format = "--format=%(refname:short) %(objectname:short) %(subject)|%(body)|%(authorname) %(committeremail) %(committerdate:unix) %(HEAD)%%";
+            assert_eq!(format!("v1 {} b|body of b|A U Thor <author@example.com> 1500000100  %\n", b.abbreviate(7)),
+                       for_each_ref(repo_path, &[format, "refs/tags/v1"]));
+            assert_eq!("master*\n", for_each_ref(repo_path, &["--format=%(refname:short)%(HEAD)", "refs/heads/master"]));
+            // Only escapes of ASCII characters are interpreted, others are kept as they are.
+            assert_eq!("v1\t%e9%zz\n", for_each_ref(repo_path, &["--format=%(refname:short)%09%e9%zz", "refs/tags/v1"]));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("for-each-ref"), String::from("--format=%(unknown)")),
+                                             String::from("fatal: unknown field name: unknown"));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("for-each-ref"), String::from("--format=%(refname")),
+                                             String::from("fatal: malformed format string %(refname"));
+        });
+    }
+
+    #[test]
+    fn test_annotated_tags() {
+        run_test(|repo_path| {
+            let (a, b, _, _) = create_history(repo_path);
+            let tag = write_tag(repo_path, "v0", a, "Release 0\n\nFirst release\n", 1_600_000_000);
+            let format = "--format=%(objectname) %(objecttype) %(subject)|%(body)|%(taggername) %(taggeremail) %(taggerdate:unix)|%(authorname)";
+            assert_eq!(format!("{} tag Release 0|First release\n|C O Mitter <committer@example.com> 1600000000|\n{} commit b|body of b|  |A U Thor\n", tag, b),
+                       for_each_ref(repo_path, &[format, "refs/tags"]));
+            // Tags are peeled to filter on their commit.
+            assert_eq!("v0\n", for_each_ref(repo_path, &["--format=%(refname:short)", "--contains", &a.to_hex(), "--no-contains", &b.to_hex()]));
+        });
+    }
+
+    #[test]
+    fn test_upstream() {
+        run_test(|repo_path| {
+            let (_, _, c, _) = create_history(repo_path);
+            create_ref(repo_path, "refs/remotes/origin/master", c);
+            fs::write(PathBuf::from(repo_path).join(".git/config"),
+                      "[branch \"master\"]\n\tremote = origin\n\tmerge = refs/heads/master\n[branch \"topic\"]\n\tremote = .\n\tmerge = refs/heads/master\n").unwrap();
+            assert_eq!("master origin/master refs/remotes/origin/master\ntopic master refs/heads/master\n",
+                       for_each_ref(repo_path, &["--format=%(refname:short) %(upstream:short) %(upstream)", "refs/heads"]));
+        });
+    }
+
+    #[test]
+    fn test_sort_and_count() {
+        run_test(|repo_path| {
+            create_history(repo_path);
+            let format = "--format=%(refname:short)";
+            assert_eq!("topic\nv1\nmaster\n", for_each_ref(repo_path, &[format, "--sort=committerdate"]));
+            assert_eq!("master\nv1\n", for_each_ref(repo_path, &[format, "--sort=-committerdate", "--count=2"]));
+            assert_eq!("v1\ntopic\nmaster\n", for_each_ref(repo_path, &[format, "--sort=-refname"]));
+            // The last key is the primary one.
+            assert_eq!("v1\ntopic\nmaster\n", for_each_ref(repo_path, &[format, "--sort=-refname", "--sort=objecttype"]));
+        });
+    }
+
+    #[test]
+    fn test_contains_and_merged() {
+        run_test(|repo_path| {
+            let (a, b, _, _) = create_history(repo_path);
+            let format = "--format=%(refname:short)";
+            assert_eq!("master\ntopic\nv1\n", for_each_ref(repo_path, &[format, "--contains", &b.to_hex()]));
+            assert_eq!("topic\nv1\n", for_each_ref(repo_path, &[format, "--contains=master~1", "--no-contains=master"]));
+            assert_eq!("master\nv1\n", for_each_ref(repo_path, &[format, "--merged"]));
+            assert_eq!("topic\n", for_each_ref(repo_path, &[format, "--no-merged=master"]));
+            assert_eq!("", for_each_ref(repo_path, &[format, "--merged", &a.to_hex()]));
+            // Each positive filter accepts the refs matching any of its commits.
+            assert_eq!("master\ntopic\n", for_each_ref(repo_path, &[format, "--contains=master", "--contains=topic"]));
+            assert_eq!("master\ntopic\nv1\n", for_each_ref(repo_path, &[format, "--merged=master", "--merged=topic"]));
+            assert_eq!("master\n", for_each_ref(repo_path, &[format, "--contains=master", "--contains=topic", "--no-merged=topic"]));
+            assert_eq!("v1\n", for_each_ref(repo_path, &[format, "--no-contains=master", "--no-contains=topic"]));
+        });
+    }
+}
@@ -0,0 +1,74 @@
+use crate::command::{COMMANDS, Command, Runtime, find_command};
+use crate::command::parseopt::Usage;
+use crate::minigiterror::{MinigitError, MinigitResult};
+
+pub struct HelpCommand;
+
+impl Command for HelpCommand {
+    const USAGE: Usage = Usage::new(&["minigit help [<command>]"], &[]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        match parsed.args.as_slice() {
+            [] => {
+                writeln!(&mut runtime.stdout, "usage: minigit <command> [<args>]\n\nThese are the minigit commands:").unwrap();
+                let width = COMMANDS.iter().map(|command| command.name.len()).max().unwrap_or(0);
+                for command in COMMANDS.iter() {
+                    writeln!(&mut runtime.stdout, "   {:width$}   {}", command.name, command.summary, width = width).unwrap();
+                }
+                writeln!(&mut runtime.stdout, "\nSee 'minigit help <command>' to read about a specific command.").unwrap();
+            }
+            [name] => match find_command(name) {
+                Some(command) => writeln!(&mut runtime.stdout, "{}", command.usage.help()).unwrap(),
+                None => return Err(MinigitError::new(format!("error: '{}' is not a minigit command. See 'minigit help'.", name))),
+            },
+            _ => return Err(Self::USAGE.error()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::command::{Runtime, execute};
+    use crate::minigiterror::ErrorKind;
+    use crate::tests::*;
+
+    #[test]
+    fn test_help() {
+        run_test(|repo_path| {
+            let stdout = execute_and_get_stdout(repo_path, vec!(String::new(), String::from("help")));
+            assert!(stdout.starts_with("usage: minigit <command> [<args>]\n"));
+            assert!(stdout.contains("\n   rm             Remove files from the working tree and from the index\n"));
+
+            let stdout = execute_and_get_stdout(repo_path, vec!(String::new(), String::from("help"), String::from("rm")));
+            assert!(stdout.starts_with("usage: minigit rm [<options>] [--] <file>...\n\n    -n, --dry-run"));
+
+            let error = execute_and_get_result(repo_path, vec!(String::new(), String::from("help"), String::from("unknown")));
+            assert_eq!("error: 'unknown' is not a minigit command. See 'minigit help'.", error.err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_dash_h() {
+        run_test(|repo_path| {
+            for args in [vec!(String::new(), String::from("rev-parse"), String::from("-h")),
+                         vec!(String::new(), String::from("rev-parse"), String::from("--help")),
+                         vec!(String::new(), String::from("rev-list"), String::from("-h"), String::from("HEAD")),
+                         vec!(String::new(), String::from("rm"), String::from("--cached"), String::from("--help"))] {
+                let mut stdout = Vec::new();
+                let result = {
+                    let mut runtime = Runtime { dir: PathBuf::from(repo_path), args, stdout: Box::new(&mut stdout), ..Runtime::default() };
+                    execute(&mut runtime)
+                };
+                let error = result.err().unwrap();
+                assert_eq!(ErrorKind::Usage, error.kind);
+                assert_eq!("", error.message);
+                assert!(String::from_utf8(stdout).unwrap().starts_with("usage: minigit "));
+            }
+        });
+    }
+}
@@ -1,28 +1,216 @@
-use std::clone::Clone;
 use std::fs;
-use std::path::PathBuf;
+use std::path::Path;
 
 use crate::command::{Command, Runtime};
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::refs::is_valid_ref_name;
 
 pub struct InitCommand;
 
+pub const DEFAULT_BRANCH: &str = "master";
+const DIRECTORIES: [&str; 5] = ["objects/info", "objects/pack", "refs/heads", "refs/tags", "hooks"];
+/// The files of the default template, used when no template directory is given.
+const DEFAULT_TEMPLATE: [(&str, &str); 2] = [
+    ("description", "Unnamed repository; edit this file 'description' to name the repository.\n"),
+    ("info/exclude", "# git ls-files --others --exclude-from=.git/info/exclude\n\
+                      # Lines that start with '#' are comments.\n\
+                      # For a project mostly in C, the following would be a good set of\n\
+                      # exclude patterns (uncomment them if you want to use them):\n\
+                      # *.[oa]\n\
+                      # *~\n"),
+];
+
 impl Command for InitCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit init [-q | --quiet] [--bare] [--template=<template-directory>] [-b <branch-name> | --initial-branch=<branch-name>] [<directory>]",
+    ], &[
+        Opt::long("template", "directory from which templates will be used").value("<template-directory>"),
+        Opt::long("bare", "create a bare repository"),
+        Opt::flag('b', "initial-branch", "override the name of the initial branch").value("<name>"),
+        Opt::flag('q', "quiet", "be quiet"),
+    ]);
+
     fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
-        let mut path =
-            if runtime.args.len() > 2 {
-                PathBuf::from(&runtime.args[2])
-            } else {
-                runtime.dir.clone()
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let directory = match parsed.args.as_slice() {
+            [] => runtime.dir.clone(),
+            [directory] => runtime.dir.join(directory),
+            _ => return Err(Self::USAGE.error()),
+        };
+        let bare = parsed.is_set("bare");
+        let path = match bare {
+            true => directory,
+            false => directory.join(".git"),
+        };
+        let branch = parsed.value("initial-branch").unwrap_or(DEFAULT_BRANCH);
+        if !is_valid_ref_name(&format!("refs/heads/{}", branch)) {
+            return Err(MinigitError::fatal(format!("fatal: invalid initial branch name: '{}'", branch)));
+        }
+
+        let reinitialized = create_git_directory(runtime, &path, bare, branch, parsed.value("template"))?;
+        if reinitialized && parsed.value("initial-branch").is_some() {
+            writeln!(&mut runtime.stderr, "warning: re-init: ignored --initial-branch={}", branch).unwrap();
+        }
+
+        if !parsed.is_set("quiet") {
+            let path = path.canonicalize().unwrap_or(path);
+            let message = match reinitialized {
+                true => "Reinitialized existing",
+                false => "Initialized empty",
             };
-        path.push(".git");
-        for dir in ["objects", "refs"].iter() {
-            let mut path = path.clone();
-            path.push(dir);
-            if let Err(e) = fs::create_dir_all(&path) {
-                return Err(MinigitError::new(format!("Couldn't create .git directory: {}", e)));
-            }
+            writeln!(&mut runtime.stdout, "{} Git repository in {}{}", message, path.display(), std::path::MAIN_SEPARATOR).unwrap();
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Creates the files and directories of a git directory, keeping those which already exist, and tells whether it was
+/// already a repository. Without a template directory, given by `template` or `GIT_TEMPLATE_DIR`, a default one is used.
+pub fn create_git_directory(runtime: &Runtime, path: &Path, bare: bool, branch: &str, template: Option<&str>) -> MinigitResult<bool> {
+    create_dir(path)?;
+    let reinitialized = path.join("HEAD").is_file();
+    // An empty template directory means no template at all.
+    match template.or_else(|| runtime.env.get("GIT_TEMPLATE_DIR").map(String::as_str)) {
+        Some("") => {}
+        Some(template) => copy_template(&runtime.dir.join(template), path)?,
+        None => {
+            for (name, content) in DEFAULT_TEMPLATE.iter() {
+                write_new_file(&path.join(name), content)?;
+            }
+        }
+    }
+    for dir in DIRECTORIES.iter() {
+        create_dir(&path.join(dir))?;
+    }
+    if !reinitialized {
+        write_new_file(&path.join("HEAD"), &format!("ref: refs/heads/{}\n", branch))?;
+    }
+    let mut config = format!("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = {}\n", bare);
+    if !bare {
+        config.push_str("\tlogallrefupdates = true\n");
+    }
+    write_new_file(&path.join("config"), &config)?;
+    Ok(reinitialized)
+}
+
+fn create_dir(path: &Path) -> MinigitResult<()> {
+    match fs::create_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot mkdir {}: {}", path.display(), e), e)),
+    }
+}
+
+/// Writes a file of the git directory unless it already exists, so that initializing again keeps the existing files.
+fn write_new_file(path: &Path, content: &str) -> MinigitResult<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir(parent)?;
+    }
+    match fs::write(path, content) {
+        Ok(()) => Ok(()),
+        Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot write {}: {}", path.display(), e), e)),
+    }
+}
+
+/// Copies the files of a template directory which don't exist yet in the git directory.
+fn copy_template(template: &Path, path: &Path) -> MinigitResult<()> {
+    let entries = match fs::read_dir(template) {
+        Ok(entries) => entries,
+        Err(e) => {
+            return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot read template directory '{}': {}", template.display(), e), e));
+        }
+    };
+    for entry in entries {
+        let entry = entry.map_err(|e| MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot read {}: {}", template.display(), e), e))?;
+        let target = path.join(entry.file_name());
+        if entry.path().is_dir() {
+            create_dir(&target)?;
+            copy_template(&entry.path(), &target)?;
+        } else if !target.exists() {
+            if let Err(e) = fs::copy(entry.path(), &target) {
+                return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot copy '{}' to '{}': {}", entry.path().display(),
+                                                                            target.display(), e), e));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::command::{Runtime, execute};
+    use crate::minigiterror::MinigitResult;
+    use crate::tests::*;
+
+    fn run(dir: &Path, args: &[&str]) -> MinigitResult<String> {
+        let mut stdout = Vec::new();
+        let result = {
+            let mut runtime = Runtime { dir: PathBuf::from(dir), stdout: Box::new(&mut stdout), stderr: Box::new(std::io::sink()), ..Runtime::default() };
+            runtime.args = [""].iter().chain(args.iter()).map(|arg| String::from(*arg)).collect();
+            execute(&mut runtime)
+        };
+        result.map(|_| String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_init_skeleton() {
+        run_test(|repo_path| {
+            let root = Path::new(repo_path);
+            let output = run(root, &["init", "-b", "main", "project"]).unwrap();
+            let git_path = root.join("project/.git").canonicalize().unwrap();
+            assert_eq!(format!("Initialized empty Git repository in {}/\n", git_path.display()), output);
+            for dir in ["hooks", "objects/info", "objects/pack", "refs/heads", "refs/tags"] {
+                assert!(git_path.join(dir).is_dir());
+            }
+            assert_eq!("ref: refs/heads/main\n", fs::read_to_string(git_path.join("HEAD")).unwrap());
+            assert!(fs::read_to_string(git_path.join("description")).unwrap().starts_with("Unnamed repository"));
+            assert!(git_path.join("info/exclude").is_file());
+            assert_eq!("[core]\n\trepositoryformatversion = 0\n\tfilemode = true\n\tbare = false\n\tlogallrefupdates = true\n",
+                       fs::read_to_string(git_path.join("config")).unwrap());
+
+            fs::write(git_path.join("description"), "mine\n").unwrap();
+            let output = run(root, &["init", "-q", "project"]).unwrap();
+            assert_eq!("", output);
+            assert_eq!("mine\n", fs::read_to_string(git_path.join("description")).unwrap());
+            assert_eq!("fatal: invalid initial branch name: 'a..b'", run(root, &["init", "--initial-branch=a..b", "other"]).err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_init_with_template() {
+        run_test(|repo_path| {
+            let root = Path::new(repo_path);
+            fs::create_dir_all(root.join("template/hooks")).unwrap();
+            fs::write(root.join("template/hooks/pre-commit"), "#!/bin/sh\n").unwrap();
+            fs::write(root.join("template/description"), "templated\n").unwrap();
+            run(root, &["init", "-q", "--template", "template", "project"]).unwrap();
+            let git_path = root.join("project/.git");
+            assert_eq!("templated\n", fs::read_to_string(git_path.join("description")).unwrap());
+            assert!(git_path.join("hooks/pre-commit").is_file());
+            assert!(!git_path.join("info/exclude").exists());
+        });
+    }
+
+    #[test]
+    fn test_bare_repository() {
+        run_test(|repo_path| {
+            let root = Path::new(repo_path);
+            let output = run(root, &["init", "--bare", "central.git"]).unwrap();
+            let bare_path = root.join("central.git").canonicalize().unwrap();
+            assert_eq!(format!("Initialized empty Git repository in {}/\n", bare_path.display()), output);
+            assert!(fs::read_to_string(bare_path.join("config")).unwrap().contains("\tbare = true\n"));
+            assert!(!bare_path.join(".git").exists());
+
+            assert_eq!("", run(&bare_path.join("refs"), &["for-each-ref"]).unwrap());
+            assert_eq!("fatal: this operation must be run in a work tree", run(&bare_path, &["status"]).err().unwrap().message);
+            assert_eq!("fatal: mixed reset is not allowed in a bare repository", run(&bare_path, &["reset"]).err().unwrap().message);
+        });
+    }
+}
@@ -0,0 +1,133 @@
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::mergebase::MergeBase;
+use crate::objectid::ObjectId;
+use crate::revision::Revision;
+
+pub struct MergeBaseCommand;
+
+#[derive(PartialEq)]
+enum Mode {
+    Default,
+    Octopus,
+    Independent,
+    IsAncestor,
+}
+
+impl Command for MergeBaseCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit merge-base [-a | --all] <commit> <commit>...",
+        "minigit merge-base [-a | --all] --octopus <commit>...",
+        "minigit merge-base --independent <commit>...",
+        "minigit merge-base --is-ancestor <commit> <commit>",
+    ], &[
+        Opt::flag('a', "all", "output all common ancestors"),
+        Opt::long("octopus", "find ancestors for a single n-way merge"),
+        Opt::long("independent", "list revs not reachable from others"),
+        Opt::long("is-ancestor", "is the first one ancestor of the other?"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let all = parsed.is_set("all");
+        let mode = match parsed.last_of(&["octopus", "independent", "is-ancestor"]) {
+            Some("octopus") => Mode::Octopus,
+            Some("independent") => Mode::Independent,
+            Some(_) => Mode::IsAncestor,
+            None => Mode::Default,
+        };
+        let revisions = parsed.args;
+        let valid_count = match mode {
+            Mode::Default => revisions.len() >= 2,
+            Mode::Octopus | Mode::Independent => !revisions.is_empty(),
+            Mode::IsAncestor => revisions.len() == 2,
+        };
+        if !valid_count || (all && (mode == Mode::Independent || mode == Mode::IsAncestor)) {
+            return Err(Self::USAGE.error());
+        }
+
+        let mut repository = runtime.repository()?;
+        let mut oids = Vec::new();
+        for revision in revisions.iter() {
+            oids.push(Revision::parse(revision)?.resolve_commit(&mut repository)?);
+        }
+        let mut merge_base = MergeBase::new(&mut repository);
+        let bases = match mode {
+            Mode::Default => merge_base.find(oids[0], &oids[1..])?,
+            Mode::Octopus => merge_base.find_octopus(&oids)?,
+            Mode::Independent => merge_base.independent(&oids)?,
+            Mode::IsAncestor => {
+                return match merge_base.is_ancestor(oids[0], oids[1])? {
+                    true => Ok(()),
+                    false => Err(MinigitError::new(String::new())),
+                };
+            }
+        };
+        print_bases(runtime, &bases, all || mode == Mode::Independent)
+    }
+}
+
+fn print_bases(runtime: &mut Runtime, bases: &[ObjectId], all: bool) -> MinigitResult<()> {
+    if bases.is_empty() {
+        return Err(MinigitError::new(String::new()));
+    }
+    let count = if all { bases.len() } else { 1 };
+    for base in bases.iter().take(count) {
+        writeln!(&mut runtime.stdout, "{}", base).unwrap();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    #[test]
+    fn test_merge_base_modes() {
+        crate::tests::run_test(|repo_path| {
+            let a = crate::tests::write_commit(repo_path, vec!(), "a", 1000, &[]);
+            let b = crate::tests::write_commit(repo_path, vec!(a), "b", 2000, &[]);
+            let c = crate::tests::write_commit(repo_path, vec!(a), "c", 3000, &[]);
+            let d = crate::tests::write_commit(repo_path, vec!(c), "d", 4000, &[]);
+            fs::write(format!("{}/.git/HEAD", repo_path), format!("{}\n", d)).unwrap();
+
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), b.to_hex(), String::from("HEAD")),
+                format!("{}\n", a));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), String::from("--octopus"), String::from("HEAD"), c.to_hex(), b.to_hex()),
+                format!("{}\n", a));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), String::from("--independent"), b.to_hex(), c.to_hex(), String::from("HEAD")),
+                format!("{}\n{}\n", b, d));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), String::from("--is-ancestor"), c.to_hex(), String::from("HEAD")),
+                String::new());
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), String::from("--is-ancestor"), b.to_hex(), String::from("HEAD")),
+                String::new());
+        });
+    }
+
+    #[test]
+    fn test_no_common_ancestor() {
+        crate::tests::run_test(|repo_path| {
+            let a = crate::tests::write_commit(repo_path, vec!(), "a", 1000, &[]);
+            let b = crate::tests::write_commit(repo_path, vec!(), "b", 1000, &[]);
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), String::from("--all"), a.to_hex(), b.to_hex()),
+                String::new());
+            crate::tests::execute_and_expect_error(
+                repo_path,
+                vec!(String::new(), String::from("merge-base"), a.to_hex()));
+        });
+    }
+}
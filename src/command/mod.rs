@@ -2,32 +2,148 @@ use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::path::PathBuf;
 
+use chrono::Local;
+
 use crate::command::add::AddCommand;
+use crate::command::clone::CloneCommand;
 use crate::command::commit::CommitCommand;
+use crate::command::for_each_ref::ForEachRefCommand;
+use crate::command::help::HelpCommand;
 use crate::command::init::InitCommand;
+use crate::command::merge_base::MergeBaseCommand;
+use crate::command::mv::MvCommand;
+use crate::command::pack_refs::PackRefsCommand;
+use crate::command::parseopt::Usage;
+use crate::command::reflog::ReflogCommand;
+use crate::command::reset::ResetCommand;
+use crate::command::restore::RestoreCommand;
+use crate::command::rev_list::RevListCommand;
+use crate::command::rev_parse::RevParseCommand;
+use crate::command::rm::RmCommand;
+use crate::command::show_ref::ShowRefCommand;
 use crate::command::status::StatusCommand;
+use crate::command::submodule::SubmoduleCommand;
+use crate::command::tag::TagCommand;
+use crate::command::update_ref::UpdateRefCommand;
+use crate::gitobject::Author;
 use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::repository::Repository;
 
 mod add;
+mod add_patch;
+mod clone;
 mod commit;
+mod for_each_ref;
+mod help;
 mod init;
+mod merge_base;
+mod mv;
+mod pack_refs;
+pub mod parseopt;
+mod reflog;
+mod reset;
+mod restore;
+mod rev_list;
+mod rev_parse;
+mod rm;
+mod show_ref;
 mod status;
+mod submodule;
+mod tag;
+mod update_ref;
 
 pub trait Command {
+    /// The usage lines and options of the command, printed by `-h` and along usage errors.
+    const USAGE: Usage;
+    /// Whether the command operates on the workspace, so that it fails in a bare repository.
+    const NEEDS_WORK_TREE: bool = false;
+
     fn execute(runtime: &mut Runtime) -> MinigitResult<()>;
 }
 
+pub struct CommandInfo {
+    pub name: &'static str,
+    /// The one-line description listed by `minigit help`.
+    pub summary: &'static str,
+    pub execute: fn(&mut Runtime) -> MinigitResult<()>,
+    pub usage: &'static Usage,
+    pub needs_work_tree: bool,
+}
+
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo { name: "add", summary: "Add file contents to the index", execute: AddCommand::execute, usage: &AddCommand::USAGE,
+                  needs_work_tree: AddCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "clone", summary: "Clone a repository into a new directory", execute: CloneCommand::execute, usage: &CloneCommand::USAGE,
+                  needs_work_tree: CloneCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "commit", summary: "Record changes to the repository", execute: CommitCommand::execute, usage: &CommitCommand::USAGE,
+                  needs_work_tree: CommitCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "for-each-ref", summary: "Output information on each ref", execute: ForEachRefCommand::execute, usage: &ForEachRefCommand::USAGE,
+                  needs_work_tree: ForEachRefCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "help", summary: "Display help information about minigit", execute: HelpCommand::execute, usage: &HelpCommand::USAGE,
+                  needs_work_tree: HelpCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "init", summary: "Create an empty Git repository", execute: InitCommand::execute, usage: &InitCommand::USAGE,
+                  needs_work_tree: InitCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "merge-base", summary: "Find as good common ancestors as possible for a merge", execute: MergeBaseCommand::execute, usage: &MergeBaseCommand::USAGE,
+                  needs_work_tree: MergeBaseCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "mv", summary: "Move or rename a file, a directory, or a symlink", execute: MvCommand::execute, usage: &MvCommand::USAGE,
+                  needs_work_tree: MvCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "pack-refs", summary: "Pack heads and tags for efficient repository access", execute: PackRefsCommand::execute, usage: &PackRefsCommand::USAGE,
+                  needs_work_tree: PackRefsCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "reflog", summary: "Manage reflog information", execute: ReflogCommand::execute, usage: &ReflogCommand::USAGE,
+                  needs_work_tree: ReflogCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "reset", summary: "Reset current HEAD to the specified state", execute: ResetCommand::execute, usage: &ResetCommand::USAGE,
+                  needs_work_tree: ResetCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "restore", summary: "Restore working tree files", execute: RestoreCommand::execute, usage: &RestoreCommand::USAGE,
+                  needs_work_tree: RestoreCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "rev-list", summary: "Lists commit objects in reverse chronological order", execute: RevListCommand::execute, usage: &RevListCommand::USAGE,
+                  needs_work_tree: RevListCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "rev-parse", summary: "Pick out and massage parameters", execute: RevParseCommand::execute, usage: &RevParseCommand::USAGE,
+                  needs_work_tree: RevParseCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "rm", summary: "Remove files from the working tree and from the index", execute: RmCommand::execute, usage: &RmCommand::USAGE,
+                  needs_work_tree: RmCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "show-ref", summary: "List references in a local repository", execute: ShowRefCommand::execute, usage: &ShowRefCommand::USAGE,
+                  needs_work_tree: ShowRefCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "status", summary: "Show the working tree status", execute: StatusCommand::execute, usage: &StatusCommand::USAGE,
+                  needs_work_tree: StatusCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "submodule", summary: "Initialize, update or inspect submodules", execute: SubmoduleCommand::execute, usage: &SubmoduleCommand::USAGE,
+                  needs_work_tree: SubmoduleCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "tag", summary: "Create, list, delete or verify a tag object", execute: TagCommand::execute, usage: &TagCommand::USAGE,
+                  needs_work_tree: TagCommand::NEEDS_WORK_TREE },
+    CommandInfo { name: "update-ref", summary: "Update the object name stored in a ref safely", execute: UpdateRefCommand::execute, usage: &UpdateRefCommand::USAGE,
+                  needs_work_tree: UpdateRefCommand::NEEDS_WORK_TREE },
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
 pub fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+    // Like `git -C <path>`, the command runs from another directory, each one being relative to the previous one.
+    while runtime.args.get(1).is_some_and(|arg| arg == "-C") {
+        match runtime.args.get(2) {
+            Some(path) => runtime.dir = runtime.dir.join(path),
+            None => return Err(MinigitError::usage(String::from("error: no directory given for -C\n\
+                                                                                      usage: minigit [-C <path>] <command> [<args>]"))),
+        }
+        runtime.args.drain(1..3);
+    }
     if runtime.args.len() == 1 {
         return Err(MinigitError::new(String::from("No command has been passed")));
     }
-    match runtime.args.get(1).unwrap().as_str() {
-        "add" => AddCommand::execute(runtime),
-        "commit" => CommitCommand::execute(runtime),
-        "init" => InitCommand::execute(runtime),
-        "status" => StatusCommand::execute(runtime),
-        unknown_command => Err(MinigitError::new(format!("Unknown git command {}", unknown_command))),
+    let name = runtime.args[1].clone();
+    let command = match find_command(&name) {
+        Some(command) => command,
+        None => return Err(MinigitError::new(format!("Unknown git command {}", name))),
+    };
+    // Like git, a lone `-h` shows the help of any command, even outside of a repository.
+    if runtime.args.len() == 3 && runtime.args[2] == "-h" {
+        writeln!(&mut runtime.stdout, "{}\n", command.usage.help()).unwrap();
+        return Err(MinigitError::usage(String::new()));
     }
+    if !["clone", "help", "init"].contains(&name.as_str()) && runtime.repository()?.is_bare() && command.needs_work_tree {
+        return Err(MinigitError::fatal(String::from("fatal: this operation must be run in a work tree")));
+    }
+    (command.execute)(runtime)
 }
 
 pub struct Runtime<'a> {
@@ -53,6 +169,11 @@ impl<'a> Default for Runtime<'a> {
 }
 
 impl<'a> Runtime<'a> {
+    /// Opens the repository the command operates on, found from the directory it runs from.
+    pub fn repository(&self) -> MinigitResult<Repository> {
+        Repository::discover(&self.dir, &self.env)
+    }
+
     pub fn get_env_var(&self, key: &str) -> MinigitResult<&String> {
         match self.env.get(key) {
             Some(value) => Ok(value),
@@ -60,6 +181,18 @@ impl<'a> Runtime<'a> {
         }
     }
 
+    /// The identity recorded as author of commits and tags and in the reflog, taken from the environment.
+    pub fn identity(&self) -> MinigitResult<Author> {
+        let name = self.get_env_var("GIT_AUTHOR_NAME")?;
+        let email = self.get_env_var("GIT_AUTHOR_EMAIL")?;
+        Ok(Author::new(name, email, Local::now()))
+    }
+
+    /// The identity recorded in the reflog by commands which don't create objects, which is empty when it isn't set.
+    pub fn reflog_identity(&self) -> Author {
+        self.identity().unwrap_or_else(|_| Author::new("", "", Local::now()))
+    }
+
     pub fn read_from_stdin(&mut self) -> MinigitResult<String> {
         let mut input = String::new();
         if let Err(e) = self.stdin.read_to_string(&mut input) {
@@ -67,4 +200,104 @@ impl<'a> Runtime<'a> {
         }
         Ok(input)
     }
-}
\ No newline at end of file
+
+    /// Reads one line without its terminator, byte by byte so that the following lines stay available. Returns none at
+    /// the end of the input.
+    pub fn read_line_from_stdin(&mut self) -> MinigitResult<Option<String>> {
+        let mut line = Vec::new();
+        let mut byte = [0u8];
+        loop {
+            match self.stdin.read(&mut byte) {
+                Ok(0) if line.is_empty() => return Ok(None),
+                Ok(0) => break,
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => line.push(byte[0]),
+                Err(e) => return Err(MinigitError::new(format!("Error trying to read from stdin: {}", e))),
+            }
+        }
+        Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::iter;
+    use std::path::Path;
+
+    use crate::minigiterror::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn test_error_kinds() {
+        crate::tests::run_test(|repo_path| {
+            let error = crate::tests::execute_and_get_result(repo_path, vec!(String::new(), String::from("rm"), String::from("--bad")));
+            assert_eq!(ErrorKind::Usage, error.err().unwrap().kind);
+            let error = crate::tests::execute_and_get_result(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("missing")));
+            assert_eq!(ErrorKind::Fatal, error.err().unwrap().kind);
+
+            let outside_path = format!("{}-outside", repo_path);
+            fs::create_dir(&outside_path).unwrap();
+            let error = crate::tests::execute_and_get_result(&outside_path, vec!(String::new(), String::from("status")));
+            fs::remove_dir(&outside_path).unwrap();
+            let error = error.err().unwrap();
+            assert_eq!(ErrorKind::NotARepository, error.kind);
+            assert_eq!("fatal: not a git repository (or any of the parent directories): .git", error.message);
+        });
+    }
+
+    fn run_in(dir: &Path, env: &[(&str, &str)], args: &[&str]) -> MinigitResult<String> {
+        let mut stdout = Vec::new();
+        let result = {
+            let mut runtime = Runtime { dir: PathBuf::from(dir), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = iter::once("minigit").chain(args.iter().copied()).map(String::from).collect();
+            runtime.env.extend(env.iter().map(|(key, value)| (String::from(*key), String::from(*value))));
+            execute(&mut runtime)
+        };
+        result.map(|_| String::from_utf8(stdout).unwrap())
+    }
+
+    #[test]
+    fn test_repository_discovery() {
+        crate::tests::run_test(|repo_path| {
+            let root = Path::new(repo_path);
+            fs::create_dir_all(root.join("dir/sub")).unwrap();
+            fs::write(root.join("dir/sub/a.txt"), "a").unwrap();
+            fs::write(root.join("dir/b.txt"), "b").unwrap();
+            fs::write(root.join("c.txt"), "c").unwrap();
+            let dir = root.join("dir");
+
+            assert_eq!("?? dir/sub/a.txt\n", run_in(&dir, &[], &["status", "sub"]).unwrap());
+            run_in(&dir.join("sub"), &[], &["add", "a.txt", "../b.txt", ":(top)c.txt"]).unwrap();
+            crate::tests::assert_index(repo_path, vec!((0o100644, String::from("c.txt")), (0o100644, String::from("dir/b.txt")),
+                                                       (0o100644, String::from("dir/sub/a.txt"))));
+            assert_eq!("rm 'dir/b.txt'\n", run_in(root, &[], &["-C", "dir", "-C", "sub", "rm", "-n", "--cached", "../b.txt"]).unwrap());
+            assert!(run_in(&dir, &[], &["add", "../../outside"]).err().unwrap().message.contains("is outside repository"));
+
+            let error = run_in(&dir, &[("GIT_CEILING_DIRECTORIES", repo_path)], &["status"]).err().unwrap();
+            assert_eq!(ErrorKind::NotARepository, error.kind);
+            assert_eq!("", run_in(root, &[("GIT_CEILING_DIRECTORIES", repo_path)], &["status"]).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_environment_variables() {
+        crate::tests::run_test(|repo_path| {
+            let root = Path::new(repo_path);
+            let outside_path = PathBuf::from(format!("{}-outside", repo_path));
+            fs::create_dir(&outside_path).unwrap();
+            fs::write(root.join("a.txt"), "a").unwrap();
+            let git_dir = root.join(".git");
+            let env = [("GIT_DIR", git_dir.to_str().unwrap()), ("GIT_WORK_TREE", repo_path), ("GIT_INDEX_FILE", "other-index")];
+            let result = run_in(&outside_path, &env, &["add", "a.txt"]);
+            let other_index_exists = outside_path.join("other-index").is_file();
+            fs::remove_dir_all(&outside_path).unwrap();
+            result.unwrap();
+            assert!(other_index_exists);
+            assert!(!git_dir.join("index").exists());
+
+            let error = run_in(root, &[("GIT_DIR", "missing")], &["status"]).err().unwrap();
+            assert_eq!("fatal: not a git repository: 'missing'", error.message);
+        });
+    }
+}
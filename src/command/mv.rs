@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::repository::Repository;
+
+pub struct MvCommand;
+
+struct Options {
+    force: bool,
+    dry_run: bool,
+    skip_errors: bool,
+    verbose: bool,
+}
+
+impl Command for MvCommand {
+    const USAGE: Usage = Usage::new(&["minigit mv [<options>] <source>... <destination>"], &[
+        Opt::flag('v', "verbose", "be verbose"),
+        Opt::flag('n', "dry-run", "dry run"),
+        Opt::flag('f', "force", "force move/rename even if target exists"),
+        Opt::short('k', "skip move/rename errors"),
+    ]);
+    const NEEDS_WORK_TREE: bool = true;
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let options = Options {
+            force: parsed.is_set("force"),
+            dry_run: parsed.is_set("dry-run"),
+            skip_errors: parsed.is_set("k"),
+            verbose: parsed.is_set("verbose"),
+        };
+        let mut paths = parsed.args;
+        if paths.len() < 2 {
+            return Err(Self::USAGE.error());
+        }
+
+        let mut repository = runtime.repository()?;
+        repository.index()?.load_for_update()?;
+        let destination = paths.pop().unwrap();
+        let moves = plan_moves(&mut repository, &paths, &destination, &options)?;
+        for (source, target) in moves {
+            if options.verbose || options.dry_run {
+                writeln!(&mut runtime.stdout, "Renaming {} to {}", source.display(), target.display()).unwrap();
+            }
+            if !options.dry_run {
+                let root = repository.workspace().get_path().to_path_buf();
+                move_path(&mut repository, &root, &source, &target)?;
+            }
+        }
+        repository.index()?.write_updates()?;
+        Ok(())
+    }
+}
+
+/// Checks every source, then returns the renames to apply. Into an existing directory, each source keeps its name.
+fn plan_moves(repository: &mut Repository, sources: &[String], destination: &str, options: &Options) -> MinigitResult<Vec<(PathBuf, PathBuf)>> {
+    let destination_path = resolve_path(repository, destination)?;
+    let into_directory = repository.workspace().exists(&destination_path) && repository.workspace().is_dir(&destination_path)?;
+    if sources.len() > 1 && !into_directory {
+        return Err(MinigitError::fatal(format!("fatal: destination '{}' is not a directory", destination)));
+    }
+    let mut moves = Vec::new();
+    for source in sources {
+        let source_path = resolve_path(repository, source)?;
+        let target = match into_directory {
+            true => destination_path.join(source_path.file_name().unwrap_or_default()),
+            false => destination_path.clone(),
+        };
+        let error = |reason: &str| MinigitError::fatal(format!("fatal: {}, source={}, destination={}", reason, source_path.display(), target.display()));
+        let result = if !repository.workspace().exists(&source_path) {
+            Err(error("bad source"))
+        } else if !repository.index()?.is_path_tracked(&source_path) || repository.index()?.is_unmerged(&source_path) {
+            Err(error("not under version control"))
+        } else if repository.index()?.get_entries().iter().any(|entry| entry.get_stage() > 0 && entry.get_path().starts_with(&source_path)) {
+            Err(error("conflicted"))
+        } else if target.starts_with(&source_path) {
+            Err(error("can not move directory into itself"))
+        } else if repository.workspace().exists(&target) && !(options.force && repository.workspace().is_file(&target)?) {
+            Err(error("destination exists"))
+        } else if !target.parent().is_none_or(|parent| parent.as_os_str().is_empty() || repository.workspace().exists(parent)) {
+            Err(error("destination directory does not exist"))
+        } else {
+            Ok((source_path, target))
+        };
+        match result {
+            Ok(path_move) => moves.push(path_move),
+            Err(_) if options.skip_errors => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(moves)
+}
+
+fn resolve_path(repository: &mut Repository, path: &str) -> MinigitResult<PathBuf> {
+    let workspace = repository.workspace();
+    workspace.resolve_path(path, false)
+        .ok_or_else(|| MinigitError::fatal(format!("fatal: '{}' is outside repository at '{}'", path, workspace.get_path().display())))
+}
+
+/// Renames a file or a directory in the workspace, then moves its entries in the index.
+fn move_path(repository: &mut Repository, root: &Path, source: &Path, target: &Path) -> MinigitResult<()> {
+    if let Err(e) = fs::rename(root.join(source), root.join(target)) {
+        return Err(MinigitError::fatal(format!("fatal: renaming '{}' failed: {}", source.display(), e)));
+    }
+    let entries: Vec<(PathBuf, _)> = repository.index()?.get_entries().iter()
+        .filter(|entry| entry.get_path().starts_with(source))
+        .map(|entry| (PathBuf::from(entry.get_path()), *entry.get_oid()))
+        .collect();
+    repository.index()?.remove(target);
+    for (path, oid) in entries {
+        // Joining an empty path would add a trailing separator.
+        let new_path = match path.strip_prefix(source).unwrap() {
+            relative_path if relative_path.as_os_str().is_empty() => PathBuf::from(target),
+            relative_path => target.join(relative_path),
+        };
+        let metadata = repository.workspace().get_metadata(&new_path)?;
+        repository.index()?.remove(&path);
+        repository.index()?.add(&new_path, oid, metadata);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gitobject::{Blob, GitObject};
+    use crate::tests::*;
+    use crate::workspace::MinigitMetadata;
+
+    use super::*;
+
+    fn mv(repo_path: &str, args: &[&str]) -> MinigitResult<String> {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("mv"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            crate::command::execute(&mut runtime)?;
+        }
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn write_and_add(repo_path: &str, path: &str, content: &str) {
+        let full_path = PathBuf::from(repo_path).join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(path)));
+    }
+
+    fn read_file(repo_path: &str, path: &str) -> String {
+        fs::read_to_string(PathBuf::from(repo_path).join(path)).unwrap()
+    }
+
+    #[test]
+    fn test_move_files() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.txt", "b");
+            fs::create_dir(PathBuf::from(repo_path).join("dir")).unwrap();
+
+            assert_eq!("", mv(repo_path, &["a.txt", "renamed.txt"]).unwrap());
+            assert_eq!("a", read_file(repo_path, "renamed.txt"));
+            assert!(!PathBuf::from(repo_path).join("a.txt").exists());
+            assert_eq!("Renaming renamed.txt to dir/renamed.txt\nRenaming b.txt to dir/b.txt\n",
+                       mv(repo_path, &["-v", "renamed.txt", "b.txt", "dir"]).unwrap());
+            assert_index(repo_path, vec!((0o100644, String::from("dir/b.txt")), (0o100644, String::from("dir/renamed.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_move_directory() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "dir/a.txt", "a");
+            write_and_add(repo_path, "dir/nested/b.txt", "b");
+            write_and_add(repo_path, "c.txt", "c");
+            mv(repo_path, &["dir", "moved"]).unwrap();
+            assert_eq!("b", read_file(repo_path, "moved/nested/b.txt"));
+            assert_index(repo_path, vec!((0o100644, String::from("c.txt")), (0o100644, String::from("moved/a.txt")),
+                                         (0o100644, String::from("moved/nested/b.txt"))));
+            // Dry runs only print the renames, and -k skips the sources which cannot be moved.
+            fs::create_dir(PathBuf::from(repo_path).join("target")).unwrap();
+            assert_eq!("Renaming moved to target/moved\n", mv(repo_path, &["-n", "-k", "moved", "missing", "target"]).unwrap());
+            assert!(PathBuf::from(repo_path).join("moved").exists());
+        });
+    }
+
+    #[test]
+    fn test_move_errors() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.txt", "b");
+            write_and_add(repo_path, "dir/c.txt", "c");
+            fs::write(PathBuf::from(repo_path).join("untracked.txt"), "untracked").unwrap();
+
+            assert_eq!("fatal: bad source, source=missing.txt, destination=x.txt", mv(repo_path, &["missing.txt", "x.txt"]).err().unwrap().message);
+            assert_eq!("fatal: not under version control, source=untracked.txt, destination=x.txt",
+                       mv(repo_path, &["untracked.txt", "x.txt"]).err().unwrap().message);
+            assert_eq!("fatal: destination exists, source=a.txt, destination=b.txt", mv(repo_path, &["a.txt", "b.txt"]).err().unwrap().message);
+            assert_eq!("fatal: can not move directory into itself, source=dir, destination=dir/dir", mv(repo_path, &["dir", "dir"]).err().unwrap().message);
+            assert_eq!("fatal: destination directory does not exist, source=a.txt, destination=missing/a.txt",
+                       mv(repo_path, &["a.txt", "missing/a.txt"]).err().unwrap().message);
+            assert_eq!("fatal: destination 'x' is not a directory", mv(repo_path, &["a.txt", "b.txt", "x"]).err().unwrap().message);
+
+            mv(repo_path, &["-f", "a.txt", "b.txt"]).unwrap();
+            assert_eq!("a", read_file(repo_path, "b.txt"));
+            assert_index(repo_path, vec!((0o100644, String::from("b.txt")), (0o100644, String::from("dir/c.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_refuse_to_move_unmerged_path() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "base");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut index = repository.index_take().unwrap();
+            index.load_for_update().unwrap();
+            for (stage, content) in ["base", "ours", "theirs"].iter().enumerate() {
+                let mut blob = Blob::new(content.as_bytes().to_vec());
+                repository.database().store(&mut blob).unwrap();
+                index.add_unmerged(Path::new("a.txt"), stage as u8 + 1, *blob.get_oid(), MinigitMetadata::from_mode(0o100644));
+            }
+            index.write_updates().unwrap();
+            drop(index);
+
+            assert_eq!("fatal: not under version control, source=a.txt, destination=b.txt", mv(repo_path, &["a.txt", "b.txt"]).err().unwrap().message);
+            assert!(PathBuf::from(repo_path).join("a.txt").exists());
+            fs::create_dir(PathBuf::from(repo_path).join("dir")).unwrap();
+            fs::rename(PathBuf::from(repo_path).join("a.txt"), PathBuf::from(repo_path).join("dir/a.txt")).unwrap();
+            let mut index = repository.index_take().unwrap();
+            index.load_for_update().unwrap();
+            let entries: Vec<_> = index.get_entries().iter().map(|entry| (*entry.get_oid(), entry.get_stage())).collect();
+            index.remove(Path::new("a.txt"));
+            for (oid, stage) in entries {
+                index.add_unmerged(Path::new("dir/a.txt"), stage, oid, MinigitMetadata::from_mode(0o100644));
+            }
+            index.write_updates().unwrap();
+            drop(index);
+            assert_eq!("fatal: conflicted, source=dir, destination=moved", mv(repo_path, &["dir", "moved"]).err().unwrap().message);
+            let index = Repository::new(PathBuf::from(repo_path)).index_take().unwrap().load_and_get_entries().unwrap();
+            assert_eq!(vec!(1, 2, 3), index.iter().map(|entry| entry.get_stage()).collect::<Vec<u8>>());
+        });
+    }
+}
@@ -0,0 +1,132 @@
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::MinigitResult;
+use crate::database::Database;
+use crate::refs::Refs;
+
+pub struct PackRefsCommand;
+
+impl Command for PackRefsCommand {
+    const USAGE: Usage = Usage::new(&["minigit pack-refs [--all] [--no-prune]"], &[
+        Opt::long("all", "pack everything"),
+        Opt::long("prune", "prune loose refs (default)"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        if !parsed.args.is_empty() {
+            return Err(Self::USAGE.error());
+        }
+        let all = parsed.is_set("all");
+        let prune = parsed.get_flag("prune").unwrap_or(true);
+        let repository = runtime.repository()?;
+        let database = Database::new(repository.get_objects_path().to_path_buf());
+        let refs = Refs::new(repository.get_path().to_path_buf());
+        refs.pack_refs(all, prune, &|oid| {
+            match database.read_type(oid)?.as_str() {
+                "tag" => Ok(Some(database.peel(oid)?)),
+                _ => Ok(None),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::objectid::ObjectId;
+    use crate::repository::Repository;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn git_path(repo_path: &str) -> PathBuf {
+        PathBuf::from(repo_path).join(".git")
+    }
+
+    fn create_ref(repo_path: &str, full_name: &str, oid: ObjectId) {
+        let path = git_path(repo_path).join(full_name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", oid)).unwrap();
+    }
+
+    fn rev_parse(repo_path: &str, revision: &str) -> String {
+        execute_and_get_stdout(repo_path, vec!(String::new(), String::from("rev-parse"), String::from(revision)))
+    }
+
+    #[test]
+    fn test_pack_all_refs() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            create_ref(repo_path, "refs/tags/v1.0", first);
+            create_ref(repo_path, "refs/heads/feature/one", first);
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("pack-refs"), String::from("--all")));
+
+            assert_eq!(format!("# pack-refs with: peeled fully-peeled sorted \n{} refs/heads/feature/one\n{} refs/heads/master\n{} refs/tags/v1.0\n", first, second, first),
+                       fs::read_to_string(git_path(repo_path).join("packed-refs")).unwrap());
+            assert!(!git_path(repo_path).join("refs/heads/master").exists());
+            assert!(!git_path(repo_path).join("refs/heads/feature").exists());
+            assert!(git_path(repo_path).join("refs/heads").is_dir());
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "HEAD"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "v1.0"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "feature/one"));
+
+            // A new commit writes a loose ref, which takes precedence over the packed one.
+            let third = commit(repo_path, "third");
+            assert_eq!(format!("{}\n", third), rev_parse(repo_path, "master"));
+            let refs = Repository::new(PathBuf::from(repo_path)).refs().list_refs("refs/heads/").unwrap();
+            assert_eq!(vec!((String::from("refs/heads/feature/one"), first), (String::from("refs/heads/master"), third)), refs);
+        });
+    }
+
+    #[test]
+    fn test_pack_tags_only_by_default() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            create_ref(repo_path, "refs/tags/v1.0", first);
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("pack-refs")));
+            assert_eq!(format!("# pack-refs with: peeled fully-peeled sorted \n{} refs/tags/v1.0\n", first),
+                       fs::read_to_string(git_path(repo_path).join("packed-refs")).unwrap());
+            assert!(git_path(repo_path).join("refs/heads/master").exists());
+
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("pack-refs"), String::from("--all"), String::from("--no-prune")));
+            assert!(git_path(repo_path).join("refs/heads/master").exists());
+            assert!(fs::read_to_string(git_path(repo_path).join("packed-refs")).unwrap().contains("refs/heads/master"));
+        });
+    }
+
+    #[test]
+    fn test_read_and_delete_refs_packed_by_git() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            fs::write(git_path(repo_path).join("packed-refs"), format!("# pack-refs with: peeled fully-peeled sorted \n\
+                                                                     {} refs/heads/topic\n\
+                                                                     {} refs/tags/v1.0\n\
+                                                                     ^{}\n", first, second, first)).unwrap();
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "topic"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "v1.0"));
+
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let identity = crate::gitobject::Author::new("A U Thor", "author@example.com", chrono::Local::now());
+            let mut transaction = repository.refs().transaction(&identity);
+            transaction.delete("refs/heads/topic", Some(first), "");
+            transaction.commit().unwrap();
+            assert_eq!(format!("# pack-refs with: peeled fully-peeled sorted \n{} refs/tags/v1.0\n^{}\n", second, first),
+                       fs::read_to_string(git_path(repo_path).join("packed-refs")).unwrap());
+            execute_and_expect_error(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("topic")));
+        });
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        run_test(|repo_path| {
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("pack-refs"), String::from("--prune-all")),
+                                             format!("error: unknown option `prune-all'\n{}", PackRefsCommand::USAGE.help()));
+        });
+    }
+}
@@ -0,0 +1,406 @@
+use std::io::Write;
+
+use crate::command::Runtime;
+use crate::minigiterror::{MinigitError, MinigitResult};
+
+/// Whether an option takes no value, like `--force`, requires one, like `-m <message>`, or may be given one, like
+/// `--abbrev[=<n>]`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Flag,
+    Value,
+    OptionalValue,
+}
+
+/// An option of a command, with the text describing it in the help.
+///
+/// A long option is given as `--name`, `--name=value` or `--name value`, and its `--no-name` form resets it. Short
+/// options are given as `-n`, `-nvalue` or `-n value`, and flags can be bundled like `-fq`. An optional value is only
+/// taken when it is attached, as in `--abbrev=10` or `-n5`.
+#[derive(Clone, Copy, Debug)]
+pub struct Opt {
+    pub short: Option<char>,
+    pub long: Option<&'static str>,
+    pub arity: Arity,
+    pub value_name: &'static str,
+    pub help: &'static str,
+    /// The value taken when the option is the last argument, like `HEAD` for `--contains`.
+    pub last_arg_default: Option<&'static str>,
+    /// Whether the option is also kept among the arguments, where its position matters, like `--not` among revisions.
+    pub in_args: bool,
+    /// Whether the value can also be given as a number alone, like `-5` for `-n 5`.
+    pub number: bool,
+}
+
+impl Opt {
+    pub const fn flag(short: char, long: &'static str, help: &'static str) -> Opt {
+        Opt { short: Some(short), long: Some(long), arity: Arity::Flag, value_name: "", help, last_arg_default: None, in_args: false, number: false }
+    }
+
+    pub const fn short(short: char, help: &'static str) -> Opt {
+        Opt { short: Some(short), long: None, arity: Arity::Flag, value_name: "", help, last_arg_default: None, in_args: false, number: false }
+    }
+
+    pub const fn long(long: &'static str, help: &'static str) -> Opt {
+        Opt { short: None, long: Some(long), arity: Arity::Flag, value_name: "", help, last_arg_default: None, in_args: false, number: false }
+    }
+
+    pub const fn value(self, value_name: &'static str) -> Opt {
+        Opt { arity: Arity::Value, value_name, ..self }
+    }
+
+    pub const fn optional_value(self, value_name: &'static str) -> Opt {
+        Opt { arity: Arity::OptionalValue, value_name, ..self }
+    }
+
+    pub const fn last_arg_default(self, value: &'static str) -> Opt {
+        Opt { last_arg_default: Some(value), ..self }
+    }
+
+    pub const fn in_args(self) -> Opt {
+        Opt { in_args: true, ..self }
+    }
+
+    pub const fn number(self) -> Opt {
+        Opt { number: true, ..self }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.long == Some(name) || (name.chars().count() == 1 && self.short == name.chars().next())
+    }
+
+    /// The left column of the help, like `-m, --message <message>` or `--abbrev[=<n>]`.
+    fn synopsis(&self) -> String {
+        let mut synopsis = match (self.short, self.long) {
+            (Some(short), Some(long)) => format!("-{}, --{}", short, long),
+            (Some(short), None) => format!("-{}", short),
+            (None, Some(long)) => format!("--{}", long),
+            (None, None) => String::new(),
+        };
+        if self.number {
+            synopsis = format!("-<n>, {}", synopsis);
+        }
+        match self.arity {
+            Arity::Flag => {}
+            Arity::Value => synopsis.push_str(&format!(" {}", self.value_name)),
+            Arity::OptionalValue if self.long.is_some() => synopsis.push_str(&format!("[={}]", self.value_name)),
+            Arity::OptionalValue => synopsis.push_str(&format!("[{}]", self.value_name)),
+        }
+        synopsis
+    }
+}
+
+/// How a command is called: the usage lines and the options it accepts, from which its help is generated.
+pub struct Usage {
+    pub lines: &'static [&'static str],
+    pub options: &'static [Opt],
+    /// Whether the options end at the first argument which is not one, like a subcommand whose options follow.
+    pub stop_at_non_option: bool,
+}
+
+/// The width of the column of options in the help, the descriptions being aligned after it.
+const OPTIONS_WIDTH: usize = 26;
+
+impl Usage {
+    pub const fn new(lines: &'static [&'static str], options: &'static [Opt]) -> Usage {
+        Usage { lines, options, stop_at_non_option: false }
+    }
+
+    pub const fn stop_at_non_option(self) -> Usage {
+        Usage { stop_at_non_option: true, ..self }
+    }
+
+    /// The text printed by `-h`, with the usage lines and a description of each option.
+    pub fn help(&self) -> String {
+        let mut help = String::new();
+        for (index, line) in self.lines.iter().enumerate() {
+            help.push_str(if index == 0 { "usage: " } else { "\n   or: " });
+            help.push_str(line);
+        }
+        if !self.options.is_empty() {
+            help.push('\n');
+        }
+        for option in self.options.iter() {
+            let synopsis = format!("    {}", option.synopsis());
+            match synopsis.len() < OPTIONS_WIDTH - 1 {
+                true => help.push_str(&format!("\n{:width$}{}", synopsis, option.help, width = OPTIONS_WIDTH)),
+                false => help.push_str(&format!("\n{}\n{:width$}{}", synopsis, "", option.help, width = OPTIONS_WIDTH)),
+            }
+        }
+        help
+    }
+
+    /// The error for a command line which doesn't match the usage.
+    pub fn error(&self) -> MinigitError {
+        MinigitError::usage(self.help())
+    }
+
+    /// A usage error explained by a message, like an unknown option.
+    pub fn error_with(&self, message: &str) -> MinigitError {
+        MinigitError::usage(format!("error: {}\n{}", message, self.help()))
+    }
+
+    /// Separates the options from the other arguments. With `-h` or `--help`, the help is printed and an error without
+    /// message is returned, so that the command stops with the exit code of usage errors.
+    pub fn parse(&self, runtime: &mut Runtime, args: &[String]) -> MinigitResult<ParsedOptions> {
+        let mut parsed = ParsedOptions { given: Vec::new(), args: Vec::new(), separator: None };
+        let mut index = 0;
+        while index < args.len() {
+            let arg = args[index].as_str();
+            index += 1;
+            if arg == "--" {
+                parsed.separator = Some(parsed.args.len());
+                parsed.args.extend(args[index..].iter().cloned());
+                break;
+            }
+            if arg == "-h" || arg == "--help" {
+                writeln!(&mut runtime.stdout, "{}\n", self.help()).unwrap();
+                return Err(MinigitError::usage(String::new()));
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                self.parse_long(long, args, &mut index, &mut parsed)?;
+            } else if let Some(option) = self.find_number(arg) {
+                parsed.given.push(GivenOption { option: *option, value: Some(String::from(&arg[1..])), negated: false });
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                self.parse_short(&arg[1..], args, &mut index, &mut parsed)?;
+            } else if self.stop_at_non_option {
+                parsed.args.extend(args[index - 1..].iter().cloned());
+                break;
+            } else {
+                parsed.args.push(String::from(arg));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn parse_long(&self, long: &str, args: &[String], index: &mut usize, parsed: &mut ParsedOptions) -> MinigitResult<()> {
+        let (name, inline_value) = match long.find('=') {
+            Some(equal) => (&long[..equal], Some(&long[equal + 1..])),
+            None => (long, None),
+        };
+        // An option whose name starts with `no-`, like `--no-contains`, wins over the negation of another one.
+        let (option, negated) = match self.find_long(name) {
+            Some(option) => (option, false),
+            None => match name.strip_prefix("no-").and_then(|name| self.find_long(name)) {
+                Some(option) => (option, true),
+                None => return Err(self.error_with(&format!("unknown option `{}'", name))),
+            },
+        };
+        if option.in_args {
+            parsed.args.push(format!("--{}", long));
+        }
+        if negated || option.arity == Arity::Flag {
+            return match inline_value {
+                Some(_) => Err(self.error_with(&format!("option `{}' takes no value", name))),
+                None => {
+                    parsed.given.push(GivenOption { option: *option, value: None, negated });
+                    Ok(())
+                }
+            };
+        }
+        let value = match (inline_value, option.arity) {
+            (Some(value), _) => Some(String::from(value)),
+            (None, Arity::OptionalValue) => None,
+            (None, _) => Some(take_value(args, index, option).ok_or_else(|| self.error_with(&format!("option `{}' requires a value", name)))?),
+        };
+        parsed.given.push(GivenOption { option: *option, value, negated: false });
+        Ok(())
+    }
+
+    /// Parses bundled short options like `-fq`, where an option taking a value takes the rest of the argument.
+    fn parse_short(&self, shorts: &str, args: &[String], index: &mut usize, parsed: &mut ParsedOptions) -> MinigitResult<()> {
+        for (offset, short) in shorts.char_indices() {
+            let option = match self.options.iter().find(|option| option.short == Some(short)) {
+                Some(option) => option,
+                None => return Err(self.error_with(&format!("unknown switch `{}'", short))),
+            };
+            let rest = &shorts[offset + short.len_utf8()..];
+            let value = match option.arity {
+                Arity::Flag => None,
+                _ if !rest.is_empty() => Some(String::from(rest)),
+                Arity::OptionalValue => None,
+                Arity::Value => Some(take_value(args, index, option).ok_or_else(|| self.error_with(&format!("switch `{}' requires a value", short)))?),
+            };
+            let takes_rest = value.is_some() || option.arity == Arity::OptionalValue;
+            parsed.given.push(GivenOption { option: *option, value, negated: false });
+            if takes_rest {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn find_long(&self, name: &str) -> Option<&Opt> {
+        self.options.iter().find(|option| option.long == Some(name))
+    }
+
+    /// The option taking a number alone, for an argument like `-5`.
+    fn find_number(&self, arg: &str) -> Option<&Opt> {
+        match arg.len() > 1 && arg.starts_with('-') && arg[1..].bytes().all(|byte| byte.is_ascii_digit()) {
+            true => self.options.iter().find(|option| option.number),
+            false => None,
+        }
+    }
+}
+
+fn take_value(args: &[String], index: &mut usize, option: &Opt) -> Option<String> {
+    match args.get(*index) {
+        Some(value) => {
+            *index += 1;
+            Some(value.clone())
+        }
+        None => option.last_arg_default.map(String::from),
+    }
+}
+
+struct GivenOption {
+    option: Opt,
+    value: Option<String>,
+    negated: bool,
+}
+
+/// The options found on a command line, named by their long name or else their short letter, and the other arguments.
+pub struct ParsedOptions {
+    given: Vec<GivenOption>,
+    pub args: Vec<String>,
+    /// The position in `args` of the first argument given after `--`, if there was one.
+    pub separator: Option<usize>,
+}
+
+impl ParsedOptions {
+    /// Whether the option was given and not negated afterwards.
+    pub fn is_set(&self, name: &str) -> bool {
+        self.get_flag(name) == Some(true)
+    }
+
+    /// `Some(false)` when the last occurrence of the option is its `--no-` form.
+    pub fn get_flag(&self, name: &str) -> Option<bool> {
+        self.given.iter().rev().find(|given| given.option.matches(name)).map(|given| !given.negated)
+    }
+
+    /// The value of the last occurrence of the option.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name).pop()
+    }
+
+    /// The values of every occurrence of the option since its last `--no-` form.
+    pub fn values(&self, name: &str) -> Vec<&str> {
+        let mut values = Vec::new();
+        for given in self.given.iter().filter(|given| given.option.matches(name)) {
+            match (given.negated, &given.value) {
+                (true, _) => values.clear(),
+                (false, Some(value)) => values.push(value.as_str()),
+                (false, None) => {}
+            }
+        }
+        values
+    }
+
+    /// The option given last among some exclusive ones, like `--soft`, `--mixed` and `--hard`.
+    pub fn last_of<'a>(&self, names: &[&'a str]) -> Option<&'a str> {
+        self.given.iter().rev()
+            .filter(|given| !given.negated)
+            .find_map(|given| names.iter().find(|name| given.option.matches(name)).copied())
+    }
+
+    /// The occurrences of some options sharing a setting, like `--hash[=<n>]` and `--abbrev[=<n>]`, in the order
+    /// they were given, with the name they are designated by and their value.
+    pub fn occurrences<'a>(&self, names: &[&'a str]) -> Vec<(&'a str, Option<&str>)> {
+        self.given.iter()
+            .filter(|given| !given.negated)
+            .filter_map(|given| names.iter().find(|name| given.option.matches(name)).map(|name| (*name, given.value.as_deref())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::minigiterror::ErrorKind;
+
+    use super::*;
+
+    const OPTIONS: &[Opt] = &[
+        Opt::flag('n', "dry-run", "dry run"),
+        Opt::flag('q', "quiet", "do not list removed files"),
+        Opt::long("cached", "only remove from the index"),
+        Opt::short('r', "allow recursive removal"),
+        Opt::flag('m', "message", "use the given message").value("<message>"),
+        Opt::long("abbrev", "use <n> digits").optional_value("<n>"),
+        Opt::long("contains", "print only tags containing the commit").value("<commit>").last_arg_default("HEAD"),
+        Opt::long("pathspec-from-file", "read pathspec from file").value("<file>"),
+        Opt::long("not", "reverse the meaning of the next arguments").in_args(),
+        Opt::flag('c', "count", "limit the count").value("<count>").number(),
+    ];
+    const USAGE: Usage = Usage::new(&["minigit test [<options>] [--] <pathspec>...", "minigit test --other"], OPTIONS);
+
+    fn parse(args: &[&str]) -> MinigitResult<ParsedOptions> {
+        let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+        USAGE.parse(&mut Runtime::default(), &args)
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let parsed = parse(&["-nr", "a", "--cached", "-mfirst", "--message", "second", "--abbrev=7", "--no-quiet", "b", "--", "-q"]).unwrap();
+        assert!(parsed.is_set("dry-run"));
+        assert!(parsed.is_set("n"));
+        assert!(parsed.is_set("r"));
+        assert!(parsed.is_set("cached"));
+        assert_eq!(Some(false), parsed.get_flag("quiet"));
+        assert_eq!(vec!("first", "second"), parsed.values("message"));
+        assert_eq!(Some("7"), parsed.value("abbrev"));
+        assert_eq!(vec!("a", "b", "-q"), parsed.args);
+        assert_eq!(Some(2), parsed.separator);
+
+        let parsed = parse(&["-m", "msg", "--abbrev", "--no-message", "--contains"]).unwrap();
+        assert!(parsed.values("message").is_empty());
+        assert_eq!(None, parsed.value("abbrev"));
+        assert!(parsed.get_flag("abbrev").is_some());
+        assert_eq!(Some("HEAD"), parsed.value("contains"));
+        assert_eq!(Some("cached"), parse(&["--dry-run", "--cached", "-r"]).unwrap().last_of(&["cached", "dry-run"]));
+
+        let parsed = parse(&["a", "--not", "b", "-12", "--not", "c"]).unwrap();
+        assert_eq!(vec!("a", "--not", "b", "--not", "c"), parsed.args);
+        assert!(parsed.is_set("not"));
+        assert_eq!(Some("12"), parsed.value("count"));
+        assert_eq!(Some("3"), parse(&["-c3"]).unwrap().value("c"));
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let error = parse(&["--unknown"]).err().unwrap();
+        assert_eq!(ErrorKind::Usage, error.kind);
+        assert!(error.message.starts_with("error: unknown option `unknown'\nusage: minigit test"));
+        assert!(parse(&["-x"]).err().unwrap().message.starts_with("error: unknown switch `x'\n"));
+        assert!(parse(&["-m"]).err().unwrap().message.starts_with("error: switch `m' requires a value\n"));
+        assert!(parse(&["--message"]).err().unwrap().message.starts_with("error: option `message' requires a value\n"));
+        assert!(parse(&["--cached=yes"]).err().unwrap().message.starts_with("error: option `cached' takes no value\n"));
+    }
+
+    #[test]
+    fn test_help() {
+        assert_eq!("usage: minigit test [<options>] [--] <pathspec>...\n   \
+                    or: minigit test --other\n\n    \
+                    -n, --dry-run         dry run\n    \
+                    -q, --quiet           do not list removed files\n    \
+                    --cached              only remove from the index\n    \
+                    -r                    allow recursive removal\n    \
+                    -m, --message <message>\n                          \
+                    use the given message\n    \
+                    --abbrev[=<n>]        use <n> digits\n    \
+                    --contains <commit>   print only tags containing the commit\n    \
+                    --pathspec-from-file <file>\n                          \
+                    read pathspec from file\n    \
+                    --not                 reverse the meaning of the next arguments\n    \
+                    -<n>, -c, --count <count>\n                          \
+                    limit the count", USAGE.help());
+
+        let mut stdout = Vec::new();
+        {
+            let mut runtime = Runtime { stdout: Box::new(&mut stdout), ..Runtime::default() };
+            let error = USAGE.parse(&mut runtime, &[String::from("-h")]).err().unwrap();
+            assert_eq!(ErrorKind::Usage, error.kind);
+            assert_eq!("", error.message);
+        }
+        assert_eq!(format!("{}\n\n", USAGE.help()), String::from_utf8(stdout).unwrap());
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::Local;
+
+use crate::approxidate;
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::refs::HEAD;
+use crate::repository::Repository;
+
+pub struct ReflogCommand;
+
+const DEFAULT_EXPIRE: &str = "90 days ago";
+const ABBREV_LEN: usize = 7;
+const EXPIRE_USAGE: Usage = Usage::new(&["minigit reflog expire [--expire=<time>] [--all] [<refs>...]"], &[
+    Opt::long("expire", "prune entries older than the specified time").value("<time>"),
+    Opt::long("all", "process the reflogs of all references"),
+]);
+
+impl Command for ReflogCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit reflog [show] [<ref>]",
+        "minigit reflog expire [--expire=<time>] [--all] [<refs>...]",
+        "minigit reflog delete <ref>@{<n>}...",
+    ], &[]).stop_at_non_option();
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args: Vec<String> = runtime.args[2..].to_vec();
+        let mut repository = runtime.repository()?;
+        match args.first().map(String::as_str) {
+            Some("show") => show(runtime, &mut repository, &args[1..]),
+            Some("expire") => expire(runtime, &mut repository, &args[1..]),
+            Some("delete") => delete(&mut repository, &args[1..]),
+            _ => show(runtime, &mut repository, &args),
+        }
+    }
+}
+
+fn show(runtime: &mut Runtime, repository: &mut Repository, args: &[String]) -> MinigitResult<()> {
+    let parsed = ReflogCommand::USAGE.parse(runtime, args)?;
+    let name = match parsed.args.as_slice() {
+        [] => HEAD,
+        [name] => name.as_str(),
+        _ => return Err(ReflogCommand::USAGE.error()),
+    };
+    let full_name = match expand_reflog_name(repository, name)? {
+        Some(full_name) => full_name,
+        None => return Err(MinigitError::fatal(format!("fatal: ambiguous argument '{}': unknown revision or path not in the working tree.\n\
+            Use '--' to separate paths from revisions, like this:\n\
+            'git <command> [<revision>...] -- [<file>...]'", name))),
+    };
+    let entries = repository.refs().read_reflog(&full_name)?;
+    for (n, entry) in entries.iter().rev().enumerate() {
+        writeln!(&mut runtime.stdout, "{} {}@{{{}}}: {}", entry.new_oid.abbreviate(ABBREV_LEN), name, n, entry.message).unwrap();
+    }
+    Ok(())
+}
+
+fn expire(runtime: &mut Runtime, repository: &mut Repository, args: &[String]) -> MinigitResult<()> {
+    let parsed = EXPIRE_USAGE.parse(runtime, args)?;
+    let all = parsed.is_set("all");
+    let cutoff = match parsed.value("expire").unwrap_or(DEFAULT_EXPIRE) {
+        "never" => return Ok(()),
+        "all" | "now" => i64::MAX,
+        date => match approxidate::parse(date, Local::now()) {
+            Some(timestamp) => timestamp,
+            None => return Err(MinigitError::fatal(format!("fatal: malformed expiration date '{}'", date))),
+        },
+    };
+    let names = parsed.args;
+
+    let mut full_names = Vec::new();
+    if all {
+        full_names = repository.refs().list_reflogs()?;
+    }
+    for name in names {
+        match expand_reflog_name(repository, &name)? {
+            Some(full_name) => full_names.push(full_name),
+            None => return Err(MinigitError::new(format!("error: reflog could not be found: '{}'", name))),
+        }
+    }
+    for full_name in full_names {
+        let mut entries = repository.refs().read_reflog(&full_name)?;
+        let count = entries.len();
+        entries.retain(|entry| entry.get_timestamp() >= cutoff);
+        if entries.len() != count {
+            repository.refs().write_reflog(&full_name, &entries)?;
+        }
+    }
+    Ok(())
+}
+
+fn delete(repository: &mut Repository, args: &[String]) -> MinigitResult<()> {
+    if args.is_empty() {
+        return Err(MinigitError::fatal(String::from("fatal: no reflog specified to delete")));
+    }
+    for arg in args {
+        let (name, n) = match parse_reflog_entry_spec(arg) {
+            Some(spec) => spec,
+            None => return Err(MinigitError::new(format!("error: not a reflog: {}", arg))),
+        };
+        let full_name = match expand_reflog_name(repository, name)? {
+            Some(full_name) => full_name,
+            None => return Err(MinigitError::new(format!("error: reflog could not be found: '{}'", name))),
+        };
+        let mut entries = repository.refs().read_reflog(&full_name)?;
+        if n >= entries.len() {
+            return Err(MinigitError::new(format!("error: no reflog entry '{}'", arg)));
+        }
+        // Entries are numbered from the most recent one.
+        let index = entries.len() - 1 - n;
+        entries.remove(index);
+        repository.refs().write_reflog(&full_name, &entries)?;
+    }
+    Ok(())
+}
+
+/// Splits `master@{2}` into `("master", 2)`. An empty name like in `@{2}` stands for HEAD.
+fn parse_reflog_entry_spec(spec: &str) -> Option<(&str, usize)> {
+    let open = spec.find("@{")?;
+    let n = spec[open + 2..].strip_suffix('}')?.parse().ok()?;
+    match &spec[..open] {
+        "" => Some((HEAD, n)),
+        name => Some((name, n)),
+    }
+}
+
+fn expand_reflog_name(repository: &mut Repository, name: &str) -> MinigitResult<Option<String>> {
+    if let Some(full_name) = repository.refs().expand_ref_name(name)? {
+        return Ok(Some(full_name));
+    }
+    // The ref itself may have been deleted while its log is still around.
+    Ok([String::from(name), format!("refs/heads/{}", name)].iter()
+        .find(|full_name| repository.refs().reflog_exists(full_name))
+        .cloned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::objectid::ObjectId;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn reflog(repo_path: &str, args: &[&str]) -> String {
+        let mut command = vec!(String::new(), String::from("reflog"));
+        command.extend(args.iter().map(|arg| String::from(*arg)));
+        execute_and_get_stdout(repo_path, command)
+    }
+
+    fn rev_parse(repo_path: &str, revision: &str) -> String {
+        execute_and_get_stdout(repo_path, vec!(String::new(), String::from("rev-parse"), String::from(revision)))
+    }
+
+    fn read_log(repo_path: &str, full_name: &str) -> Vec<String> {
+        fs::read_to_string(PathBuf::from(repo_path).join(".git/logs").join(full_name)).unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_commits_are_logged_for_head_and_branch() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first\n\nbody");
+            let second = commit(repo_path, "second");
+            for full_name in [HEAD, "refs/heads/master"].iter() {
+                let lines = read_log(repo_path, full_name);
+                assert_eq!(2, lines.len());
+                assert!(lines[0].starts_with(&format!("{} {} A U Thor <author@example.com> ", ObjectId::null(), first)));
+                assert!(lines[0].ends_with("\tcommit (initial): first"));
+                assert!(lines[1].starts_with(&format!("{} {} ", first, second)));
+                assert!(lines[1].ends_with("\tcommit: second"));
+            }
+            assert_eq!(format!("{}\n", second), fs::read_to_string(PathBuf::from(repo_path).join(".git/refs/heads/master")).unwrap());
+            assert_eq!("ref: refs/heads/master\n", fs::read_to_string(PathBuf::from(repo_path).join(".git/HEAD")).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_detached_head_is_logged() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            fs::write(PathBuf::from(repo_path).join(".git/HEAD"), format!("{}\n", first)).unwrap();
+            let second = commit(repo_path, "second");
+            assert_eq!(2, read_log(repo_path, HEAD).len());
+            assert_eq!(1, read_log(repo_path, "refs/heads/master").len());
+            assert_eq!(format!("{}\n", second), fs::read_to_string(PathBuf::from(repo_path).join(".git/HEAD")).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_show() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            let expected = format!("{} HEAD@{{0}}: commit: second\n{} HEAD@{{1}}: commit (initial): first\n",
+                                   second.abbreviate(7), first.abbreviate(7));
+            assert_eq!(expected, reflog(repo_path, &[]));
+            assert_eq!(expected, reflog(repo_path, &["show", "HEAD"]));
+            assert_eq!(format!("{} master@{{0}}: commit: second\n{} master@{{1}}: commit (initial): first\n",
+                               second.abbreviate(7), first.abbreviate(7)),
+                       reflog(repo_path, &["show", "master"]));
+            execute_and_expect_error(repo_path, vec!(String::new(), String::from("reflog"), String::from("show"), String::from("unknown")));
+        });
+    }
+
+    #[test]
+    fn test_resolve_reflog_selectors() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "@{0}"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "HEAD@{1}"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "master@{1}"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "master@{now}"));
+            // Older than the whole log: the first value the ref had.
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "master@{yesterday}"));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("master@{2}")),
+                                             String::from("fatal: log for 'master' only has 2 entries"));
+        });
+    }
+
+    #[test]
+    fn test_expire() {
+        run_test(|repo_path| {
+            commit(repo_path, "first");
+            commit(repo_path, "second");
+            reflog(repo_path, &["expire", "--all"]);
+            assert_eq!(2, read_log(repo_path, HEAD).len());
+            reflog(repo_path, &["expire", "--expire=never", "--all"]);
+            assert_eq!(2, read_log(repo_path, HEAD).len());
+            reflog(repo_path, &["expire", "--expire=all", "master"]);
+            assert_eq!(2, read_log(repo_path, HEAD).len());
+            assert!(read_log(repo_path, "refs/heads/master").is_empty());
+            reflog(repo_path, &["expire", "--expire=now", "--all"]);
+            assert!(read_log(repo_path, HEAD).is_empty());
+            execute_and_expect_error(repo_path, vec!(String::new(), String::from("reflog"), String::from("expire"), String::from("--expire=whenever")));
+        });
+    }
+
+    #[test]
+    fn test_delete() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            commit(repo_path, "second");
+            let third = commit(repo_path, "third");
+            reflog(repo_path, &["delete", "HEAD@{1}"]);
+            let lines = read_log(repo_path, HEAD);
+            assert_eq!(2, lines.len());
+            assert!(lines[0].ends_with("\tcommit (initial): first"));
+            assert!(lines[1].ends_with("\tcommit: third"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "HEAD@{1}"));
+            assert_eq!(format!("{}\n", third), rev_parse(repo_path, "HEAD@{0}"));
+            // `@{n}` reads the log of the current branch, which is left untouched.
+            assert_eq!(3, read_log(repo_path, "refs/heads/master").len());
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("reflog"), String::from("delete"), String::from("master")),
+                                             String::from("error: not a reflog: master"));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("reflog"), String::from("delete"), String::from("master@{5}")),
+                                             String::from("error: no reflog entry 'master@{5}'"));
+        });
+    }
+
+    #[test]
+    fn test_parse_reflog_entry_spec() {
+        assert_eq!(Some(("master", 2)), parse_reflog_entry_spec("master@{2}"));
+        assert_eq!(Some((HEAD, 0)), parse_reflog_entry_spec("@{0}"));
+        assert_eq!(None, parse_reflog_entry_spec("master"));
+        assert_eq!(None, parse_reflog_entry_spec("master@{yesterday}"));
+    }
+}
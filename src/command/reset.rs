@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{GitObject, TreeEntry, GITLINK_MODE};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+use crate::revision::Revision;
+use crate::workspace::MinigitMetadata;
+
+pub struct ResetCommand;
+
+const ORIG_HEAD: &str = "ORIG_HEAD";
+const SHORT_OID_LEN: usize = 7;
+
+#[derive(PartialEq)]
+enum Mode {
+    Soft,
+    Mixed,
+    Hard,
+}
+
+impl Command for ResetCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit reset [--mixed | --soft | --hard] [-q] [<commit>]",
+        "minigit reset [-q] [<commit>] [--] <paths>...",
+    ], &[
+        Opt::flag('q', "quiet", "be quiet, only report errors"),
+        Opt::long("mixed", "reset HEAD and index"),
+        Opt::long("soft", "reset only HEAD"),
+        Opt::long("hard", "reset HEAD, index and working tree"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let mode = match parsed.last_of(&["soft", "mixed", "hard"]) {
+            Some("soft") => Some(Mode::Soft),
+            Some("mixed") => Some(Mode::Mixed),
+            Some(_) => Some(Mode::Hard),
+            None => None,
+        };
+        let quiet = parsed.is_set("quiet");
+        let separator = parsed.separator.unwrap_or(parsed.args.len());
+        let mut values = parsed.args[..separator].to_vec();
+        let mut paths = parsed.args[separator..].to_vec();
+
+        let mut repository = runtime.repository()?;
+        if repository.is_bare() {
+            match mode {
+                Some(Mode::Soft) => {}
+                Some(Mode::Hard) => return Err(MinigitError::fatal(String::from("fatal: this operation must be run in a work tree"))),
+                _ => return Err(MinigitError::fatal(String::from("fatal: mixed reset is not allowed in a bare repository"))),
+            }
+        }
+        // Without `--`, the first argument is the commit only if it names one.
+        let revision = match parsed.separator.is_some() {
+            true if values.len() > 1 => return Err(Self::USAGE.error()),
+            true => values.pop(),
+            false if !values.is_empty() && Revision::parse(&values[0]).and_then(|revision| revision.resolve_commit(&mut repository)).is_ok() => {
+                Some(values.remove(0))
+            }
+            false => None,
+        };
+        paths.extend(values);
+        // On an unborn branch, HEAD stands for an empty tree, so that files can be unstaged before the first commit.
+        let (revision, commit_oid) = match revision {
+            Some(revision) => {
+                let commit_oid = Revision::parse(&revision)?.resolve_commit(&mut repository)?;
+                (revision, Some(commit_oid))
+            }
+            None => (String::from(HEAD), repository.refs().read_head()?),
+        };
+
+        if !paths.is_empty() {
+            return match mode {
+                Some(Mode::Soft) => Err(MinigitError::fatal(String::from("fatal: Cannot do soft reset with paths."))),
+                Some(Mode::Hard) => Err(MinigitError::fatal(String::from("fatal: Cannot do hard reset with paths."))),
+                _ => reset_paths(&mut repository, commit_oid, &paths),
+            };
+        }
+        let mode = mode.unwrap_or(Mode::Mixed);
+        // Resolved before anything changes, so that a reset never stops half way.
+        let identity = runtime.reflog_identity();
+        let commit = match commit_oid {
+            Some(commit_oid) => Some(repository.database().load_commit(&commit_oid)?),
+            None => None,
+        };
+        if mode != Mode::Soft {
+            let target = match &commit {
+                Some(commit) => repository.database().load_tree_list(commit.get_tree())?,
+                None => BTreeMap::new(),
+            };
+            reset_index(&mut repository, &target, mode == Mode::Hard)?;
+        }
+        let (commit_oid, commit) = match (commit_oid, commit) {
+            (Some(commit_oid), Some(commit)) => (commit_oid, commit),
+            _ => return Ok(()),
+        };
+
+        let orig_head = repository.refs().read_head()?;
+        let mut transaction = repository.refs().transaction(&identity);
+        if let Some(orig_head) = orig_head {
+            transaction.update(ORIG_HEAD, orig_head, None, "");
+        }
+        transaction.update(HEAD, commit_oid, None, &format!("reset: moving to {}", revision));
+        transaction.commit()?;
+
+        if mode == Mode::Hard && !quiet {
+            writeln!(&mut runtime.stdout, "HEAD is now at {} {}", commit_oid.abbreviate(SHORT_OID_LEN), commit.get_title_line()).unwrap();
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the whole index by the files of the target tree and, with `hard`, the tracked files of the workspace too.
+/// Untracked files are left alone.
+pub fn reset_index(repository: &mut Repository, target: &BTreeMap<PathBuf, TreeEntry>, hard: bool) -> MinigitResult<()> {
+    repository.index()?.load_for_update()?;
+    if hard {
+        let tracked_paths: Vec<PathBuf> = repository.index()?.get_entries().iter().map(|entry| PathBuf::from(entry.get_path())).collect();
+        for path in tracked_paths.iter().filter(|path| !target.contains_key(*path)) {
+            repository.workspace().remove_file(path)?;
+        }
+    }
+    repository.index()?.remove_all();
+    for (path, entry) in target.iter() {
+        let metadata = match hard {
+            // A submodule only gets its directory, the update of its repository being left to `submodule update`.
+            true if entry.mode == GITLINK_MODE => {
+                repository.workspace().create_dir(path)?;
+                MinigitMetadata::from_mode(entry.mode)
+            }
+            true => {
+                let blob = repository.database().load_blob(&entry.oid)?;
+                repository.workspace().write_file(path, blob.get_data(), entry.mode)?;
+                repository.workspace().get_metadata(path)?
+            }
+            false => MinigitMetadata::from_mode(entry.mode),
+        };
+        repository.index()?.add(path, entry.oid, metadata);
+    }
+    repository.index()?.write_updates()?;
+    Ok(())
+}
+
+/// Copies the entries of the commit matching the pathspecs into the index, and removes those the commit does not have.
+/// Without a commit, on an unborn branch, the matching entries are all removed.
+fn reset_paths(repository: &mut Repository, commit_oid: Option<ObjectId>, pathspecs: &[String]) -> MinigitResult<()> {
+    let target = match commit_oid {
+        Some(commit_oid) => {
+            let tree_oid = *repository.database().load_commit(&commit_oid)?.get_tree();
+            repository.database().load_tree_list(&tree_oid)?
+        }
+        None => BTreeMap::new(),
+    };
+    repository.index()?.load_for_update()?;
+    let mut known_paths: BTreeSet<PathBuf> = repository.index()?.get_entries().iter().map(|entry| PathBuf::from(entry.get_path())).collect();
+    known_paths.extend(target.keys().cloned());
+    let pathspec = Pathspec::parse(pathspecs, repository.workspace())?;
+    if let Some(unmatched) = pathspec.find_unmatched(known_paths.iter().map(PathBuf::as_path)).first() {
+        return Err(MinigitError::new(format!("error: pathspec '{}' did not match any file(s) known to git", unmatched)));
+    }
+    for path in known_paths.iter().filter(|path| pathspec.matches(path)) {
+        repository.index()?.remove(path);
+        if let Some(entry) = target.get(path) {
+            repository.index()?.add(path, entry.oid, MinigitMetadata::from_mode(entry.mode));
+        }
+    }
+    repository.index()?.write_updates()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::command::execute;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn reset(repo_path: &str, args: &[&str]) -> MinigitResult<String> {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("reset"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            runtime.env.insert(String::from("GIT_AUTHOR_NAME"), String::from("A U Thor"));
+            runtime.env.insert(String::from("GIT_AUTHOR_EMAIL"), String::from("author@example.com"));
+            execute(&mut runtime)?;
+        }
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn write_and_add(repo_path: &str, path: &str, content: &str) {
+        let full_path = PathBuf::from(repo_path).join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(path)));
+    }
+
+    fn read_file(repo_path: &str, path: &str) -> String {
+        fs::read_to_string(PathBuf::from(repo_path).join(path)).unwrap()
+    }
+
+    fn head(repo_path: &str) -> ObjectId {
+        Repository::new(PathBuf::from(repo_path)).refs().read_head().unwrap().unwrap()
+    }
+
+    fn index_oid(repo_path: &str, path: &str) -> ObjectId {
+        let entries = Repository::new(PathBuf::from(repo_path)).index_take().unwrap().load_and_get_entries().unwrap();
+        *entries.iter().find(|entry| entry.get_path_as_str() == path).unwrap().get_oid()
+    }
+
+    /// Commits `a.txt`, then modifies it and adds `dir/b.txt` in a second commit.
+    fn create_history(repo_path: &str) -> (ObjectId, ObjectId) {
+        write_and_add(repo_path, "a.txt", "one");
+        let first = commit(repo_path, "first");
+        write_and_add(repo_path, "a.txt", "two");
+        write_and_add(repo_path, "dir/b.txt", "b");
+        let second = commit(repo_path, "second");
+        (first, second)
+    }
+
+    #[test]
+    fn test_soft_reset() {
+        run_test(|repo_path| {
+            let (first, second) = create_history(repo_path);
+            reset(repo_path, &["--soft", "HEAD~1"]).unwrap();
+            assert_eq!(first, head(repo_path));
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt")), (0o100644, String::from("dir/b.txt"))));
+            assert_eq!(format!("{}\n", second), read_file(repo_path, ".git/ORIG_HEAD"));
+            let reflog = Repository::new(PathBuf::from(repo_path)).refs().read_reflog("refs/heads/master").unwrap();
+            assert_eq!("reset: moving to HEAD~1", reflog.last().unwrap().message);
+            assert_eq!(second, reflog.last().unwrap().old_oid);
+            // Going back to the original commit.
+            reset(repo_path, &["--soft", "ORIG_HEAD"]).unwrap();
+            assert_eq!(second, head(repo_path));
+        });
+    }
+
+    #[test]
+    fn test_mixed_reset() {
+        run_test(|repo_path| {
+            let (first, _) = create_history(repo_path);
+            let first_a = index_oid(repo_path, "a.txt");
+            reset(repo_path, &["HEAD~1"]).unwrap();
+            assert_eq!(first, head(repo_path));
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt"))));
+            assert_ne!(first_a, index_oid(repo_path, "a.txt"));
+            assert_eq!("two", read_file(repo_path, "a.txt"));
+            assert_eq!("b", read_file(repo_path, "dir/b.txt"));
+        });
+    }
+
+    #[test]
+    fn test_hard_reset() {
+        run_test(|repo_path| {
+            let (first, second) = create_history(repo_path);
+            fs::write(PathBuf::from(repo_path).join("untracked.txt"), "untracked").unwrap();
+            assert_eq!(format!("HEAD is now at {} first\n", first.abbreviate(7)), reset(repo_path, &["--hard", "HEAD~1"]).unwrap());
+            assert_eq!(first, head(repo_path));
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt"))));
+            assert_eq!("one", read_file(repo_path, "a.txt"));
+            assert!(!PathBuf::from(repo_path).join("dir").exists());
+            assert_eq!("untracked", read_file(repo_path, "untracked.txt"));
+
+            assert_eq!("", reset(repo_path, &["--hard", "-q", &second.to_hex()]).unwrap());
+            assert_eq!("two", read_file(repo_path, "a.txt"));
+            assert_eq!("b", read_file(repo_path, "dir/b.txt"));
+        });
+    }
+
+    #[test]
+    fn test_hard_reset_without_identity() {
+        run_test(|repo_path| {
+            let (first, second) = create_history(repo_path);
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "modified").unwrap();
+            {
+                let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(std::io::sink()), ..Runtime::default() };
+                runtime.args = ["", "reset", "--hard", "HEAD~1"].iter().map(|arg| String::from(*arg)).collect();
+                runtime.env.remove("GIT_AUTHOR_NAME");
+                runtime.env.remove("GIT_AUTHOR_EMAIL");
+                execute(&mut runtime).unwrap();
+            }
+            assert_eq!(first, head(repo_path));
+            assert_eq!(format!("{}\n", second), read_file(repo_path, ".git/ORIG_HEAD"));
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt"))));
+            assert_eq!("one", read_file(repo_path, "a.txt"));
+            execute_and_expect_success_message(repo_path, vec!(String::new(), String::from("status")), String::new());
+        });
+    }
+
+    #[test]
+    fn test_reset_paths() {
+        run_test(|repo_path| {
+            let (first, second) = create_history(repo_path);
+            write_and_add(repo_path, "a.txt", "three");
+            write_and_add(repo_path, "dir/c.txt", "c");
+            reset(repo_path, &["a.txt"]).unwrap();
+            assert_eq!(index_oid(repo_path, "a.txt"), lookup(repo_path, second, "a.txt"));
+            reset(repo_path, &["--", "dir"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt")), (0o100644, String::from("dir/b.txt"))));
+            reset(repo_path, &[&first.to_hex(), "--", "dir", "a.txt"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt"))));
+            assert_eq!(index_oid(repo_path, "a.txt"), lookup(repo_path, first, "a.txt"));
+            assert_eq!(second, head(repo_path));
+            assert_eq!("three", read_file(repo_path, "a.txt"));
+            assert_eq!("fatal: Cannot do hard reset with paths.", reset(repo_path, &["--hard", "HEAD", "a.txt"]).err().unwrap().message);
+
+            write_and_add(repo_path, "dir/c.txt", "c");
+            write_and_add(repo_path, "d.txt", "d");
+            reset(repo_path, &["*.txt", ":!d.txt"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt")), (0o100644, String::from("d.txt")), (0o100644, String::from("dir/b.txt"))));
+            assert_eq!(index_oid(repo_path, "a.txt"), lookup(repo_path, second, "a.txt"));
+            reset(repo_path, &["."]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt")), (0o100644, String::from("dir/b.txt"))));
+            assert_eq!("error: pathspec 'missing.txt' did not match any file(s) known to git",
+                       reset(repo_path, &["--", "missing.txt"]).err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_reset_paths_from_subdirectory() {
+        run_test(|repo_path| {
+            create_history(repo_path);
+            write_and_add(repo_path, "dir/b.txt", "changed");
+            write_and_add(repo_path, "dir/c.txt", "c");
+            write_and_add(repo_path, "a.txt", "changed");
+            let sub_path = format!("{}/dir", repo_path);
+            reset(&sub_path, &["b.txt"]).unwrap();
+            reset(&sub_path, &["--", "c.txt", "../a.txt"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt")), (0o100644, String::from("dir/b.txt"))));
+            assert_eq!(index_oid(repo_path, "dir/b.txt"), lookup(repo_path, head(repo_path), "dir/b.txt"));
+            assert_eq!(index_oid(repo_path, "a.txt"), lookup(repo_path, head(repo_path), "a.txt"));
+        });
+    }
+
+    #[test]
+    fn test_reset_on_unborn_branch() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.txt", "b");
+            write_and_add(repo_path, "c.txt", "c");
+            reset(repo_path, &["--", "a.txt"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("b.txt")), (0o100644, String::from("c.txt"))));
+            reset(repo_path, &["b.txt"]).unwrap();
+            assert_index(repo_path, vec!((0o100644, String::from("c.txt"))));
+            reset(repo_path, &[]).unwrap();
+            assert_index(repo_path, vec!());
+            assert_eq!("c", read_file(repo_path, "c.txt"));
+            assert!(Repository::new(PathBuf::from(repo_path)).refs().read_head().unwrap().is_none());
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hard_reset_restores_symlinks() {
+        run_test(|repo_path| {
+            let link_path = PathBuf::from(repo_path).join("link");
+            std::os::unix::fs::symlink("a.txt", &link_path).unwrap();
+            write_and_add(repo_path, "a.txt", "a");
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("link")));
+            let first = commit(repo_path, "first");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let tree = *repository.database().load_commit(&first).unwrap().get_tree();
+            let (mode, oid) = {
+                let entries = repository.database().load_tree_list(&tree).unwrap();
+                (entries[Path::new("link")].mode, entries[Path::new("link")].oid)
+            };
+            assert_eq!(0o120000, mode);
+            assert_eq!(b"a.txt".to_vec(), *repository.database().load_blob(&oid).unwrap().get_data());
+
+            // A file replacing the link is replaced back, without writing through the link.
+            fs::remove_file(&link_path).unwrap();
+            fs::write(&link_path, "file").unwrap();
+            reset(repo_path, &["--hard", "-q"]).unwrap();
+            assert_eq!(PathBuf::from("a.txt"), fs::read_link(&link_path).unwrap());
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "modified").unwrap();
+            reset(repo_path, &["--hard", "-q"]).unwrap();
+            assert_eq!("a", read_file(repo_path, "link"));
+        });
+    }
+
+    fn lookup(repo_path: &str, commit: ObjectId, path: &str) -> ObjectId {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let tree = *repository.database().load_commit(&commit).unwrap().get_tree();
+        repository.database().load_tree_list(&tree).unwrap()[Path::new(path)].oid
+    }
+}
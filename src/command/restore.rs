@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{GitObject, TreeEntry, GITLINK_MODE};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+use crate::revision::Revision;
+use crate::workspace::MinigitMetadata;
+
+pub struct RestoreCommand;
+
+const OURS_STAGE: u8 = 2;
+const THEIRS_STAGE: u8 = 3;
+
+struct Options {
+    staged: bool,
+    worktree: bool,
+    source: Option<String>,
+    stage: Option<u8>,
+}
+
+impl Command for RestoreCommand {
+    const USAGE: Usage = Usage::new(&["minigit restore [<options>] [--source=<tree-ish>] [--] <pathspec>..."], &[
+        Opt::flag('s', "source", "which tree-ish to checkout from").value("<tree-ish>"),
+        Opt::flag('S', "staged", "restore the index"),
+        Opt::flag('W', "worktree", "restore the working tree (default)"),
+        Opt::long("ours", "restore from our version for unmerged files"),
+        Opt::long("theirs", "restore from their version for unmerged files"),
+    ]);
+    const NEEDS_WORK_TREE: bool = true;
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let mut options = Options {
+            staged: parsed.is_set("staged"),
+            worktree: parsed.is_set("worktree"),
+            source: parsed.value("source").map(String::from),
+            stage: match parsed.last_of(&["ours", "theirs"]) {
+                Some("ours") => Some(OURS_STAGE),
+                Some(_) => Some(THEIRS_STAGE),
+                None => None,
+            },
+        };
+        let pathspecs = parsed.args;
+        if pathspecs.is_empty() {
+            return Err(MinigitError::fatal(String::from("fatal: you must specify path(s) to restore")));
+        }
+        if !options.staged {
+            options.worktree = true;
+        }
+        // The index is restored from HEAD by default, the workspace from the index.
+        if options.staged && options.source.is_none() {
+            options.source = Some(String::from(HEAD));
+        }
+        if options.stage.is_some() && options.source.is_some() {
+            return Err(MinigitError::fatal(String::from("fatal: cannot specify a source with --ours or --theirs")));
+        }
+
+        let mut repository = runtime.repository()?;
+        let source_files = match &options.source {
+            Some(source) => {
+                let tree_oid = Revision::parse(source)?.resolve_to(&mut repository, "tree")?;
+                Some(repository.database().load_tree_list(&tree_oid)?)
+            }
+            None => None,
+        };
+        repository.index()?.load_for_update()?;
+        let paths = match_paths(&mut repository, &pathspecs, source_files.as_ref())?;
+
+        if options.worktree {
+            for path in paths.iter() {
+                let source_entry = match &source_files {
+                    Some(source_files) => source_files.get(path).map(|entry| (entry.oid, entry.mode)),
+                    None => index_version(&mut repository, path, options.stage)?,
+                };
+                match source_entry {
+                    Some((_, GITLINK_MODE)) => repository.workspace().create_dir(path)?,
+                    Some((oid, mode)) => {
+                        let blob = repository.database().load_blob(&oid)?;
+                        repository.workspace().write_file(path, blob.get_data(), mode)?;
+                    }
+                    None => repository.workspace().remove_file(path)?,
+                }
+            }
+        }
+        if options.staged {
+            let source_files = source_files.unwrap();
+            for path in paths.iter() {
+                repository.index()?.remove(path);
+                if let Some(entry) = source_files.get(path) {
+                    repository.index()?.add(path, entry.oid, MinigitMetadata::from_mode(entry.mode));
+                }
+            }
+            repository.index()?.write_updates()?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the files of the index or of the source matching the pathspecs, which must all match something.
+fn match_paths(repository: &mut Repository, pathspecs: &[String], source_files: Option<&BTreeMap<PathBuf, TreeEntry>>) -> MinigitResult<Vec<PathBuf>> {
+    let mut known_paths: BTreeSet<PathBuf> = repository.index()?.get_entries().iter().map(|entry| PathBuf::from(entry.get_path())).collect();
+    if let Some(source_files) = source_files {
+        known_paths.extend(source_files.keys().cloned());
+    }
+    let pathspec = Pathspec::parse(pathspecs, repository.workspace())?;
+    if let Some(unmatched) = pathspec.find_unmatched(known_paths.iter().map(PathBuf::as_path)).first() {
+        return Err(MinigitError::new(format!("error: pathspec '{}' did not match any file(s) known to git", unmatched)));
+    }
+    Ok(known_paths.into_iter().filter(|path| pathspec.matches(path)).collect())
+}
+
+/// The version of a file to write from the index, which for an unmerged file must be chosen with `--ours` or `--theirs`.
+fn index_version(repository: &mut Repository, path: &Path, stage: Option<u8>) -> MinigitResult<Option<(ObjectId, u32)>> {
+    let index = repository.index()?;
+    let stage = match (index.is_unmerged(path), stage) {
+        (false, _) => 0,
+        (true, Some(stage)) => stage,
+        (true, None) => return Err(MinigitError::new(format!("error: path '{}' is unmerged", path.display()))),
+    };
+    match index.get_stage_entry(path, stage) {
+        Some(entry) => Ok(Some((*entry.get_oid(), entry.get_mode()))),
+        None if stage == OURS_STAGE => Err(MinigitError::new(format!("error: path '{}' does not have our version", path.display()))),
+        None => Err(MinigitError::new(format!("error: path '{}' does not have their version", path.display()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::gitobject::Blob;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn restore(repo_path: &str, args: &[&str]) -> MinigitResult<()> {
+        let mut command = vec!(String::new(), String::from("restore"));
+        command.extend(args.iter().map(|arg| String::from(*arg)));
+        execute_and_get_result(repo_path, command)
+    }
+
+    fn write_and_add(repo_path: &str, path: &str, content: &str) {
+        let full_path = PathBuf::from(repo_path).join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(path)));
+    }
+
+    fn read_file(repo_path: &str, path: &str) -> String {
+        fs::read_to_string(PathBuf::from(repo_path).join(path)).unwrap()
+    }
+
+    fn index_oid(repo_path: &str, path: &str) -> Option<ObjectId> {
+        let entries = Repository::new(PathBuf::from(repo_path)).index_take().unwrap().load_and_get_entries().unwrap();
+        entries.iter().find(|entry| entry.get_path_as_str() == path).map(|entry| *entry.get_oid())
+    }
+
+    #[test]
+    fn test_restore_worktree_from_index() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "dir/b.txt", "b");
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "modified").unwrap();
+            fs::remove_file(PathBuf::from(repo_path).join("dir/b.txt")).unwrap();
+            fs::write(PathBuf::from(repo_path).join("untracked.txt"), "untracked").unwrap();
+
+            restore(repo_path, &["."]).unwrap();
+            assert_eq!("a", read_file(repo_path, "a.txt"));
+            assert_eq!("b", read_file(repo_path, "dir/b.txt"));
+            assert_eq!("untracked", read_file(repo_path, "untracked.txt"));
+            assert_eq!("error: pathspec 'untracked.txt' did not match any file(s) known to git",
+                       execute_and_get_result(repo_path, vec!(String::new(), String::from("restore"), String::from("untracked.txt"))).err().unwrap().message);
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_restore_mode() {
+        run_test(|repo_path| {
+            let path = PathBuf::from(repo_path).join("script.sh");
+            fs::write(&path, "#!/bin/sh").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("script.sh")));
+            fs::remove_file(&path).unwrap();
+            restore(repo_path, &["script.sh"]).unwrap();
+            assert_eq!(0o755, fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+        });
+    }
+
+    #[test]
+    fn test_restore_staged() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            commit(repo_path, "first");
+            let committed = index_oid(repo_path, "a.txt");
+            write_and_add(repo_path, "a.txt", "staged");
+            write_and_add(repo_path, "new.txt", "new");
+
+            restore(repo_path, &["--staged", "a.txt", "new.txt"]).unwrap();
+            assert_eq!(committed, index_oid(repo_path, "a.txt"));
+            assert_eq!(None, index_oid(repo_path, "new.txt"));
+            assert_eq!("staged", read_file(repo_path, "a.txt"));
+            assert_eq!("new", read_file(repo_path, "new.txt"));
+        });
+    }
+
+    #[test]
+    fn test_restore_from_source() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "one");
+            let first = commit(repo_path, "first");
+            write_and_add(repo_path, "a.txt", "two");
+            write_and_add(repo_path, "b.txt", "b");
+            commit(repo_path, "second");
+
+            restore(repo_path, &["--source=HEAD~1", ":(glob)*.txt", ":!b.txt"]).unwrap();
+            assert_eq!("one", read_file(repo_path, "a.txt"));
+            assert_ne!(index_oid(repo_path, "a.txt"), None);
+
+            // Files which are not in the source are deleted.
+            restore(repo_path, &["-s", &first.to_hex(), "--staged", "--worktree", "."]).unwrap();
+            assert!(!PathBuf::from(repo_path).join("b.txt").exists());
+            assert_eq!(None, index_oid(repo_path, "b.txt"));
+            assert_index(repo_path, vec!((0o100644, String::from("a.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_unmerged_paths() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "base");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut oids = Vec::new();
+            for content in ["base", "ours", "theirs"].iter() {
+                let mut blob = Blob::new(content.as_bytes().to_vec());
+                repository.database().store(&mut blob).unwrap();
+                oids.push(*blob.get_oid());
+            }
+            let mut index = repository.index_take().unwrap();
+            index.load_for_update().unwrap();
+            for (stage, oid) in oids.into_iter().enumerate() {
+                index.add_unmerged(Path::new("a.txt"), stage as u8 + 1, oid, MinigitMetadata::from_mode(0o100644));
+            }
+            index.write_updates().unwrap();
+            drop(index);
+
+            assert_eq!("error: path 'a.txt' is unmerged", restore(repo_path, &["a.txt"]).err().unwrap().message);
+            restore(repo_path, &["--theirs", "a.txt"]).unwrap();
+            assert_eq!("theirs", read_file(repo_path, "a.txt"));
+            restore(repo_path, &["--ours", "a.txt"]).unwrap();
+            assert_eq!("ours", read_file(repo_path, "a.txt"));
+            execute_and_expect_error(repo_path, vec!(String::new(), String::from("commit")));
+        });
+    }
+}
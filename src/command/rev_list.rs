@@ -0,0 +1,172 @@
+use chrono::Local;
+
+use crate::approxidate;
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::GitObject;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::pathspec::Pathspec;
+use crate::revlist::{Order, RevList, RevListOptions};
+use crate::workspace::Workspace;
+
+pub struct RevListCommand;
+
+impl Command for RevListCommand {
+    const USAGE: Usage = Usage::new(&["minigit rev-list [<options>] <commit>... [--] [<path>...]"], &[
+        Opt::flag('n', "max-count", "limit the number of commits to output").value("<number>").number(),
+        Opt::long("since", "show commits more recent than a specific date").value("<date>"),
+        Opt::long("after", "show commits more recent than a specific date").value("<date>"),
+        Opt::long("until", "show commits older than a specific date").value("<date>"),
+        Opt::long("before", "show commits older than a specific date").value("<date>"),
+        Opt::long("author", "limit the commits to those by a matching author").value("<pattern>"),
+        Opt::long("grep", "limit the commits to those with a matching message").value("<pattern>"),
+        Opt::long("all-match", "limit the commits to those matching all --grep"),
+        Opt::flag('i', "regexp-ignore-case", "match the patterns case-insensitively"),
+        Opt::long("first-parent", "follow only the first parent of merge commits"),
+        // Their position among the revisions matters, so they are kept with them.
+        Opt::long("all", "pretend as if all the refs are listed on the command line").in_args(),
+        Opt::long("not", "reverse the meaning of the ^ prefix up to the next --not").in_args(),
+        Opt::long("topo-order", "show no parents before all of their children"),
+        Opt::long("date-order", "show no parents before all of their children, otherwise by date"),
+        Opt::long("reverse", "output the commits in reverse order"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let date = |names: &[&str]| match parsed.occurrences(names).pop() {
+            Some((_, Some(date))) => parse_date(date).map(Some),
+            _ => Ok(None),
+        };
+        let separator = parsed.separator.unwrap_or(parsed.args.len());
+        let revisions = parsed.args[..separator].to_vec();
+        let mut options = RevListOptions {
+            first_parent: parsed.is_set("first-parent"),
+            order: match parsed.last_of(&["topo-order", "date-order"]) {
+                Some("topo-order") => Order::Topo,
+                Some(_) => Order::Date,
+                None => Order::Default,
+            },
+            reverse: parsed.is_set("reverse"),
+            max_count: parsed.value("max-count").map(parse_count).transpose()?,
+            since: date(&["since", "after"])?,
+            until: date(&["until", "before"])?,
+            authors: parsed.values("author").into_iter().map(String::from).collect(),
+            grep: parsed.values("grep").into_iter().map(String::from).collect(),
+            all_match: parsed.is_set("all-match"),
+            ignore_case: parsed.is_set("regexp-ignore-case"),
+            paths: None,
+        };
+        if revisions.iter().all(|revision| revision == "--not") {
+            return Err(Self::USAGE.error());
+        }
+
+        let mut repository = runtime.repository()?;
+        // Paths are relative to the directory the command runs from, or to the top of a bare repository.
+        let paths = &parsed.args[separator..];
+        options.paths = match paths.is_empty() {
+            true => None,
+            false if repository.is_bare() => Some(Pathspec::parse(paths, &Workspace::new(repository.get_path()))?),
+            false => Some(Pathspec::parse(paths, repository.workspace())?),
+        };
+        let mut rev_list = RevList::new(&mut repository, options);
+        rev_list.add_revision_args(&revisions)?;
+        for commit in rev_list.walk()? {
+            writeln!(&mut runtime.stdout, "{}", commit.get_oid()).unwrap();
+        }
+        Ok(())
+    }
+}
+
+fn parse_count(value: &str) -> MinigitResult<usize> {
+    match value.parse() {
+        Ok(count) => Ok(count),
+        Err(_) => Err(MinigitError::fatal(format!("fatal: '{}': not an integer", value))),
+    }
+}
+
+fn parse_date(value: &str) -> MinigitResult<i64> {
+    match approxidate::parse(value, Local::now()) {
+        Some(timestamp) => Ok(timestamp),
+        None => Err(MinigitError::fatal(format!("fatal: invalid date format: {}", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::command::Command;
+    use crate::command::rev_list::RevListCommand;
+
+    #[test]
+    fn test_list_commits_with_options() {
+        crate::tests::run_test(|repo_path| {
+            let a = crate::tests::write_commit(repo_path, vec!(), "first", 1000, &[("a.txt", "a")]);
+            let b = crate::tests::write_commit(repo_path, vec!(a), "second", 2000, &[("a.txt", "a"), ("b.txt", "b")]);
+            let c = crate::tests::write_commit(repo_path, vec!(b), "third", 3000, &[("a.txt", "c"), ("b.txt", "b")]);
+            fs::write(format!("{}/.git/HEAD", repo_path), format!("{}\n", c)).unwrap();
+
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("HEAD")),
+                format!("{}\n{}\n{}\n", c, b, a));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("--reverse"), String::from("-2"), String::from("HEAD")),
+                format!("{}\n{}\n", b, c));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("--since=@1500"), String::from("--until=2500 +0000"), String::from("HEAD")),
+                format!("{}\n", b));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("HEAD~2.."), String::from("--"), String::from("a.txt")),
+                format!("{}\n", c));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("--grep=sec"), String::from("-n"), String::from("5"), String::from("HEAD")),
+                format!("{}\n", b));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("--not"), String::from("HEAD~2"), String::from("--max-count=5"),
+                     String::from("--not"), String::from("HEAD")),
+                format!("{}\n{}\n", c, b));
+        });
+    }
+
+    #[test]
+    fn test_paths_from_subdirectory() {
+        crate::tests::run_test(|repo_path| {
+            let a = crate::tests::write_commit(repo_path, vec!(), "first", 1000, &[("f.txt", "1"), ("sub/f.txt", "1")]);
+            let b = crate::tests::write_commit(repo_path, vec!(a), "second", 2000, &[("f.txt", "2"), ("sub/f.txt", "1")]);
+            let c = crate::tests::write_commit(repo_path, vec!(b), "third", 3000, &[("f.txt", "2"), ("sub/f.txt", "2")]);
+            fs::write(format!("{}/.git/HEAD", repo_path), format!("{}\n", c)).unwrap();
+            fs::create_dir(format!("{}/sub", repo_path)).unwrap();
+
+            let sub_path = format!("{}/sub", repo_path);
+            crate::tests::execute_and_expect_success_message(
+                &sub_path,
+                vec!(String::new(), String::from("rev-list"), String::from("HEAD"), String::from("--"), String::from("f.txt")),
+                format!("{}\n{}\n", c, a));
+            crate::tests::execute_and_expect_success_message(
+                &sub_path,
+                vec!(String::new(), String::from("rev-list"), String::from("HEAD"), String::from("--"), String::from("../f.txt")),
+                format!("{}\n{}\n", b, a));
+            crate::tests::execute_and_expect_success_message(
+                &sub_path,
+                vec!(String::new(), String::from("rev-list"), String::from("HEAD"), String::from("--"), String::from(":/*.txt"), String::from(":!f.txt")),
+                format!("{}\n{}\n", b, a));
+        });
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        crate::tests::run_test(|repo_path| {
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-list"), String::from("--bogus"), String::from("HEAD")),
+                format!("error: unknown option `bogus'\n{}", RevListCommand::USAGE.help()));
+        });
+    }
+}
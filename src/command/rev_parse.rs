@@ -0,0 +1,255 @@
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::repository::Repository;
+use crate::revision::Revision;
+
+pub struct RevParseCommand;
+
+const DEFAULT_ABBREV_LEN: usize = 7;
+
+impl Command for RevParseCommand {
+    const USAGE: Usage = Usage::new(&["minigit rev-parse [<options>] <args>..."], &[
+        Opt::long("verify", "verify that exactly one parameter is a usable object name"),
+        Opt::long("short", "shorten the object names to <n> digits").optional_value("<n>"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let verify = parsed.is_set("verify");
+        let abbrev_len = match parsed.occurrences(&["short"]).pop() {
+            _ if !parsed.is_set("short") => None,
+            Some((_, Some(len))) => match len.parse() {
+                Ok(len) => Some(len),
+                Err(_) => return Err(MinigitError::fatal(format!("fatal: invalid value for --short: {}", len))),
+            },
+            _ => Some(DEFAULT_ABBREV_LEN),
+        };
+        // Like git, the paths after `--` are printed as they are.
+        let separator = parsed.separator.unwrap_or(parsed.args.len());
+        let revisions = parsed.args[..separator].to_vec();
+        let paths = parsed.args[separator..].to_vec();
+        if verify && (revisions.len() != 1 || parsed.separator.is_some()) {
+            return Err(needed_single_revision_error());
+        }
+
+        let mut repository = runtime.repository()?;
+        for revision in revisions {
+            let oid = match resolve(&mut repository, &revision, verify) {
+                Ok(oid) => oid,
+                Err(_) if verify => return Err(needed_single_revision_error()),
+                Err(error) => return Err(error),
+            };
+            match abbrev_len {
+                Some(len) => writeln!(&mut runtime.stdout, "{}", oid.abbreviate(len)).unwrap(),
+                None => writeln!(&mut runtime.stdout, "{}", oid).unwrap(),
+            }
+        }
+        if parsed.separator.is_some() {
+            writeln!(&mut runtime.stdout, "--").unwrap();
+            for path in paths {
+                writeln!(&mut runtime.stdout, "{}", path).unwrap();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn resolve(repository: &mut Repository, revision: &str, verify: bool) -> MinigitResult<ObjectId> {
+    let oid = Revision::parse(revision)?.resolve(repository)?;
+    if verify && !repository.database().exists(&oid) {
+        return Err(needed_single_revision_error());
+    }
+    Ok(oid)
+}
+
+fn needed_single_revision_error() -> MinigitError {
+    MinigitError::fatal(String::from("fatal: Needed a single revision"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::gitobject::{Blob, GitObject};
+    use crate::repository::Repository;
+    use crate::workspace::MinigitMetadata;
+
+    fn rev_parse(repo_path: &str, revision: &str) -> String {
+        crate::tests::execute_and_get_stdout(repo_path, vec!(String::new(), String::from("rev-parse"), String::from(revision)))
+    }
+
+    #[test]
+    fn test_resolve_head_and_ancestors() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/a.txt", repo_path), "one").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let first = crate::tests::commit(repo_path, "first");
+            fs::write(format!("{}/a.txt", repo_path), "two").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let second = crate::tests::commit(repo_path, "second");
+
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "HEAD"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "@"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "HEAD^"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "HEAD~1"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "HEAD^1~0"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, &format!("{}~", second.abbreviate(6))));
+            crate::tests::execute_and_expect_error(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("HEAD~2")));
+            crate::tests::execute_and_expect_error(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("HEAD^2")));
+        });
+    }
+
+    #[test]
+    fn test_resolve_branch_names_in_lookup_order() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/a.txt", repo_path), "one").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let first = crate::tests::commit(repo_path, "first");
+            fs::write(format!("{}/a.txt", repo_path), "two").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let second = crate::tests::commit(repo_path, "second");
+
+            fs::create_dir_all(format!("{}/.git/refs/heads", repo_path)).unwrap();
+            fs::create_dir_all(format!("{}/.git/refs/tags", repo_path)).unwrap();
+            fs::write(format!("{}/.git/refs/heads/topic", repo_path), format!("{}\n", first)).unwrap();
+            fs::write(format!("{}/.git/refs/heads/release", repo_path), format!("{}\n", first)).unwrap();
+            fs::write(format!("{}/.git/refs/tags/release", repo_path), format!("{}\n", second)).unwrap();
+
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "topic"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "refs/heads/topic"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "release"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "heads/release"));
+        });
+    }
+
+    #[test]
+    fn test_resolve_trees_and_paths() {
+        crate::tests::run_test(|repo_path| {
+            fs::create_dir(format!("{}/dir", repo_path)).unwrap();
+            fs::write(format!("{}/dir/a.txt", repo_path), "Hello World").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("dir")));
+            crate::tests::commit(repo_path, "first");
+
+            let blob = "5e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689\n";
+            assert_eq!(blob, rev_parse(repo_path, "HEAD:dir/a.txt"));
+            assert_eq!(blob, rev_parse(repo_path, ":dir/a.txt"));
+            assert_eq!(rev_parse(repo_path, "HEAD^{tree}"), rev_parse(repo_path, "HEAD:"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("HEAD:dir/b.txt")),
+                String::from("fatal: path 'dir/b.txt' does not exist in 'HEAD'"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("HEAD:dir/a.txt^{commit}")),
+                String::from("fatal: path 'dir/a.txt^{commit}' does not exist in 'HEAD'"));
+        });
+    }
+
+    #[test]
+    fn test_resolve_index_stages() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/a.txt", repo_path), "base").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut index = repository.index_take().unwrap();
+            index.load_for_update().unwrap();
+            let mut oids = Vec::new();
+            for (stage, content) in ["ours", "theirs"].iter().enumerate() {
+                let mut blob = Blob::new(content.as_bytes().to_vec());
+                repository.database().store(&mut blob).unwrap();
+                index.add_unmerged(Path::new("a.txt"), stage as u8 + 2, *blob.get_oid(), MinigitMetadata::from_mode(0o100644));
+                oids.push(*blob.get_oid());
+            }
+            index.write_updates().unwrap();
+            drop(index);
+
+            assert_eq!(format!("{}\n", oids[0]), rev_parse(repo_path, ":2:a.txt"));
+            assert_eq!(format!("{}\n", oids[1]), rev_parse(repo_path, ":3:a.txt"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from(":a.txt")),
+                String::from("fatal: path 'a.txt' is in the index, but not at stage 0\nhint: Did you mean ':2:a.txt'?"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from(":1:a.txt")),
+                String::from("fatal: path 'a.txt' is in the index, but not at stage 1\nhint: Did you mean ':2:a.txt'?"));
+        });
+    }
+
+    #[test]
+    fn test_resolve_reflog_entries() {
+        crate::tests::run_test(|repo_path| {
+            let old = "1111111111111111111111111111111111111111";
+            let new = "2222222222222222222222222222222222222222";
+            fs::create_dir_all(format!("{}/.git/logs", repo_path)).unwrap();
+            fs::write(format!("{}/.git/HEAD", repo_path), format!("{}\n", new)).unwrap();
+            fs::write(format!("{}/.git/logs/HEAD", repo_path), format!(
+                "0000000000000000000000000000000000000000 {} A <a@b.c> 1500000000 +0000\tcommit (initial): one\n\
+                {} {} A <a@b.c> 1500000001 +0000\tcommit: two\n", old, old, new)).unwrap();
+
+            assert_eq!(format!("{}\n", new), rev_parse(repo_path, "@{0}"));
+            assert_eq!(format!("{}\n", old), rev_parse(repo_path, "HEAD@{1}"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("HEAD@{2}")),
+                String::from("fatal: log for 'HEAD' only has 2 entries"));
+        });
+    }
+
+    #[test]
+    fn test_ambiguous_abbreviation() {
+        crate::tests::run_test(|repo_path| {
+            // Both blobs have an id starting with "8324".
+            fs::write(format!("{}/a.txt", repo_path), "142").unwrap();
+            fs::write(format!("{}/b.txt", repo_path), "784").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt"), String::from("b.txt")));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("8324")),
+                String::from("error: short object ID 8324 is ambiguous\n\
+                hint: The candidates are:\n\
+                hint:   83248fb blob\n\
+                hint:   83249da blob\n\
+                fatal: ambiguous argument '8324': unknown revision or path not in the working tree.\n\
+                Use '--' to separate paths from revisions, like this:\n\
+                'git <command> [<revision>...] -- [<file>...]'"));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("83249")),
+                String::from("83249da5a136e0e0e9388917c65b042ef5179b5e\n"));
+        });
+    }
+
+    #[test]
+    fn test_verify_and_short() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/a.txt", repo_path), "one").unwrap();
+            crate::tests::execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("a.txt")));
+            let head = crate::tests::commit(repo_path, "first");
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("--short"), String::from("HEAD")),
+                format!("{}\n", head.abbreviate(7)));
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("--short=10"), String::from("HEAD"), String::from("--"), String::from("a.txt")),
+                format!("{}\n--\na.txt\n", head.abbreviate(10)));
+            let error = crate::tests::execute_and_get_result(repo_path, vec!(String::new(), String::from("rev-parse"), String::from("--bogus")));
+            assert_eq!(crate::minigiterror::ErrorKind::Usage, error.err().unwrap().kind);
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("--verify"), String::from("unknown")),
+                String::from("fatal: Needed a single revision"));
+            crate::tests::execute_and_expect_error_message(
+                repo_path,
+                vec!(String::new(), String::from("rev-parse"), String::from("unknown")),
+                String::from("fatal: ambiguous argument 'unknown': unknown revision or path not in the working tree.\n\
+                Use '--' to separate paths from revisions, like this:\n\
+                'git <command> [<revision>...] -- [<file>...]'"));
+        });
+    }
+}
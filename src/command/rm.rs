@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{Blob, TreeEntry};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::pathspec::Pathspec;
+use crate::repository::Repository;
+
+pub struct RmCommand;
+
+struct Options {
+    dry_run: bool,
+    cached: bool,
+    force: bool,
+    recursive: bool,
+    quiet: bool,
+}
+
+impl Command for RmCommand {
+    const USAGE: Usage = Usage::new(&["minigit rm [<options>] [--] <file>..."], &[
+        Opt::flag('n', "dry-run", "dry run"),
+        Opt::flag('q', "quiet", "do not list removed files"),
+        Opt::long("cached", "only remove from the index"),
+        Opt::flag('f', "force", "override the up-to-date check"),
+        Opt::short('r', "allow recursive removal"),
+    ]);
+    const NEEDS_WORK_TREE: bool = true;
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let options = Options {
+            dry_run: parsed.is_set("dry-run"),
+            cached: parsed.is_set("cached"),
+            force: parsed.is_set("force"),
+            recursive: parsed.is_set("r"),
+            quiet: parsed.is_set("quiet"),
+        };
+        let pathspecs = parsed.args;
+        if pathspecs.is_empty() {
+            return Err(Self::USAGE.error());
+        }
+
+        let mut repository = runtime.repository()?;
+        repository.index()?.load_for_update()?;
+        let paths = match_paths(&mut repository, &pathspecs, options.recursive)?;
+        if !options.force {
+            check_modifications(&mut repository, &paths, options.cached)?;
+        }
+        for path in paths.iter() {
+            if !options.quiet {
+                writeln!(&mut runtime.stdout, "rm '{}'", path.display()).unwrap();
+            }
+            if options.dry_run {
+                continue;
+            }
+            repository.index()?.remove(path);
+            if !options.cached {
+                repository.workspace().remove_file(path)?;
+            }
+        }
+        repository.index()?.write_updates()?;
+        Ok(())
+    }
+}
+
+/// Returns the tracked files designated by the pathspecs, which must all match something.
+fn match_paths(repository: &mut Repository, pathspecs: &[String], recursive: bool) -> MinigitResult<Vec<PathBuf>> {
+    let pathspec = Pathspec::parse(pathspecs, repository.workspace())?;
+    let tracked_paths: Vec<PathBuf> = repository.index()?.get_entries().iter().map(|entry| PathBuf::from(entry.get_path())).collect();
+    if let Some(unmatched) = pathspec.find_unmatched(tracked_paths.iter().map(PathBuf::as_path)).first() {
+        return Err(MinigitError::fatal(format!("fatal: pathspec '{}' did not match any files", unmatched)));
+    }
+    let mut paths: Vec<PathBuf> = tracked_paths.into_iter().filter(|path| pathspec.matches(path)).collect();
+    if !recursive {
+        if let Some(directory) = paths.iter().find_map(|path| pathspec.find_recursive_match(path)) {
+            return Err(MinigitError::fatal(format!("fatal: not removing '{}' recursively without -r", directory)));
+        }
+    }
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Refuses to lose content which is neither in HEAD nor, unless only the index is updated, in the workspace.
+fn check_modifications(repository: &mut Repository, paths: &[PathBuf], cached: bool) -> MinigitResult<()> {
+    let head_files = match repository.refs().read_head()? {
+        Some(head) => {
+            let tree_oid = *repository.database().load_commit(&head)?.get_tree();
+            repository.database().load_tree_list(&tree_oid)?
+        }
+        None => BTreeMap::new(),
+    };
+    let mut both_changed = Vec::new();
+    let mut staged_changes = Vec::new();
+    let mut local_changes = Vec::new();
+    for path in paths {
+        // Like git, removing an unmerged path resolves the conflict, so all its stages go without any check.
+        let index_oid = match repository.index()?.get_entry(path) {
+            Some(entry) => *entry.get_oid(),
+            None => continue,
+        };
+        let staged = head_files.get(path).map(|entry: &TreeEntry| entry.oid) != Some(index_oid);
+        let local = match repository.workspace().exists(path) {
+            true if repository.workspace().is_gitlink(path) => repository.submodule(path).refs().read_head()? != Some(index_oid),
+            true => {
+                let blob = Blob::new(repository.workspace().read_file(path)?);
+                repository.database().hash_object(&blob) != index_oid
+            }
+            false => false,
+        };
+        match (staged, local, cached) {
+            (true, true, _) => both_changed.push(path),
+            (true, false, false) => staged_changes.push(path),
+            (false, true, false) => local_changes.push(path),
+            _ => {}
+        }
+    }
+    let mut messages = Vec::new();
+    if !both_changed.is_empty() {
+        messages.push(error_message(&both_changed, "has staged content different from both the\nfile and the HEAD:",
+                                    "have staged content different from both the\nfile and the HEAD:", "(use -f to force removal)"));
+    }
+    if !staged_changes.is_empty() {
+        messages.push(error_message(&staged_changes, "has changes staged in the index:", "have changes staged in the index:",
+                                    "(use --cached to keep the file, or -f to force removal)"));
+    }
+    if !local_changes.is_empty() {
+        messages.push(error_message(&local_changes, "has local modifications:", "have local modifications:",
+                                    "(use --cached to keep the file, or -f to force removal)"));
+    }
+    match messages.is_empty() {
+        true => Ok(()),
+        false => Err(MinigitError::new(messages.join("\n"))),
+    }
+}
+
+fn error_message(paths: &[&PathBuf], singular: &str, plural: &str, hint: &str) -> String {
+    let mut message = match paths.len() {
+        1 => format!("error: the following file {}", singular),
+        _ => format!("error: the following files {}", plural),
+    };
+    for path in paths {
+        message.push_str(&format!("\n    {}", path.display()));
+    }
+    message.push('\n');
+    message.push_str(hint);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::gitobject::GitObject;
+    use crate::tests::*;
+    use crate::workspace::MinigitMetadata;
+
+    use super::*;
+
+    fn rm(repo_path: &str, args: &[&str]) -> MinigitResult<String> {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("rm"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            crate::command::execute(&mut runtime)?;
+        }
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn write_and_add(repo_path: &str, path: &str, content: &str) {
+        let full_path = PathBuf::from(repo_path).join(path);
+        fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+        fs::write(full_path, content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(path)));
+    }
+
+    fn exists(repo_path: &str, path: &str) -> bool {
+        PathBuf::from(repo_path).join(path).exists()
+    }
+
+    #[test]
+    fn test_remove_files() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.txt", "b");
+            write_and_add(repo_path, "dir/c.txt", "c");
+            commit(repo_path, "first");
+
+            assert_eq!("rm 'a.txt'\n", rm(repo_path, &["a.txt", "-n"]).unwrap());
+            assert!(exists(repo_path, "a.txt"));
+            assert_eq!("rm 'a.txt'\n", rm(repo_path, &["a.txt"]).unwrap());
+            assert!(!exists(repo_path, "a.txt"));
+            assert_eq!("", rm(repo_path, &["--cached", "-q", "b.txt"]).unwrap());
+            assert!(exists(repo_path, "b.txt"));
+            assert_index(repo_path, vec!((0o100644, String::from("dir/c.txt"))));
+
+            assert_eq!("fatal: not removing 'dir' recursively without -r", rm(repo_path, &["dir"]).err().unwrap().message);
+            assert_eq!("fatal: pathspec 'b.txt' did not match any files", rm(repo_path, &["b.txt"]).err().unwrap().message);
+            assert_eq!("rm 'dir/c.txt'\n", rm(repo_path, &["-r", "dir/"]).unwrap());
+            assert!(!exists(repo_path, "dir"));
+            assert_index(repo_path, vec!());
+        });
+    }
+
+    #[test]
+    fn test_remove_with_wildcards() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.rs", "b");
+            write_and_add(repo_path, "dir/c.txt", "c");
+            write_and_add(repo_path, "dir/d.txt", "d");
+            commit(repo_path, "first");
+
+            assert_eq!("rm 'a.txt'\nrm 'dir/d.txt'\n", rm(repo_path, &["*.txt", ":!dir/c.txt"]).unwrap());
+            assert_eq!("fatal: pathspec '*.md' did not match any files", rm(repo_path, &["*.md"]).err().unwrap().message);
+            assert_eq!("rm 'b.rs'\n", rm(repo_path, &["--cached", ":(icase)B.RS"]).unwrap());
+            assert_index(repo_path, vec!((0o100644, String::from("dir/c.txt"))));
+        });
+    }
+
+    #[test]
+    fn test_refuse_to_lose_changes() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "a");
+            write_and_add(repo_path, "b.txt", "b");
+            commit(repo_path, "first");
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "modified").unwrap();
+            write_and_add(repo_path, "b.txt", "staged");
+            write_and_add(repo_path, "c.txt", "new");
+
+            assert_eq!("error: the following file has local modifications:\n    a.txt\n(use --cached to keep the file, or -f to force removal)",
+                       rm(repo_path, &["a.txt"]).err().unwrap().message);
+            assert_eq!("error: the following files have changes staged in the index:\n    b.txt\n    c.txt\n\
+                        (use --cached to keep the file, or -f to force removal)",
+                       rm(repo_path, &["b.txt", "c.txt"]).err().unwrap().message);
+            // Only the index is updated, so the content stays in the workspace.
+            rm(repo_path, &["--cached", "a.txt", "b.txt"]).unwrap();
+            fs::write(PathBuf::from(repo_path).join("c.txt"), "modified").unwrap();
+            assert_eq!("error: the following file has staged content different from both the\nfile and the HEAD:\n    c.txt\n(use -f to force removal)",
+                       rm(repo_path, &["--cached", "c.txt"]).err().unwrap().message);
+            rm(repo_path, &["-f", "c.txt"]).unwrap();
+            assert!(!exists(repo_path, "c.txt"));
+            assert_index(repo_path, vec!());
+        });
+    }
+
+    #[test]
+    fn test_remove_unmerged_paths() {
+        run_test(|repo_path| {
+            write_and_add(repo_path, "a.txt", "base");
+            write_and_add(repo_path, "b.txt", "base");
+            commit(repo_path, "first");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut index = repository.index_take().unwrap();
+            index.load_for_update().unwrap();
+            for (stage, content) in ["base", "ours", "theirs"].iter().enumerate() {
+                let mut blob = Blob::new(content.as_bytes().to_vec());
+                repository.database().store(&mut blob).unwrap();
+                for path in ["a.txt", "b.txt"].iter() {
+                    index.add_unmerged(Path::new(path), stage as u8 + 1, *blob.get_oid(), MinigitMetadata::from_mode(0o100644));
+                }
+            }
+            index.write_updates().unwrap();
+            drop(index);
+            fs::write(PathBuf::from(repo_path).join("a.txt"), "conflicted").unwrap();
+
+            assert_eq!("rm 'a.txt'\n", rm(repo_path, &["a.txt"]).unwrap());
+            assert!(!exists(repo_path, "a.txt"));
+            assert_eq!("rm 'b.txt'\n", rm(repo_path, &["--cached", "b.txt"]).unwrap());
+            assert!(exists(repo_path, "b.txt"));
+            assert_index(repo_path, vec!());
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
+use crate::refs::HEAD;
+use crate::repository::Repository;
+
+pub struct ShowRefCommand;
+
+const DEFAULT_ABBREV_LEN: usize = 7;
+
+struct Options {
+    heads: bool,
+    tags: bool,
+    head: bool,
+    verify: bool,
+    dereference: bool,
+    quiet: bool,
+    hash_only: bool,
+    abbrev: usize,
+}
+
+impl Command for ShowRefCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit show-ref [-q | --quiet] [--verify] [--head] [-d | --dereference] [-s | --hash[=<n>]] [--abbrev[=<n>]] [--tags] [--heads] [--] [<pattern>...]",
+    ], &[
+        Opt::long("tags", "only show tags (can be combined with heads)"),
+        Opt::long("heads", "only show heads (can be combined with tags)"),
+        Opt::long("verify", "stricter reference checking, requires exact ref path"),
+        Opt::long("head", "show the HEAD reference, even if it would be filtered out"),
+        Opt::flag('d', "dereference", "dereference tags into object IDs"),
+        Opt::flag('s', "hash", "only show SHA1 hash using <n> digits").optional_value("<n>"),
+        Opt::long("abbrev", "use <n> digits to display object names").optional_value("<n>"),
+        Opt::flag('q', "quiet", "do not print results to stdout (useful with --verify)"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let mut options = Options {
+            heads: parsed.is_set("heads"),
+            tags: parsed.is_set("tags"),
+            head: parsed.is_set("head"),
+            verify: parsed.is_set("verify"),
+            dereference: parsed.is_set("dereference"),
+            quiet: parsed.is_set("quiet"),
+            hash_only: parsed.is_set("hash"),
+            abbrev: OID_HEX_LEN,
+        };
+        for (name, value) in parsed.occurrences(&["hash", "abbrev"]) {
+            match (name, value) {
+                (_, Some(value)) => options.abbrev = parse_abbrev(value)?,
+                ("abbrev", None) => options.abbrev = DEFAULT_ABBREV_LEN,
+                _ => {}
+            }
+        }
+        let patterns = parsed.args;
+
+        let mut repository = runtime.repository()?;
+        let refs = match options.verify {
+            true => verify_refs(&mut repository, &patterns, options.quiet)?,
+            false => match_refs(&mut repository, &patterns, &options)?,
+        };
+        if refs.is_empty() {
+            return Err(MinigitError::new(String::new()));
+        }
+        if !options.quiet {
+            for (name, oid) in refs {
+                // With `--dereference`, an annotated tag is followed by the object it points to.
+                let peeled = match options.dereference && repository.database().read_type(&oid)? == "tag" {
+                    true => vec!((name.clone(), oid), (format!("{}^{{}}", name), repository.database().peel(&oid)?)),
+                    false => vec!((name, oid)),
+                };
+                for (name, oid) in peeled {
+                    match options.hash_only {
+                        true => writeln!(&mut runtime.stdout, "{}", oid.abbreviate(options.abbrev)).unwrap(),
+                        false => writeln!(&mut runtime.stdout, "{} {}", oid.abbreviate(options.abbrev), name).unwrap(),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_abbrev(value: &str) -> MinigitResult<usize> {
+    match value.parse() {
+        Ok(abbrev) => Ok(abbrev),
+        Err(_) => Err(ShowRefCommand::USAGE.error_with("option `abbrev' expects a numerical value")),
+    }
+}
+
+/// With `--verify`, every argument must be the full name of an existing ref.
+fn verify_refs(repository: &mut Repository, names: &[String], quiet: bool) -> MinigitResult<Vec<(String, ObjectId)>> {
+    if names.is_empty() {
+        return Err(MinigitError::fatal(String::from("fatal: --verify requires a reference")));
+    }
+    let mut refs = Vec::new();
+    for name in names {
+        let oid = match name.starts_with("refs/") || name == HEAD {
+            true => repository.refs().resolve_ref(name)?,
+            false => None,
+        };
+        match oid {
+            Some(oid) => refs.push((name.clone(), oid)),
+            None if quiet => return Err(MinigitError::new(String::new())),
+            None => return Err(MinigitError::fatal(format!("fatal: '{}' - not a valid ref", name))),
+        }
+    }
+    Ok(refs)
+}
+
+fn match_refs(repository: &mut Repository, patterns: &[String], options: &Options) -> MinigitResult<Vec<(String, ObjectId)>> {
+    let mut refs = Vec::new();
+    if options.head {
+        if let Some(oid) = repository.refs().read_head()? {
+            refs.push((String::from(HEAD), oid));
+        }
+    }
+    for (name, oid) in repository.refs().list_refs("refs/")? {
+        let kind_matches = (!options.heads && !options.tags)
+            || (options.heads && name.starts_with("refs/heads/"))
+            || (options.tags && name.starts_with("refs/tags/"));
+        if kind_matches && (patterns.is_empty() || patterns.iter().any(|pattern| matches_tail(&name, pattern))) {
+            refs.push((name, oid));
+        }
+    }
+    Ok(refs)
+}
+
+/// A pattern matches the ref names it is a trailing sequence of components of: `master` matches `refs/heads/master`
+/// and `refs/remotes/origin/master`, but not `refs/heads/mymaster`.
+fn matches_tail(name: &str, pattern: &str) -> bool {
+    name == pattern || name.ends_with(&format!("/{}", pattern.trim_start_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::tests::*;
+
+    use super::*;
+
+    fn show_ref(repo_path: &str, args: &[&str]) -> String {
+        let mut command = vec!(String::new(), String::from("show-ref"));
+        command.extend(args.iter().map(|arg| String::from(*arg)));
+        execute_and_get_stdout(repo_path, command)
+    }
+
+    fn create_ref(repo_path: &str, full_name: &str, oid: ObjectId) {
+        let path = PathBuf::from(repo_path).join(".git").join(full_name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", oid)).unwrap();
+    }
+
+    #[test]
+    fn test_list_and_filter_refs() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            create_ref(repo_path, "refs/tags/v1.0", first);
+            create_ref(repo_path, "refs/remotes/origin/master", first);
+            create_ref(repo_path, "refs/heads/mymaster", first);
+
+            assert_eq!(format!("{} refs/heads/master\n{} refs/heads/mymaster\n{} refs/remotes/origin/master\n{} refs/tags/v1.0\n",
+                               second, first, first, first),
+                       show_ref(repo_path, &[]));
+            assert_eq!(format!("{} refs/heads/master\n{} refs/remotes/origin/master\n", second, first), show_ref(repo_path, &["master"]));
+            assert_eq!(format!("{} refs/heads/master\n", second), show_ref(repo_path, &["--heads", "master"]));
+            assert_eq!(format!("{} refs/tags/v1.0\n", first), show_ref(repo_path, &["--tags"]));
+            assert_eq!(format!("{} HEAD\n{} refs/tags/v1.0\n", second, first), show_ref(repo_path, &["--head", "--tags"]));
+            assert_eq!(format!("{}\n", first.abbreviate(7)), show_ref(repo_path, &["--hash=7", "v1.0"]));
+            assert_eq!(format!("{} refs/tags/v1.0\n", first.abbreviate(10)), show_ref(repo_path, &["--abbrev=10", "tags/v1.0"]));
+            assert_eq!("", show_ref(repo_path, &["-q", "v1.0"]));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("show-ref"), String::from("v2.0")), String::new());
+        });
+    }
+
+    #[test]
+    fn test_dereference_tags() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let tag = write_tag(repo_path, "v1.0", first, "Release\n", 1_500_000_000);
+            create_ref(repo_path, "refs/tags/light", first);
+            assert_eq!(format!("{} refs/tags/light\n{} refs/tags/v1.0\n{} refs/tags/v1.0^{{}}\n", first, tag, first),
+                       show_ref(repo_path, &["--tags", "-d"]));
+            assert_eq!(format!("{}\n{}\n", tag, first), show_ref(repo_path, &["--dereference", "-s", "v1.0"]));
+        });
+    }
+
+    #[test]
+    fn test_verify() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            assert_eq!(format!("{} refs/heads/master\n{} HEAD\n", first, first), show_ref(repo_path, &["--verify", "refs/heads/master", "HEAD"]));
+            assert_eq!(format!("{}\n", first), show_ref(repo_path, &["--verify", "-s", "refs/heads/master"]));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("show-ref"), String::from("--verify"), String::from("master")),
+                                             String::from("fatal: 'master' - not a valid ref"));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("show-ref"), String::from("--verify"), String::from("-q"), String::from("refs/heads/missing")),
+                                             String::new());
+        });
+    }
+}
@@ -2,17 +2,31 @@ use std::collections::BTreeSet;
 use std::path::Path;
 
 use crate::command::{Command, Runtime};
+use crate::command::parseopt::Usage;
 use crate::minigiterror::MinigitResult;
+use crate::pathspec::Pathspec;
 use crate::repository::Repository;
 
 pub struct StatusCommand;
 
 impl Command for StatusCommand {
+    const USAGE: Usage = Usage::new(&["minigit status [--] [<pathspec>...]"], &[]);
+    const NEEDS_WORK_TREE: bool = true;
+
     fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
-        let mut repository = Repository::new(runtime.dir.to_path_buf());
+        let args = runtime.args[2..].to_vec();
+        let pathspecs = Self::USAGE.parse(runtime, &args)?.args;
+        let mut repository = runtime.repository()?;
         repository.index()?.load_for_update()?;
+        let pathspec = match pathspecs.is_empty() {
+            true => None,
+            false => Some(Pathspec::parse(&pathspecs, repository.workspace())?),
+        };
         let mut untracked = BTreeSet::new();
-        scan_workspace(&mut repository, &mut untracked, &runtime.dir)?;
+        scan_workspace(&mut repository, &mut untracked, Path::new(""))?;
+        if let Some(pathspec) = pathspec {
+            untracked = filter_untracked(&mut repository, untracked, &pathspec)?;
+        }
         for path in untracked {
             writeln!(&mut runtime.stdout, "?? {}", path).unwrap();
         }
@@ -23,7 +37,8 @@ impl Command for StatusCommand {
 fn scan_workspace(repository: &mut Repository, untracked: &mut BTreeSet<String>, root: &Path) -> MinigitResult<()> {
     for path in repository.workspace().list_dir(&root)? {
         if repository.index()?.is_path_tracked(&path) {
-            if repository.workspace().is_dir(&path)? {
+            // A tracked submodule is another repository, whose files are not listed.
+            if repository.workspace().is_dir(&path)? && !repository.workspace().is_gitlink(&path) {
                 scan_workspace(repository, untracked, &path)?;
             }
         } else if is_trackable_file(repository, &path)? {
@@ -37,8 +52,29 @@ fn scan_workspace(repository: &mut Repository, untracked: &mut BTreeSet<String>,
     Ok(())
 }
 
+/// Keeps the untracked paths matching the pathspec. A directory not matched as a whole is replaced by its matching files.
+fn filter_untracked(repository: &mut Repository, untracked: BTreeSet<String>, pathspec: &Pathspec) -> MinigitResult<BTreeSet<String>> {
+    let mut filtered = BTreeSet::new();
+    for path in untracked {
+        match path.strip_suffix(std::path::MAIN_SEPARATOR) {
+            Some(directory) if !pathspec.matches(Path::new(directory)) => {
+                for file in repository.workspace().list_files_from_path(Path::new(directory))? {
+                    if pathspec.matches(&file) {
+                        filtered.insert(format!("{}", file.display()));
+                    }
+                }
+            }
+            _ if pathspec.matches(Path::new(path.trim_end_matches(std::path::MAIN_SEPARATOR))) => {
+                filtered.insert(path);
+            }
+            _ => {}
+        }
+    }
+    Ok(filtered)
+}
+
 fn is_trackable_file(repository: &mut Repository, path: &Path) -> MinigitResult<bool> {
-    if repository.workspace().is_file(path)? {
+    if repository.workspace().is_file(path)? || repository.workspace().is_gitlink(path) {
         return Ok(!repository.index()?.is_path_tracked(path));
     }
     if !repository.workspace().is_dir(path)? {
@@ -133,4 +169,27 @@ mod tests {
                 "?? outer/\n".to_string());
         });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_list_untracked_files_matching_pathspec() {
+        crate::tests::run_test(|repo_path| {
+            fs::write(format!("{}/alice.txt", repo_path), "Alice").unwrap();
+            fs::write(format!("{}/bob.rs", repo_path), "Bob").unwrap();
+            fs::create_dir_all(format!("{}/dir/nested", repo_path)).unwrap();
+            fs::write(format!("{}/dir/carol.txt", repo_path), "Carol").unwrap();
+            fs::write(format!("{}/dir/nested/dave.rs", repo_path), "Dave").unwrap();
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("status"), String::from("dir")),
+                "?? dir/\n".to_string());
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("status"), String::from("*.txt")),
+                "?? alice.txt\n?? dir/carol.txt\n".to_string());
+            crate::tests::execute_and_expect_success_message(
+                repo_path,
+                vec!(String::new(), String::from("status"), String::from(":!*.txt")),
+                "?? bob.rs\n?? dir/\n".to_string());
+        });
+    }
+}
@@ -0,0 +1,305 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::command::{Command, Runtime};
+use crate::command::clone::{CloneOptions, clone_repository};
+use crate::command::parseopt::{Opt, Usage};
+use crate::command::reset::reset_index;
+use crate::config::Config;
+use crate::gitobject::GITLINK_MODE;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+
+pub struct SubmoduleCommand;
+
+const UPDATE_USAGE: Usage = Usage::new(&["minigit submodule update [--init] [--] [<path>...]"], &[
+    Opt::long("init", "initialize uninitialized submodules before update"),
+]);
+
+/// A gitlink of the index with its `.gitmodules` entry.
+struct Submodule {
+    name: String,
+    path: PathBuf,
+    oid: ObjectId,
+    unmerged: bool,
+}
+
+impl Command for SubmoduleCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit submodule [status] [--] [<path>...]",
+        "minigit submodule init [--] [<path>...]",
+        "minigit submodule update [--init] [--] [<path>...]",
+    ], &[]);
+    const NEEDS_WORK_TREE: bool = true;
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let mut args = runtime.args[2..].to_vec();
+        let subcommand = match args.first().map(String::as_str) {
+            Some("status" | "init" | "update") => args.remove(0),
+            _ => String::from("status"),
+        };
+        let usage = match subcommand.as_str() {
+            "update" => &UPDATE_USAGE,
+            _ => &Self::USAGE,
+        };
+        let parsed = usage.parse(runtime, &args)?;
+        let init = parsed.is_set("init");
+        let pathspecs = parsed.args;
+
+        let mut repository = runtime.repository()?;
+        let submodules = list_submodules(&mut repository, &pathspecs)?;
+        match subcommand.as_str() {
+            "status" => status(runtime, &mut repository, &submodules),
+            "init" => init_submodules(runtime, &mut repository, &submodules),
+            _ => {
+                if init {
+                    init_submodules(runtime, &mut repository, &submodules)?;
+                }
+                update(runtime, &mut repository, &submodules)
+            }
+        }
+    }
+}
+
+/// Lists the gitlinks of the index matching the pathspecs, named after the `.gitmodules` section giving their path.
+fn list_submodules(repository: &mut Repository, pathspecs: &[String]) -> MinigitResult<Vec<Submodule>> {
+    let gitmodules_path = repository.workspace().get_path().join(".gitmodules");
+    let gitmodules = match gitmodules_path.is_file() {
+        true => Config::load(gitmodules_path)?,
+        false => Config::parse("")?,
+    };
+    let pathspec = Pathspec::parse(pathspecs, repository.workspace())?;
+    let mut submodules: Vec<Submodule> = Vec::new();
+    for entry in repository.index_take()?.load_and_get_entries()? {
+        let path = PathBuf::from(entry.get_path());
+        if entry.get_mode() != GITLINK_MODE || !pathspec.matches(&path) {
+            continue;
+        }
+        if let Some(submodule) = submodules.iter_mut().find(|submodule| submodule.path == path) {
+            submodule.unmerged = true;
+            continue;
+        }
+        let name = gitmodules.get_subsections("submodule").into_iter()
+            .find(|name| gitmodules.get(&format!("submodule.{}.path", name)).map(Path::new) == Some(path.as_path()))
+            .ok_or_else(|| MinigitError::fatal(format!("fatal: no submodule mapping found in .gitmodules for path '{}'", path.display())))?;
+        submodules.push(Submodule { name: String::from(name), path, oid: *entry.get_oid(), unmerged: entry.get_stage() != 0 });
+    }
+    if let Some(unmatched) = pathspec.find_unmatched(submodules.iter().map(|submodule| submodule.path.as_path())).first() {
+        return Err(MinigitError::new(format!("error: pathspec '{}' did not match any file(s) known to git", unmatched)));
+    }
+    Ok(submodules)
+}
+
+/// Prints each submodule prefixed by `-` when it is not checked out, `+` when it is checked out at another commit than
+/// the recorded one, and `U` when it has merge conflicts.
+fn status(runtime: &mut Runtime, repository: &mut Repository, submodules: &[Submodule]) -> MinigitResult<()> {
+    for submodule in submodules {
+        let mut sub_repository = repository.submodule(&submodule.path);
+        let head = match sub_repository.get_path().join(HEAD).is_file() {
+            true => sub_repository.refs().read_head()?,
+            false => None,
+        };
+        let line = match head {
+            _ if submodule.unmerged => format!("U{} {}", ObjectId::null().to_hex(), submodule.path.display()),
+            None => format!("-{} {}", submodule.oid.to_hex(), submodule.path.display()),
+            Some(head) => format!("{}{} {} ({})", if head == submodule.oid { ' ' } else { '+' }, head.to_hex(), submodule.path.display(),
+                                  describe(&mut sub_repository, head)?),
+        };
+        writeln!(&mut runtime.stdout, "{}", line).unwrap();
+    }
+    Ok(())
+}
+
+/// Names a commit after a tag pointing to it, then after a branch, or else abbreviates it.
+fn describe(repository: &mut Repository, oid: ObjectId) -> MinigitResult<String> {
+    for (name, tag_oid) in repository.refs().list_refs("refs/tags/")? {
+        if repository.database().peel(&tag_oid)? == oid {
+            return Ok(String::from(&name["refs/tags/".len()..]));
+        }
+    }
+    for prefix in ["refs/heads/", "refs/remotes/"].iter() {
+        if let Some((name, _)) = repository.refs().list_refs(prefix)?.into_iter().find(|(_, ref_oid)| *ref_oid == oid) {
+            return Ok(String::from(&name["refs/".len()..]));
+        }
+    }
+    Ok(oid.abbreviate(7))
+}
+
+/// Copies the URL of the submodules from `.gitmodules` to the configuration, where relative URLs are resolved against
+/// the URL of the `origin` remote, or else the workspace.
+fn init_submodules(runtime: &mut Runtime, repository: &mut Repository, submodules: &[Submodule]) -> MinigitResult<()> {
+    let gitmodules_path = repository.workspace().get_path().join(".gitmodules");
+    let gitmodules = Config::load(gitmodules_path)?;
+    let config_path = repository.get_path().join("config");
+    for submodule in submodules {
+        if Config::load(config_path.clone())?.get(&format!("submodule.{}.url", submodule.name)).is_some() {
+            continue;
+        }
+        let url = gitmodules.get(&format!("submodule.{}.url", submodule.name))
+            .ok_or_else(|| MinigitError::fatal(format!("fatal: No url found for submodule path '{}' in .gitmodules", submodule.path.display())))?;
+        let url = match url.starts_with("./") || url.starts_with("../") {
+            true => {
+                let base = match repository.config()?.get("remote.origin.url") {
+                    Some(base) => String::from(base),
+                    None => repository.workspace().get_path().to_string_lossy().into_owned(),
+                };
+                resolve_relative_url(&base, url)
+            }
+            false => String::from(url),
+        };
+        Config::set_value(&config_path, &format!("submodule.{}.url", submodule.name), &url)?;
+        Config::set_value(&config_path, &format!("submodule.{}.active", submodule.name), "true")?;
+        writeln!(&mut runtime.stderr, "Submodule '{}' ({}) registered for path '{}'", submodule.name, url, submodule.path.display()).unwrap();
+    }
+    Ok(())
+}
+
+/// Each `../` removes a component of the base, and `./` none.
+fn resolve_relative_url(base: &str, url: &str) -> String {
+    let mut base = base.trim_end_matches('/');
+    let mut url = url;
+    loop {
+        if let Some(rest) = url.strip_prefix("./") {
+            url = rest;
+        } else if let Some(rest) = url.strip_prefix("../") {
+            url = rest;
+            base = base.rfind('/').map(|index| &base[..index]).unwrap_or("");
+        } else {
+            break;
+        }
+    }
+    format!("{}/{}", base, url)
+}
+
+/// Clones the initialized submodules which are missing, and checks out the recorded commit with a detached HEAD.
+fn update(runtime: &mut Runtime, repository: &mut Repository, submodules: &[Submodule]) -> MinigitResult<()> {
+    let identity = runtime.reflog_identity();
+    // Read again, as `--init` may have just changed it.
+    let config = Config::load(repository.get_path().join("config"))?;
+    for submodule in submodules {
+        let url = match config.get(&format!("submodule.{}.url", submodule.name)) {
+            Some(url) => String::from(url),
+            None => {
+                writeln!(&mut runtime.stderr, "Submodule path '{}' not initialized\nMaybe you want to use 'update --init'?",
+                         submodule.path.display()).unwrap();
+                continue;
+            }
+        };
+        if submodule.unmerged {
+            writeln!(&mut runtime.stdout, "Skipping unmerged submodule {}", submodule.path.display()).unwrap();
+            continue;
+        }
+        let workspace_path = repository.workspace().get_path().join(&submodule.path);
+        let cloned = fs::symlink_metadata(workspace_path.join(".git")).is_err();
+        if cloned {
+            writeln!(&mut runtime.stderr, "Cloning into '{}'...", workspace_path.display()).unwrap();
+            let options = CloneOptions { bare: false, branch: None, depth: None, quiet: false };
+            if let Err(e) = clone_repository(runtime, &url, &workspace_path.join(".git"), &options) {
+                return Err(MinigitError::fatal(format!("{}\nfatal: clone of '{}' into submodule path '{}' failed",
+                                                     e.message, url, workspace_path.display())));
+            }
+        }
+
+        // A new clone has no checkout yet, even when its HEAD is already the recorded commit.
+        let mut sub_repository = repository.submodule(&submodule.path);
+        if !cloned && sub_repository.refs().read_head()? == Some(submodule.oid) {
+            continue;
+        }
+        if !sub_repository.database().exists(&submodule.oid) {
+            return Err(MinigitError::fatal(format!("fatal: Unable to find current revision {} in submodule path '{}'",
+                                                 submodule.oid.to_hex(), submodule.path.display())));
+        }
+        let tree_oid = *sub_repository.database().load_commit(&submodule.oid)?.get_tree();
+        let target = sub_repository.database().load_tree_list(&tree_oid)?;
+        reset_index(&mut sub_repository, &target, true)?;
+        let old_head = sub_repository.refs().read_head()?;
+        let mut transaction = sub_repository.refs().transaction(&identity);
+        transaction.set_deref(false);
+        transaction.update(HEAD, submodule.oid, old_head, &format!("checkout: moving to {}", submodule.oid.to_hex()));
+        transaction.commit()?;
+        writeln!(&mut runtime.stdout, "Submodule path '{}': checked out '{}'", submodule.path.display(), submodule.oid.to_hex()).unwrap();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::command::{execute, Runtime};
+    use crate::gitobject::GITLINK_MODE;
+    use crate::objectid::ObjectId;
+    use crate::repository::Repository;
+    use crate::tests::*;
+    use crate::workspace::MinigitMetadata;
+
+    fn submodule(repo_path: &str, args: &[&str]) -> String {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), stderr: Box::new(std::io::sink()), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("submodule"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            runtime.env.insert(String::from("GIT_AUTHOR_NAME"), String::from("A U Thor"));
+            runtime.env.insert(String::from("GIT_AUTHOR_EMAIL"), String::from("author@example.com"));
+            execute(&mut runtime).unwrap();
+        }
+        String::from_utf8(stdout).unwrap()
+    }
+
+    fn commit_file(repo_path: &str, name: &str, content: &str) -> ObjectId {
+        fs::write(format!("{}/{}", repo_path, name), content).unwrap();
+        execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(name)));
+        commit(repo_path, name)
+    }
+
+    #[test]
+    fn test_init_update_and_status() {
+        run_test(|repo_path| {
+            let upstream_path = format!("{}/upstream", repo_path);
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("init"), upstream_path.clone()));
+            let oid = commit_file(&upstream_path, "a.txt", "A");
+            fs::write(format!("{}/.gitmodules", repo_path), "[submodule \"lib\"]\n\tpath = lib\n\turl = ./upstream\n").unwrap();
+            {
+                let mut repository = Repository::new(PathBuf::from(repo_path));
+                repository.index().unwrap().load_for_update().unwrap();
+                repository.index().unwrap().add(Path::new("lib"), oid, MinigitMetadata::from_mode(GITLINK_MODE));
+                repository.index().unwrap().write_updates().unwrap();
+            }
+
+            assert_eq!(format!("-{} lib\n", oid), submodule(repo_path, &[]));
+            assert_eq!("", submodule(repo_path, &["update"]));
+            assert_eq!(format!("Submodule path 'lib': checked out '{}'\n", oid), submodule(repo_path, &["update", "--init"]));
+            assert_eq!("A", fs::read_to_string(format!("{}/lib/a.txt", repo_path)).unwrap());
+            let config = fs::read_to_string(format!("{}/.git/config", repo_path)).unwrap();
+            assert!(config.contains(&format!("[submodule \"lib\"]\n\turl = {}\n\tactive = true\n", upstream_path)));
+            assert_eq!(format!(" {} lib (heads/master)\n", oid), submodule(repo_path, &["status", "lib"]));
+            execute_and_expect_success_message(repo_path, vec!(String::new(), String::from("status")), String::from("?? .gitmodules\n?? upstream/\n"));
+        });
+    }
+
+    #[test]
+    fn test_add_checked_out_submodule() {
+        run_test(|repo_path| {
+            let lib_path = format!("{}/lib", repo_path);
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("init"), lib_path.clone()));
+            execute_and_expect_error_message(repo_path, vec!(String::new(), String::from("add"), String::from("lib")),
+                                             String::from("error: 'lib' does not have a commit checked out\nfatal: adding files failed"));
+            let first_oid = commit_file(&lib_path, "a.txt", "A");
+            fs::write(format!("{}/.gitmodules", repo_path), "[submodule \"lib\"]\n\tpath = lib\n\turl = ./lib\n").unwrap();
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from(".")));
+            assert_index(repo_path, vec!((0o100644, String::from(".gitmodules")), (GITLINK_MODE, String::from("lib"))));
+
+            let second_oid = commit_file(&lib_path, "b.txt", "B");
+            assert_eq!(format!("+{} lib (heads/master)\n", second_oid), submodule(repo_path, &[]));
+            execute_and_expect_success(repo_path, vec!(String::new(), String::from("add"), String::from("-u")));
+            let repository_oid = Repository::new(PathBuf::from(repo_path)).index_take().unwrap().load_and_get_entries().unwrap()
+                .into_iter().find(|entry| entry.get_mode() == GITLINK_MODE).map(|entry| *entry.get_oid());
+            assert_ne!(Some(first_oid), repository_oid);
+            assert_eq!(Some(second_oid), repository_oid);
+        });
+    }
+}
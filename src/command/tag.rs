@@ -0,0 +1,352 @@
+use std::fs;
+
+use chrono::Local;
+
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::{Author, GitObject, Tag};
+use crate::mergebase::MergeBase;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::pathspec::wildmatch;
+use crate::refs::{HEAD, is_valid_ref_name};
+use crate::repository::Repository;
+use crate::revision::Revision;
+
+pub struct TagCommand;
+
+const TAGS_PREFIX: &str = "refs/tags/";
+const SHORT_OID_LEN: usize = 7;
+/// The width of the tag names when their message is listed with `-n`.
+const NAME_WIDTH: usize = 15;
+
+struct Options {
+    annotate: bool,
+    force: bool,
+    messages: Vec<String>,
+    message_file: Option<String>,
+    lines: Option<usize>,
+    contains: Option<String>,
+}
+
+enum Mode {
+    List,
+    Delete,
+    Create,
+}
+
+impl Command for TagCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit tag [-a | -f] [-m <msg> | -F <file>] <tagname> [<commit> | <object>]",
+        "minigit tag -d <tagname>...",
+        "minigit tag [-n[<num>]] [-l] [--contains <commit>] [<pattern>...]",
+    ], &[
+        Opt::flag('l', "list", "list tag names"),
+        Opt::short('n', "print <n> lines of each tag message").optional_value("<n>"),
+        Opt::flag('d', "delete", "delete tags"),
+        Opt::flag('a', "annotate", "annotated tag, needs a message"),
+        Opt::flag('m', "message", "tag message").value("<message>"),
+        Opt::flag('F', "file", "read message from file").value("<file>"),
+        Opt::flag('f', "force", "replace the tag if exists"),
+        Opt::long("contains", "print only tags that contain the commit").value("<commit>").last_arg_default(HEAD),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let lines = match (parsed.get_flag("n"), parsed.value("n")) {
+            (Some(true), Some(lines)) => match lines.parse() {
+                Ok(lines) => Some(lines),
+                Err(_) => return Err(Self::USAGE.error_with("switch `n' expects a numerical value")),
+            },
+            (Some(true), None) => Some(1),
+            _ => None,
+        };
+        let options = Options {
+            annotate: parsed.is_set("annotate"),
+            force: parsed.is_set("force"),
+            messages: parsed.values("message").into_iter().map(String::from).collect(),
+            message_file: parsed.value("file").map(String::from),
+            lines,
+            contains: parsed.value("contains").map(String::from),
+        };
+        let mode = match parsed.last_of(&["list", "delete"]) {
+            Some("list") => Some(Mode::List),
+            Some(_) => Some(Mode::Delete),
+            None => None,
+        };
+        let values = parsed.args;
+
+        // Without a tag name, or with a listing option, the tags are listed.
+        let mode = mode.unwrap_or(match values.is_empty() || options.lines.is_some() || options.contains.is_some() {
+            true => Mode::List,
+            false => Mode::Create,
+        });
+        let mut repository = runtime.repository()?;
+        match mode {
+            Mode::List => list_tags(runtime, &mut repository, &values, &options),
+            Mode::Delete => delete_tags(runtime, &mut repository, &values),
+            Mode::Create => {
+                if values.len() > 2 {
+                    return Err(Self::USAGE.error());
+                }
+                let message = read_message(runtime, &options)?;
+                create_tag(runtime, &mut repository, &values[0], values.get(1).map_or(HEAD, |target| target.as_str()), message, options.force)
+            }
+        }
+    }
+}
+
+/// Returns the message of an annotated tag, or `None` for a lightweight tag.
+fn read_message(runtime: &mut Runtime, options: &Options) -> MinigitResult<Option<String>> {
+    let message = match &options.message_file {
+        Some(_) if !options.messages.is_empty() => return Err(MinigitError::fatal(String::from("fatal: only one -F or -m option is allowed."))),
+        Some(path) if path == "-" => runtime.read_from_stdin()?,
+        Some(path) => match fs::read_to_string(runtime.dir.join(path)) {
+            Ok(content) => content,
+            Err(e) => return Err(MinigitError::fatal(format!("fatal: could not open or read '{}': {}", path, e))),
+        },
+        // Each -m option is a paragraph.
+        None if !options.messages.is_empty() => options.messages.join("\n\n"),
+        None if options.annotate => return Err(MinigitError::fatal(String::from("fatal: no tag message given (use -m or -F)"))),
+        None => return Ok(None),
+    };
+    Ok(Some(cleanup_message(&message)))
+}
+
+/// Removes comment lines, trailing whitespace, and leading, trailing and repeated blank lines.
+fn cleanup_message(message: &str) -> String {
+    let mut cleaned = String::new();
+    let mut blank_lines = 0;
+    for line in message.lines().filter(|line| !line.starts_with('#')).map(|line| line.trim_end()) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if blank_lines > 0 && !cleaned.is_empty() {
+            cleaned.push('\n');
+        }
+        blank_lines = 0;
+        cleaned.push_str(line);
+        cleaned.push('\n');
+    }
+    cleaned
+}
+
+fn create_tag(runtime: &mut Runtime, repository: &mut Repository, name: &str, target: &str, message: Option<String>, force: bool) -> MinigitResult<()> {
+    let full_name = format!("{}{}", TAGS_PREFIX, name);
+    if name.starts_with('-') || !is_valid_ref_name(&full_name) {
+        return Err(MinigitError::fatal(format!("fatal: '{}' is not a valid tag name.", name)));
+    }
+    let target_oid = match Revision::parse(target).and_then(|revision| revision.resolve(repository)) {
+        Ok(oid) => oid,
+        Err(_) => return Err(MinigitError::fatal(format!("fatal: Failed to resolve '{}' as a valid ref.", target))),
+    };
+    let current_oid = repository.refs().resolve_ref(&full_name)?;
+    if current_oid.is_some() && !force {
+        return Err(MinigitError::fatal(format!("fatal: tag '{}' already exists", name)));
+    }
+
+    let tagger = runtime.identity()?;
+    let oid = match message {
+        Some(message) => {
+            let object_type = repository.database().read_type(&target_oid)?;
+            let mut tag = Tag::new(&target_oid, &object_type, name, tagger.clone(), &message);
+            repository.database().store(&mut tag)?;
+            *tag.get_oid()
+        }
+        None => target_oid,
+    };
+    let mut transaction = repository.refs().transaction(&tagger);
+    match force {
+        true => transaction.update(&full_name, oid, None, ""),
+        false => transaction.create(&full_name, oid, ""),
+    }
+    transaction.commit()?;
+    if let Some(current_oid) = current_oid {
+        if current_oid != oid {
+            writeln!(&mut runtime.stdout, "Updated tag '{}' (was {})", name, current_oid.abbreviate(SHORT_OID_LEN)).unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// Deletes every tag which exists, and fails at the end if some did not.
+fn delete_tags(runtime: &mut Runtime, repository: &mut Repository, names: &[String]) -> MinigitResult<()> {
+    let mut errors = Vec::new();
+    for name in names {
+        let full_name = format!("{}{}", TAGS_PREFIX, name);
+        let oid = match repository.refs().resolve_ref(&full_name)? {
+            Some(oid) => oid,
+            None => {
+                errors.push(format!("error: tag '{}' not found.", name));
+                continue;
+            }
+        };
+        let identity = Author::new("", "", Local::now());
+        let mut transaction = repository.refs().transaction(&identity);
+        transaction.delete(&full_name, Some(oid), "");
+        transaction.commit()?;
+        writeln!(&mut runtime.stdout, "Deleted tag '{}' (was {})", name, oid.abbreviate(SHORT_OID_LEN)).unwrap();
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(MinigitError::new(errors.join("\n"))),
+    }
+}
+
+fn list_tags(runtime: &mut Runtime, repository: &mut Repository, patterns: &[String], options: &Options) -> MinigitResult<()> {
+    let contains = match &options.contains {
+        Some(revision) => Some(Revision::parse(revision)?.resolve_commit(repository)?),
+        None => None,
+    };
+    for (full_name, oid) in repository.refs().list_refs(TAGS_PREFIX)? {
+        let name = &full_name[TAGS_PREFIX.len()..];
+        if !patterns.is_empty() && !patterns.iter().any(|pattern| wildmatch(pattern.as_bytes(), name.as_bytes(), false)) {
+            continue;
+        }
+        if let Some(commit) = contains {
+            // Only the tags of commits, possibly through annotated tags, can contain a commit.
+            let peeled = repository.database().peel(&oid)?;
+            if repository.database().read_type(&peeled)? != "commit" || !MergeBase::new(repository).is_ancestor(commit, peeled)? {
+                continue;
+            }
+        }
+        match options.lines {
+            Some(lines) => {
+                let message = tag_message(repository, &oid)?;
+                let message: Vec<&str> = message.lines().take(lines).collect();
+                writeln!(&mut runtime.stdout, "{:<width$} {}", name, message.join("\n    "), width = NAME_WIDTH).unwrap();
+            }
+            None => writeln!(&mut runtime.stdout, "{}", name).unwrap(),
+        }
+    }
+    Ok(())
+}
+
+/// The message of an annotated tag, or of the commit a lightweight tag points to.
+fn tag_message(repository: &mut Repository, oid: &ObjectId) -> MinigitResult<String> {
+    match repository.database().read_type(oid)?.as_str() {
+        "tag" => Ok(String::from(repository.database().load_tag(oid)?.get_message())),
+        "commit" => Ok(String::from(repository.database().load_commit(oid)?.get_message())),
+        _ => Ok(String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::command::execute;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn tag(repo_path: &str, args: &[&str], stdin: &str) -> MinigitResult<String> {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime { dir: PathBuf::from(repo_path), stdout: Box::new(&mut stdout), ..Runtime::default() };
+            runtime.args = vec!(String::new(), String::from("tag"));
+            runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+            runtime.env.insert(String::from("GIT_AUTHOR_NAME"), String::from("A U Thor"));
+            runtime.env.insert(String::from("GIT_AUTHOR_EMAIL"), String::from("author@example.com"));
+            runtime.stdin = Box::new(Cursor::new(String::from(stdin).into_bytes()));
+            execute(&mut runtime)?;
+        }
+        Ok(String::from_utf8(stdout).unwrap())
+    }
+
+    fn rev_parse(repo_path: &str, revision: &str) -> String {
+        execute_and_get_stdout(repo_path, vec!(String::new(), String::from("rev-parse"), String::from(revision)))
+    }
+
+    fn read_tag(repo_path: &str, name: &str) -> ObjectId {
+        Repository::new(PathBuf::from(repo_path)).refs().resolve_ref(&format!("refs/tags/{}", name)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_create_lightweight_and_annotated_tags() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            tag(repo_path, &["light", "HEAD~1"], "").unwrap();
+            assert_eq!(first, read_tag(repo_path, "light"));
+
+            tag(repo_path, &["-a", "-m", "Release 1.0  ", "-m", "notes", "v1.0"], "").unwrap();
+            let oid = read_tag(repo_path, "v1.0");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let annotated = repository.database().load_tag(&oid).unwrap();
+            assert_eq!(second, *annotated.get_object());
+            assert_eq!("commit", annotated.get_object_type());
+            assert_eq!("v1.0", annotated.get_tag_name());
+            assert_eq!("A U Thor", annotated.get_tagger().unwrap().get_name());
+            assert_eq!("Release 1.0\n\nnotes\n", annotated.get_message());
+
+            assert_eq!(format!("{}\n", oid), rev_parse(repo_path, "v1.0"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "v1.0^{}"));
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "v1.0^{commit}"));
+            assert_eq!(format!("{}\n", first), rev_parse(repo_path, "v1.0~1"));
+
+            // A tag of a tag is peeled fully.
+            tag(repo_path, &["-F", "-", "nested", "v1.0"], "# comment\nNested\n").unwrap();
+            assert_eq!("Nested\n", repository.database().load_tag(&read_tag(repo_path, "nested")).unwrap().get_message());
+            assert_eq!(format!("{}\n", second), rev_parse(repo_path, "nested^{}"));
+        });
+    }
+
+    #[test]
+    fn test_create_errors_and_force() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            tag(repo_path, &["v1", "HEAD~1"], "").unwrap();
+            assert_eq!("fatal: tag 'v1' already exists", tag(repo_path, &["v1"], "").err().unwrap().message);
+            assert_eq!(format!("Updated tag 'v1' (was {})\n", first.abbreviate(7)), tag(repo_path, &["-f", "v1"], "").unwrap());
+            assert_eq!(second, read_tag(repo_path, "v1"));
+            assert_eq!("fatal: 'bad..name' is not a valid tag name.", tag(repo_path, &["bad..name"], "").err().unwrap().message);
+            assert_eq!("fatal: Failed to resolve 'missing' as a valid ref.", tag(repo_path, &["v2", "missing"], "").err().unwrap().message);
+            assert_eq!("fatal: no tag message given (use -m or -F)", tag(repo_path, &["-a", "v2"], "").err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_list_tags() {
+        run_test(|repo_path| {
+            commit(repo_path, "first\n\nbody line");
+            tag(repo_path, &["v1.0"], "").unwrap();
+            tag(repo_path, &["-m", "annotated\nsecond line\nthird line", "v2.0"], "").unwrap();
+            tag(repo_path, &["other"], "").unwrap();
+            assert_eq!("other\nv1.0\nv2.0\n", tag(repo_path, &[], "").unwrap());
+            assert_eq!("v1.0\nv2.0\n", tag(repo_path, &["-l", "v*"], "").unwrap());
+            assert_eq!("v1.0            first\nv2.0            annotated\n", tag(repo_path, &["-n", "v*"], "").unwrap());
+            assert_eq!("v2.0            annotated\n    second line\n", tag(repo_path, &["-n2", "-l", "v2*"], "").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_list_contains() {
+        run_test(|repo_path| {
+            commit(repo_path, "first");
+            tag(repo_path, &["-m", "old", "old"], "").unwrap();
+            let second = commit(repo_path, "second");
+            tag(repo_path, &["new"], "").unwrap();
+            assert_eq!("new\nold\n", tag(repo_path, &["--contains", "HEAD~1"], "").unwrap());
+            assert_eq!("new\n", tag(repo_path, &["--contains", &second.to_hex()], "").unwrap());
+            assert_eq!("new\n", tag(repo_path, &["--contains"], "").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_delete_tags() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            tag(repo_path, &["v1"], "").unwrap();
+            tag(repo_path, &["v2"], "").unwrap();
+            let error = tag(repo_path, &["-d", "v1", "missing"], "").err().unwrap();
+            assert_eq!("error: tag 'missing' not found.", error.message);
+            assert_eq!(format!("Deleted tag 'v2' (was {})\n", first.abbreviate(7)), tag(repo_path, &["-d", "v2"], "").unwrap());
+            assert_eq!("", tag(repo_path, &[], "").unwrap());
+        });
+    }
+}
@@ -0,0 +1,205 @@
+use crate::command::{Command, Runtime};
+use crate::command::parseopt::{Opt, Usage};
+use crate::gitobject::Author;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
+use crate::repository::Repository;
+use crate::revision::Revision;
+
+pub struct UpdateRefCommand;
+
+impl Command for UpdateRefCommand {
+    const USAGE: Usage = Usage::new(&[
+        "minigit update-ref [<options>] -d <refname> [<old-val>]",
+        "minigit update-ref [<options>]    <refname> <new-val> [<old-val>]",
+        "minigit update-ref [<options>] --stdin",
+    ], &[
+        Opt::short('m', "reason of the update").value("<reason>"),
+        Opt::short('d', "delete the reference"),
+        Opt::long("no-deref", "update <refname> not the one it points to"),
+        Opt::long("stdin", "read updates from stdin"),
+    ]);
+
+    fn execute(runtime: &mut Runtime) -> MinigitResult<()> {
+        let args = runtime.args[2..].to_vec();
+        let parsed = Self::USAGE.parse(runtime, &args)?;
+        let message = String::from(parsed.value("m").unwrap_or(""));
+        let delete = parsed.is_set("d");
+        let no_deref = parsed.is_set("no-deref");
+        let stdin = parsed.is_set("stdin");
+        let values = parsed.args;
+
+        let mut repository = runtime.repository()?;
+        let identity = runtime.reflog_identity();
+        if stdin {
+            if delete || !values.is_empty() {
+                return Err(Self::USAGE.error());
+            }
+            let input = runtime.read_from_stdin()?;
+            let commands = parse_commands(&mut repository, &input)?;
+            return apply_commands(repository, &identity, no_deref, &message, commands);
+        }
+
+        let valid_count = match delete {
+            true => values.len() == 1 || values.len() == 2,
+            false => values.len() == 2 || values.len() == 3,
+        };
+        if !valid_count {
+            return Err(Self::USAGE.error());
+        }
+        let command = match delete {
+            // Like git, a deletion with an empty or null old value doesn't check the ref.
+            true => StdinCommand::Delete(values[0].clone(), parse_old_value(&mut repository, values.get(1))?.filter(|oid| !oid.is_null())),
+            false => StdinCommand::Update(values[0].clone(), resolve_value(&mut repository, &values[1])?,
+                                          parse_old_value(&mut repository, values.get(2))?),
+        };
+        apply_commands(repository, &identity, no_deref, &message, vec!(command))
+    }
+}
+
+enum StdinCommand {
+    Update(String, ObjectId, Option<ObjectId>),
+    Create(String, ObjectId),
+    Delete(String, Option<ObjectId>),
+    Verify(String, Option<ObjectId>),
+}
+
+fn apply_commands(mut repository: Repository, identity: &Author, no_deref: bool, message: &str, commands: Vec<StdinCommand>) -> MinigitResult<()> {
+    let mut transaction = repository.refs().transaction(identity);
+    transaction.set_deref(!no_deref);
+    for command in commands {
+        match command {
+            StdinCommand::Update(name, new_oid, old_oid) => transaction.update(&name, new_oid, old_oid, message),
+            StdinCommand::Create(name, new_oid) => transaction.create(&name, new_oid, message),
+            StdinCommand::Delete(name, old_oid) => transaction.delete(&name, old_oid, message),
+            StdinCommand::Verify(name, old_oid) => transaction.verify(&name, old_oid),
+        }
+    }
+    transaction.commit()
+}
+
+/// Parses the commands of `update-ref --stdin`, one per line:
+/// `update <ref> <new> [<old>]`, `create <ref> <new>`, `delete <ref> [<old>]` and `verify <ref> [<old>]`.
+fn parse_commands(repository: &mut Repository, input: &str) -> MinigitResult<Vec<StdinCommand>> {
+    let mut commands = Vec::new();
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let words: Vec<&str> = line.split(' ').collect();
+        let arity_error = || MinigitError::fatal(format!("fatal: {}: wrong number of arguments", words[0]));
+        let command = match words[0] {
+            "update" => match words.len() {
+                3 | 4 => StdinCommand::Update(String::from(words[1]), resolve_value(repository, words[2])?,
+                                              parse_old_value(repository, words.get(3))?),
+                _ => return Err(arity_error()),
+            },
+            "create" => match words.len() {
+                3 => StdinCommand::Create(String::from(words[1]), resolve_value(repository, words[2])?),
+                _ => return Err(arity_error()),
+            },
+            "delete" => match words.len() {
+                2 | 3 => StdinCommand::Delete(String::from(words[1]), parse_old_value(repository, words.get(2))?),
+                _ => return Err(arity_error()),
+            },
+            "verify" => match words.len() {
+                2 | 3 => StdinCommand::Verify(String::from(words[1]), Some(parse_old_value(repository, words.get(2))?.unwrap_or_else(ObjectId::null))),
+                _ => return Err(arity_error()),
+            },
+            _ => return Err(MinigitError::fatal(format!("fatal: unknown command: {}", line))),
+        };
+        commands.push(command);
+    }
+    Ok(commands)
+}
+
+/// An empty old value, like the null id, means that the ref must not exist yet.
+fn parse_old_value<T: AsRef<str>>(repository: &mut Repository, value: Option<T>) -> MinigitResult<Option<ObjectId>> {
+    match value {
+        Some(value) if value.as_ref().is_empty() => Ok(Some(ObjectId::null())),
+        Some(value) => Ok(Some(resolve_value(repository, value.as_ref())?)),
+        None => Ok(None),
+    }
+}
+
+fn resolve_value(repository: &mut Repository, value: &str) -> MinigitResult<ObjectId> {
+    if value.len() == OID_HEX_LEN && ObjectId::is_hex_prefix(value) {
+        return ObjectId::from_hex(value);
+    }
+    match Revision::parse(value).and_then(|revision| revision.resolve(repository)) {
+        Ok(oid) => Ok(oid),
+        Err(_) => Err(MinigitError::fatal(format!("fatal: {}: not a valid SHA1", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::command::execute;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn update_ref(repo_path: &str, args: &[&str], stdin: &str) -> MinigitResult<()> {
+        let mut runtime = Runtime { dir: PathBuf::from(repo_path), ..Runtime::default() };
+        runtime.args = vec!(String::new(), String::from("update-ref"));
+        runtime.args.extend(args.iter().map(|arg| String::from(*arg)));
+        // Updating refs doesn't need an identity.
+        runtime.env.remove("GIT_AUTHOR_NAME");
+        runtime.env.remove("GIT_AUTHOR_EMAIL");
+        runtime.stdin = Box::new(Cursor::new(String::from(stdin).into_bytes()));
+        execute(&mut runtime)
+    }
+
+    fn read_ref(repo_path: &str, full_name: &str) -> Option<ObjectId> {
+        Repository::new(PathBuf::from(repo_path)).refs().resolve_ref(full_name).unwrap()
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            update_ref(repo_path, &["refs/heads/topic", "HEAD~1"], "").unwrap();
+            assert_eq!(Some(first), read_ref(repo_path, "refs/heads/topic"));
+            update_ref(repo_path, &["-m", "move topic", "refs/heads/topic", &second.to_hex(), &first.to_hex()], "").unwrap();
+            assert_eq!(Some(second), read_ref(repo_path, "refs/heads/topic"));
+            let reflog = Repository::new(PathBuf::from(repo_path)).refs().read_reflog("refs/heads/topic").unwrap();
+            assert_eq!("move topic", reflog[1].message);
+
+            let error = update_ref(repo_path, &["refs/heads/topic", &first.to_hex(), &first.to_hex()], "").err().unwrap();
+            assert_eq!(format!("fatal: cannot lock ref 'refs/heads/topic': is at {} but expected {}", second, first), error.message);
+            assert!(update_ref(repo_path, &["refs/heads/topic", &first.to_hex(), ""], "").is_err());
+
+            update_ref(repo_path, &["-d", "refs/heads/topic", &second.to_hex()], "").unwrap();
+            assert_eq!(None, read_ref(repo_path, "refs/heads/topic"));
+            update_ref(repo_path, &["refs/heads/topic", &first.to_hex()], "").unwrap();
+            update_ref(repo_path, &["-d", "refs/heads/topic", ""], "").unwrap();
+            assert_eq!(None, read_ref(repo_path, "refs/heads/topic"));
+            assert!(update_ref(repo_path, &["refs/heads/topic"], "").is_err());
+            assert_eq!("fatal: unknown: not a valid SHA1", update_ref(repo_path, &["refs/heads/topic", "unknown"], "").err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_stdin_batch() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            let input = format!("create refs/heads/a {}\nupdate refs/heads/master {} {}\n\nverify refs/heads/missing\n", first, first, second);
+            update_ref(repo_path, &["--stdin"], &input).unwrap();
+            assert_eq!(Some(first), read_ref(repo_path, "refs/heads/a"));
+            assert_eq!(Some(first), read_ref(repo_path, "refs/heads/master"));
+
+            // The failing verification leaves every ref untouched.
+            let input = format!("delete refs/heads/a\nupdate HEAD {}\nverify refs/heads/master {}\n", second, second);
+            assert!(update_ref(repo_path, &["--stdin"], &input).is_err());
+            assert_eq!(Some(first), read_ref(repo_path, "refs/heads/a"));
+            assert_eq!(Some(first), read_ref(repo_path, "refs/heads/master"));
+
+            assert_eq!("fatal: create: wrong number of arguments",
+                       update_ref(repo_path, &["--stdin"], "create refs/heads/b\n").err().unwrap().message);
+            assert_eq!("fatal: unknown command: move refs/heads/b",
+                       update_ref(repo_path, &["--stdin"], "move refs/heads/b\n").err().unwrap().message);
+        });
+    }
+}
@@ -0,0 +1,229 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lockfile::Lockfile;
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+
+/// The variables of a git configuration file such as `.git/config`.
+///
+/// Variables are named `section.name` or `section.subsection.name`. Section and variable names are case-insensitive,
+/// subsection names are not.
+pub struct Config {
+    variables: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn load(path: PathBuf) -> MinigitResult<Config> {
+        if !path.is_file() {
+            return Ok(Config { variables: Vec::new() });
+        }
+        match fs::read_to_string(&path) {
+            Ok(content) => Config::parse(&content),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", path.display(), e), e)),
+        }
+    }
+
+    pub fn parse(content: &str) -> MinigitResult<Config> {
+        let mut variables = Vec::new();
+        let mut section: Option<String> = None;
+        for (line_number, line) in content.lines().enumerate() {
+            let bad_line = || MinigitError::with_kind(ErrorKind::Corrupt, format!("fatal: bad config line {} in file .git/config", line_number + 1));
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                let end = line.find(']').ok_or_else(bad_line)?;
+                section = Some(parse_section_header(&line[1..end]).ok_or_else(bad_line)?);
+                continue;
+            }
+            let section = section.as_ref().ok_or_else(bad_line)?;
+            let (name, value) = match line.find('=') {
+                Some(index) => (line[..index].trim(), parse_value(line[index + 1..].trim()).ok_or_else(bad_line)?),
+                // A variable without value is a boolean set to true.
+                None => (line.split(['#', ';']).next().unwrap().trim(), String::from("true")),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(bad_line());
+            }
+            variables.push((format!("{}.{}", section, name.to_ascii_lowercase()), value));
+        }
+        Ok(Config { variables })
+    }
+
+    /// Returns the last value set for a variable, like `git config --get`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize_key(key);
+        self.variables.iter().rev()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the subsections of a section in the order they first appear, like the names of `[submodule "name"]`.
+    pub fn get_subsections(&self, section: &str) -> Vec<&str> {
+        let prefix = format!("{}.", section.to_ascii_lowercase());
+        let mut subsections = Vec::new();
+        for (name, _) in self.variables.iter() {
+            if let Some(subsection) = name.strip_prefix(&prefix).and_then(|rest| rest.rfind('.').map(|index| &rest[..index])) {
+                if !subsections.contains(&subsection) {
+                    subsections.push(subsection);
+                }
+            }
+        }
+        subsections
+    }
+
+    /// Sets a variable in a configuration file, like `git config <key> <value>`. The last line setting the variable is
+    /// replaced, otherwise the variable is added at the end of its section, which is created if needed.
+    pub fn set_value(path: &Path, key: &str, value: &str) -> MinigitResult<()> {
+        let content = match path.is_file() {
+            true => fs::read_to_string(path).map_err(|e| MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", path.display(), e), e))?,
+            false => String::new(),
+        };
+        let key = normalize_key(key);
+        let (section, name) = key.split_at(key.rfind('.').ok_or_else(|| MinigitError::new(format!("error: key does not contain a section: {}", key)))?);
+        let name = &name[1..];
+        let mut lines: Vec<String> = content.lines().map(String::from).collect();
+        let mut in_section = false;
+        let mut section_end = None;
+        let mut variable_line = None;
+        for (index, line) in lines.iter().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                in_section = trimmed.find(']').and_then(|end| parse_section_header(&trimmed[1..end])).as_deref() == Some(section);
+            } else if in_section && trimmed.split(|c: char| c == '=' || c.is_whitespace()).next().unwrap().eq_ignore_ascii_case(name) {
+                variable_line = Some(index);
+            }
+            if in_section {
+                section_end = Some(index + 1);
+            }
+        }
+        let variable = format!("\t{} = {}", name, quote_value(value));
+        match (variable_line, section_end) {
+            (Some(index), _) => lines[index] = variable,
+            (None, Some(index)) => lines.insert(index, variable),
+            (None, None) => {
+                lines.push(match section.find('.') {
+                    Some(index) => format!("[{} \"{}\"]", &section[..index], section[index + 1..].replace('\\', "\\\\").replace('"', "\\\"")),
+                    None => format!("[{}]", section),
+                });
+                lines.push(variable);
+            }
+        }
+        let mut lockfile = Lockfile::new(path.to_path_buf())?;
+        lockfile.write_str(&format!("{}\n", lines.join("\n")))?;
+        lockfile.commit()
+    }
+}
+
+/// Quotes a value when it would otherwise be read differently, escaping quotes and backslashes.
+fn quote_value(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t");
+    match value.contains(['#', ';']) || value.starts_with(char::is_whitespace) || value.ends_with(char::is_whitespace) {
+        true => format!("\"{}\"", escaped),
+        false => escaped,
+    }
+}
+
+/// Turns `core`, `remote "origin"` or the deprecated `remote.origin` into `core` or `remote.origin`.
+fn parse_section_header(header: &str) -> Option<String> {
+    let header = header.trim();
+    match header.find(|c: char| c.is_whitespace()) {
+        Some(index) => {
+            let subsection = header[index..].trim().strip_prefix('"')?.strip_suffix('"')?;
+            Some(format!("{}.{}", header[..index].to_ascii_lowercase(), subsection.replace("\\\"", "\"").replace("\\\\", "\\")))
+        }
+        None => match header.find('.') {
+            Some(index) => Some(format!("{}.{}", header[..index].to_ascii_lowercase(), header[index + 1..].to_ascii_lowercase())),
+            None => Some(header.to_ascii_lowercase()),
+        },
+    }
+}
+
+fn parse_value(raw: &str) -> Option<String> {
+    let mut value = String::new();
+    let mut in_quotes = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '#' | ';' if !in_quotes => break,
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'b' => { value.pop(); }
+                escaped @ ('"' | '\\') => value.push(escaped),
+                _ => return None,
+            },
+            c => value.push(c),
+        }
+    }
+    match in_quotes {
+        true => None,
+        false => Some(String::from(value.trim_end())),
+    }
+}
+
+fn normalize_key(key: &str) -> String {
+    let first_dot = key.find('.').unwrap_or(0);
+    let last_dot = key.rfind('.').unwrap_or(0);
+    format!("{}{}{}", key[..first_dot].to_ascii_lowercase(), &key[first_dot..last_dot], key[last_dot..].to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "[core]\n\
+                           \trepositoryformatversion = 0\n\
+                           \tbare = false\n\
+                           [remote \"origin\"]\n\
+                           \turl = https://example.com/repo.git # where it lives\n\
+                           \tfetch = +refs/heads/*:refs/remotes/origin/*\n\
+                           [branch \"Main\"]\n\
+                           \tremote = origin\n\
+                           \tmerge = refs/heads/main\n\
+                           ; comment\n\
+                           [Alias]\n\
+                           \tlg = \"log --format=\\\"%h %s\\\"\"\n\
+                           \tFlag\n\
+                           [core]\n\
+                           \tbare = true\n";
+
+    #[test]
+    fn test_get_values() {
+        let config = Config::parse(CONTENT).unwrap();
+        assert_eq!(Some("0"), config.get("core.repositoryformatversion"));
+        assert_eq!(Some("true"), config.get("CORE.Bare"));
+        assert_eq!(Some("https://example.com/repo.git"), config.get("remote.origin.url"));
+        assert_eq!(Some("origin"), config.get("branch.Main.remote"));
+        assert_eq!(None, config.get("branch.main.remote"));
+        assert_eq!(Some("log --format=\"%h %s\""), config.get("alias.lg"));
+        assert_eq!(Some("true"), config.get("alias.flag"));
+        assert_eq!(None, config.get("core.missing"));
+    }
+
+    #[test]
+    fn test_reject_malformed_files() {
+        assert!(Config::parse("name = value\n").is_err());
+        assert!(Config::parse("[core\n").is_err());
+        assert!(Config::parse("[core]\nname = \"unterminated\n").is_err());
+        assert!(Config::parse("[core]\nbad name = value\n").is_err());
+    }
+
+    #[test]
+    fn test_set_values() {
+        let path = std::env::temp_dir().join(format!("minigit_config_test_{}", std::process::id()));
+        fs::write(&path, "[core]\n\tbare = false\n[submodule \"lib\"]\n\turl = ../lib\n").unwrap();
+        Config::set_value(&path, "core.bare", "true").unwrap();
+        Config::set_value(&path, "submodule.lib.active", "true").unwrap();
+        Config::set_value(&path, "submodule.other.url", "/srv/other # repo").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!("[core]\n\tbare = true\n[submodule \"lib\"]\n\turl = ../lib\n\tactive = true\n[submodule \"other\"]\n\turl = \"/srv/other # repo\"\n", content);
+        let config = Config::parse(&content).unwrap();
+        assert_eq!(vec!("lib", "other"), config.get_subsections("submodule"));
+        assert_eq!(Some("/srv/other # repo"), config.get("submodule.other.url"));
+    }
+}
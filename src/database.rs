@@ -1,44 +1,257 @@
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crypto::digest::Digest;
 use crypto::sha1::Sha1;
 use flate2::Compression;
+use flate2::read::ZlibDecoder;
 use flate2::write::ZlibEncoder;
 
-use crate::gitobject::GitObject;
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::gitobject::{Blob, Commit, GitObject, Tag, Tree, TreeEntry};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
 
 pub struct Database {
-    path: PathBuf
+    path: PathBuf,
+    /// The commits whose parents are missing, in a shallow clone.
+    shallow: HashSet<ObjectId>,
+}
+
+pub enum DatabaseObject {
+    Blob(Blob),
+    Tree(Tree),
+    Commit(Commit),
+    Tag(Tag),
+}
+
+impl DatabaseObject {
+    pub fn get_type(&self) -> &str {
+        match self {
+            DatabaseObject::Blob(blob) => blob.get_type(),
+            DatabaseObject::Tree(tree) => tree.get_type(),
+            DatabaseObject::Commit(commit) => commit.get_type(),
+            DatabaseObject::Tag(tag) => tag.get_type(),
+        }
+    }
+
+    pub fn get_oid(&self) -> &ObjectId {
+        match self {
+            DatabaseObject::Blob(blob) => blob.get_oid(),
+            DatabaseObject::Tree(tree) => tree.get_oid(),
+            DatabaseObject::Commit(commit) => commit.get_oid(),
+            DatabaseObject::Tag(tag) => tag.get_oid(),
+        }
+    }
 }
 
 impl Database {
     pub fn new(path: PathBuf) -> Database {
-        Database { path }
+        Database { path, shallow: HashSet::new() }
+    }
+
+    /// Cuts the history at the given commits, which are then loaded without parents.
+    pub fn set_shallow(&mut self, shallow: HashSet<ObjectId>) {
+        self.shallow = shallow;
     }
 
     pub fn store<T: GitObject>(&self, gitobject: &mut T) -> MinigitResult<()> {
+        let bytes_buffer = self.serialize_object(gitobject);
+        let mut hasher = Sha1::new();
+        hasher.input(&bytes_buffer);
+        gitobject.set_oid(ObjectId::from_sha1(&mut hasher));
+
+        match self.write_object(gitobject.get_oid(), bytes_buffer) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Couldn't write bytes to disk: {}", e), e)),
+        }
+    }
+
+    /// Computes the id an object would be stored with, without writing it.
+    pub fn hash_object<T: GitObject>(&self, gitobject: &T) -> ObjectId {
+        let mut hasher = Sha1::new();
+        hasher.input(&self.serialize_object(gitobject));
+        ObjectId::from_sha1(&mut hasher)
+    }
+
+    fn serialize_object<T: GitObject>(&self, gitobject: &T) -> Vec<u8> {
         let mut bytes_buffer: Vec<u8> = Vec::new();
         bytes_buffer.extend_from_slice(gitobject.get_type().as_bytes());
         bytes_buffer.extend_from_slice(b" ");
         bytes_buffer.extend_from_slice(&gitobject.get_data().len().to_string().as_bytes());
         bytes_buffer.push(0);
         bytes_buffer.extend_from_slice(gitobject.get_data().as_slice());
+        bytes_buffer
+    }
 
-        let mut hasher = Sha1::new();
-        hasher.input(&bytes_buffer);
-        gitobject.set_oid(hasher.result_str());
+    pub fn exists(&self, oid: &ObjectId) -> bool {
+        self.object_path(oid).exists()
+    }
 
-        match self.write_object(gitobject.get_oid(), bytes_buffer) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(MinigitError::new(format!("Couldn't write bytes to disk: {}", e))),
+    pub fn load(&self, oid: &ObjectId) -> MinigitResult<DatabaseObject> {
+        let (object_type, data) = self.read_object(oid)?;
+        let mut object = match object_type.as_str() {
+            "blob" => DatabaseObject::Blob(Blob::new(data)),
+            "tree" => DatabaseObject::Tree(Tree::parse(data)?),
+            "commit" => DatabaseObject::Commit(Commit::parse(data)?),
+            "tag" => DatabaseObject::Tag(Tag::parse(data)?),
+            _ => return Err(MinigitError::fatal(format!("fatal: invalid object type \"{}\" for object {}", object_type, oid))),
+        };
+        match object {
+            DatabaseObject::Blob(ref mut blob) => blob.set_oid(*oid),
+            DatabaseObject::Tree(ref mut tree) => tree.set_oid(*oid),
+            DatabaseObject::Commit(ref mut commit) => {
+                commit.set_oid(*oid);
+                if self.shallow.contains(oid) {
+                    commit.remove_parents();
+                }
+            }
+            DatabaseObject::Tag(ref mut tag) => tag.set_oid(*oid),
+        }
+        Ok(object)
+    }
+
+    pub fn load_commit(&self, oid: &ObjectId) -> MinigitResult<Commit> {
+        match self.load(oid)? {
+            DatabaseObject::Commit(commit) => Ok(commit),
+            object => Err(MinigitError::fatal(format!("fatal: object {} is a {}, not a commit", oid, object.get_type()))),
         }
     }
 
-    fn write_object(&self, oid: &str, content: Vec<u8>) -> Result<(), io::Error> {
+    pub fn load_tree(&self, oid: &ObjectId) -> MinigitResult<Tree> {
+        match self.load(oid)? {
+            DatabaseObject::Tree(tree) => Ok(tree),
+            object => Err(MinigitError::fatal(format!("fatal: object {} is a {}, not a tree", oid, object.get_type()))),
+        }
+    }
+
+    pub fn load_blob(&self, oid: &ObjectId) -> MinigitResult<Blob> {
+        match self.load(oid)? {
+            DatabaseObject::Blob(blob) => Ok(blob),
+            object => Err(MinigitError::fatal(format!("fatal: object {} is a {}, not a blob", oid, object.get_type()))),
+        }
+    }
+
+    /// Lists every file of a tree and of its subtrees, by path.
+    pub fn load_tree_list(&self, tree_oid: &ObjectId) -> MinigitResult<BTreeMap<PathBuf, TreeEntry>> {
+        let mut list = BTreeMap::new();
+        self.build_tree_list(tree_oid, Path::new(""), &mut list)?;
+        Ok(list)
+    }
+
+    fn build_tree_list(&self, tree_oid: &ObjectId, prefix: &Path, list: &mut BTreeMap<PathBuf, TreeEntry>) -> MinigitResult<()> {
+        for entry in self.load_tree(tree_oid)?.get_entries() {
+            let path = prefix.join(&entry.name);
+            match entry.is_tree() {
+                true => self.build_tree_list(&entry.oid, &path, list)?,
+                false => {
+                    list.insert(path, entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load_tag(&self, oid: &ObjectId) -> MinigitResult<Tag> {
+        match self.load(oid)? {
+            DatabaseObject::Tag(tag) => Ok(tag),
+            object => Err(MinigitError::fatal(format!("fatal: object {} is a {}, not a tag", oid, object.get_type()))),
+        }
+    }
+
+    /// Follows annotated tags until reaching an object which is not a tag.
+    pub fn peel(&self, oid: &ObjectId) -> MinigitResult<ObjectId> {
+        if self.read_type(oid)? != "tag" {
+            return Ok(*oid);
+        }
+        let mut tag = self.load_tag(oid)?;
+        while tag.get_object_type() == "tag" {
+            tag = self.load_tag(tag.get_object())?;
+        }
+        Ok(*tag.get_object())
+    }
+
+    pub fn read_type(&self, oid: &ObjectId) -> MinigitResult<String> {
+        Ok(self.read_object(oid)?.0)
+    }
+
+    fn read_object(&self, oid: &ObjectId) -> MinigitResult<(String, Vec<u8>)> {
+        let compressed_content = match fs::read(self.object_path(oid)) {
+            Ok(content) => content,
+            Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: unable to read object {}: {}", oid, e), e)),
+        };
+        let mut content = Vec::new();
+        if let Err(e) = ZlibDecoder::new(compressed_content.as_slice()).read_to_end(&mut content) {
+            return Err(MinigitError::with_source(ErrorKind::Corrupt, format!("fatal: unable to inflate object {}: {}", oid, e), e));
+        }
+        let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, format!("fatal: loose object {} is corrupt", oid));
+        let space = content.iter().position(|byte| *byte == b' ').ok_or_else(corrupted)?;
+        let nul = content.iter().position(|byte| *byte == 0).ok_or_else(corrupted)?;
+        if nul < space {
+            return Err(corrupted());
+        }
+        let object_type = String::from_utf8_lossy(&content[..space]).into_owned();
+        let size: usize = std::str::from_utf8(&content[space + 1..nul]).ok()
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(corrupted)?;
+        let data = content.split_off(nul + 1);
+        if data.len() != size {
+            return Err(corrupted());
+        }
+        Ok((object_type, data))
+    }
+
+    /// Lists the ids of all stored objects whose hexadecimal representation starts with `prefix`.
+    pub fn prefix_match(&self, prefix: &str) -> MinigitResult<Vec<ObjectId>> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.len() < 2 {
+            return Ok(Vec::new());
+        }
+        let dir_path = self.path.join(&prefix[..2]);
+        if !dir_path.is_dir() {
+            return Ok(Vec::new());
+        }
+        let read_dir = match fs::read_dir(&dir_path) {
+            Ok(read_dir) => read_dir,
+            Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("Couldn't list objects in {}: {}", dir_path.display(), e), e)),
+        };
+        let mut oids = Vec::new();
+        for entry in read_dir.filter_map(|entry| entry.ok()) {
+            let hex = format!("{}{}", &prefix[..2], entry.file_name().to_string_lossy());
+            if hex.len() == OID_HEX_LEN && hex.starts_with(&prefix) {
+                if let Ok(oid) = ObjectId::from_hex(&hex) {
+                    oids.push(oid);
+                }
+            }
+        }
+        oids.sort();
+        Ok(oids)
+    }
+
+    /// Adds a stored object to another database, as a hard link to the same file when both are on the same filesystem.
+    pub fn link_object(&self, oid: &ObjectId, target: &Database) -> MinigitResult<()> {
+        let source_path = self.object_path(oid);
+        let target_path = target.object_path(oid);
+        if target_path.exists() {
+            return Ok(());
+        }
+        let result = fs::create_dir_all(target_path.parent().unwrap())
+            .and_then(|_| fs::hard_link(&source_path, &target_path).or_else(|_| fs::copy(&source_path, &target_path).map(|_| ())));
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("fatal: failed to copy object {}: {}", oid, e), e)),
+        }
+    }
+
+    fn object_path(&self, oid: &ObjectId) -> PathBuf {
+        let oid = oid.to_hex();
+        self.path.join(&oid[0..2]).join(&oid[2..])
+    }
+
+    fn write_object(&self, oid: &ObjectId, content: Vec<u8>) -> Result<(), io::Error> {
+        let oid = oid.to_hex();
         let mut root_path = PathBuf::from(&self.path);
         root_path.push(&oid[0..2]);
         if !root_path.exists() {
@@ -0,0 +1,205 @@
+/// Line diffs computed with Myers' algorithm, grouped in hunks like `diff -u`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditKind {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// A line of the diff. The line numbers start at 0 and give the position in both versions, even for the version which
+/// doesn't contain the line. The text keeps its line terminator, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edit<'a> {
+    pub kind: EditKind,
+    pub a_line: usize,
+    pub b_line: usize,
+    pub text: &'a str,
+}
+
+/// A range of edits, with some unchanged lines as context around the changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hunk {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits a text in lines which keep their terminator, so that joining them gives back the text.
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+pub fn diff<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Edit<'a>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                true => v[index(k + 1)],
+                false => v[index(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Walks back from the end through the furthest points reached at each step.
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let previous_k = match k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let previous_x = v[index(previous_k)];
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit { kind: EditKind::Equal, a_line: x as usize, b_line: y as usize, text: a[x as usize] });
+        }
+        if d > 0 {
+            match x == previous_x {
+                true => edits.push(Edit { kind: EditKind::Insert, a_line: x as usize, b_line: previous_y as usize, text: b[previous_y as usize] }),
+                false => edits.push(Edit { kind: EditKind::Delete, a_line: previous_x as usize, b_line: y as usize, text: a[previous_x as usize] }),
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    edits.reverse();
+    edits
+}
+
+/// Groups the changes in hunks, merging the changes separated by at most twice the context.
+pub fn hunks(edits: &[Edit], context: usize) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for (index, edit) in edits.iter().enumerate() {
+        if edit.kind == EditKind::Equal {
+            continue;
+        }
+        match hunks.last_mut() {
+            Some(hunk) if index <= hunk.end + 2 * context => hunk.end = index + 1,
+            _ => hunks.push(Hunk { start: index, end: index + 1 }),
+        }
+    }
+    for hunk in hunks.iter_mut() {
+        hunk.start = hunk.start.saturating_sub(context);
+        hunk.end = usize::min(hunk.end + context, edits.len());
+    }
+    hunks
+}
+
+/// Splits a hunk at each run of unchanged lines, which becomes the context of the hunks on both sides.
+pub fn split_hunk(edits: &[Edit], hunk: &Hunk) -> Vec<Hunk> {
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for (index, edit) in edits.iter().enumerate().take(hunk.end).skip(hunk.start) {
+        if edit.kind == EditKind::Equal {
+            continue;
+        }
+        match groups.last_mut() {
+            Some(group) if group.1 == index => group.1 = index + 1,
+            _ => groups.push((index, index + 1)),
+        }
+    }
+    (0..groups.len())
+        .map(|group| Hunk {
+            start: if group == 0 { hunk.start } else { groups[group - 1].1 },
+            end: if group + 1 == groups.len() { hunk.end } else { groups[group + 1].0 },
+        })
+        .collect()
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` line of the hunk, where a single line count is omitted.
+    pub fn header(&self, edits: &[Edit]) -> String {
+        let edits = &edits[self.start..self.end];
+        let a_length = edits.iter().filter(|edit| edit.kind != EditKind::Insert).count();
+        let b_length = edits.iter().filter(|edit| edit.kind != EditKind::Delete).count();
+        let range = |start: usize, length: usize| match length {
+            0 => format!("{},0", start),
+            1 => format!("{}", start + 1),
+            _ => format!("{},{}", start + 1, length),
+        };
+        format!("@@ -{} +{} @@", range(edits[0].a_line, a_length), range(edits[0].b_line, b_length))
+    }
+
+    pub fn is_splittable(&self, edits: &[Edit]) -> bool {
+        split_hunk(edits, self).len() > 1
+    }
+
+    /// The header and the lines of the hunk, each prefixed by ` `, `-` or `+`.
+    pub fn format(&self, edits: &[Edit]) -> String {
+        let mut output = format!("{}\n", self.header(edits));
+        for edit in edits[self.start..self.end].iter() {
+            output.push_str(&format_line(edit));
+        }
+        output
+    }
+}
+
+pub fn format_line(edit: &Edit) -> String {
+    let prefix = match edit.kind {
+        EditKind::Equal => ' ',
+        EditKind::Delete => '-',
+        EditKind::Insert => '+',
+    };
+    match edit.text.strip_suffix('\n') {
+        Some(text) => format!("{}{}\n", prefix, text),
+        None => format!("{}{}\n\\ No newline at end of file\n", prefix, edit.text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format_diff(a: &str, b: &str, context: usize) -> String {
+        let (a, b) = (split_lines(a), split_lines(b));
+        let edits = diff(&a, &b);
+        hunks(&edits, context).iter().map(|hunk| hunk.format(&edits)).collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let (a, b) = (split_lines("a\nb\nc\na\nb\nb\na\n"), split_lines("c\nb\na\nb\na\nc\n"));
+        let edits = diff(&a, &b);
+        assert_eq!(5, edits.iter().filter(|edit| edit.kind != EditKind::Equal).count());
+        let kept: Vec<&str> = edits.iter().filter(|edit| edit.kind != EditKind::Delete).map(|edit| edit.text).collect();
+        assert_eq!(b, kept);
+        assert_eq!(Vec::<Edit>::new(), diff(&[], &[]));
+    }
+
+    #[test]
+    fn test_hunks() {
+        let a = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let b = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\nthirteen";
+        assert_eq!("@@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n@@ -10,3 +10,4 @@\n 10\n 11\n 12\n+thirteen\n\\ No newline at end of file\n",
+                   format_diff(a, b, 3));
+        assert_eq!("@@ -3 +3 @@\n-3\n+three\n@@ -12,0 +13 @@\n+thirteen\n\\ No newline at end of file\n", format_diff(a, b, 0));
+        assert_eq!("@@ -0,0 +1 @@\n+new\n", format_diff("", "new\n", 3));
+    }
+
+    #[test]
+    fn test_split_hunk() {
+        let (a, b) = (split_lines("1\n2\n3\n4\n5\n"), split_lines("one\n2\n3\n4\nfive\n"));
+        let edits = diff(&a, &b);
+        let hunks = hunks(&edits, 3);
+        assert_eq!(1, hunks.len());
+        let split: Vec<String> = split_hunk(&edits, &hunks[0]).iter().map(|hunk| hunk.format(&edits)).collect();
+        assert_eq!(vec!("@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n", "@@ -2,4 +2,4 @@\n 2\n 3\n 4\n-5\n+five\n"), split);
+    }
+}
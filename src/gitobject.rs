@@ -1,27 +1,33 @@
 use std::cmp::min;
 use std::path::{Component, Path, PathBuf};
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeZone};
 
-use crate::{oid_to_compressed_u8_array, u16_to_u8_array_big_endian, u32_to_u8_array_big_endian};
-use crate::minigiterror::MinigitResult;
+use crate::{u16_to_u8_array_big_endian, u32_to_u8_array_big_endian};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
 use crate::workspace::MinigitMetadata;
 
+pub const TREE_MODE: u32 = 0o40000;
+pub const SYMLINK_MODE: u32 = 0o120000;
+/// The mode of a submodule, whose entry points to a commit of another repository.
+pub const GITLINK_MODE: u32 = 0o160000;
+
 pub trait GitObject {
     fn get_data(&self) -> &Vec<u8>;
     fn get_type(&self) -> &str;
-    fn get_oid(&self) -> &str;
-    fn set_oid(&mut self, oid: String);
+    fn get_oid(&self) -> &ObjectId;
+    fn set_oid(&mut self, oid: ObjectId);
 }
 
 pub struct Blob {
     data: Vec<u8>,
-    oid: String,
+    oid: ObjectId,
 }
 
 impl Blob {
     pub fn new(data: Vec<u8>) -> Blob {
-        Blob { data, oid: String::new() }
+        Blob { data, oid: ObjectId::null() }
     }
 }
 
@@ -34,17 +40,17 @@ impl GitObject for Blob {
         "blob"
     }
 
-    fn get_oid(&self) -> &str {
+    fn get_oid(&self) -> &ObjectId {
         &self.oid
     }
 
-    fn set_oid(&mut self, oid: String) {
+    fn set_oid(&mut self, oid: ObjectId) {
         self.oid = oid;
     }
 }
 
 trait TreeOrEntry {
-    fn get_oid(&self) -> &str;
+    fn get_oid(&self) -> &ObjectId;
     fn get_mode(&self) -> u32;
     fn get_name(&self) -> &str;
     fn add_entry(&mut self, components: Vec<String>, entry: Entry);
@@ -54,13 +60,13 @@ trait TreeOrEntry {
 pub struct Tree {
     entries: Vec<Box<TreeOrEntry>>,
     name: String,
-    oid: String,
+    oid: ObjectId,
     data: Vec<u8>,
 }
 
 impl Tree {
     fn new(name: &str) -> Tree {
-        Tree { entries: Vec::new(), name: String::from(name), oid: String::new(), data: Vec::new() }
+        Tree { entries: Vec::new(), name: String::from(name), oid: ObjectId::null(), data: Vec::new() }
     }
 
     pub fn build(entries: Vec<Entry>) -> Tree {
@@ -80,15 +86,47 @@ impl Tree {
     pub fn traverse(&mut self, function: &mut FnMut(&mut Tree) -> MinigitResult<()>) -> MinigitResult<()> {
         self.traverse_private(function)
     }
+
+    pub fn parse(data: Vec<u8>) -> MinigitResult<Tree> {
+        let mut tree = Tree::new("root");
+        let mut offset = 0;
+        while offset < data.len() {
+            let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, String::from("fatal: corrupted tree object"));
+            let space = data[offset..].iter().position(|byte| *byte == b' ').ok_or_else(corrupted)? + offset;
+            let nul = data[space..].iter().position(|byte| *byte == 0).ok_or_else(corrupted)? + space;
+            if data.len() < nul + 21 {
+                return Err(corrupted());
+            }
+            let mode = std::str::from_utf8(&data[offset..space]).ok()
+                .and_then(|mode| u32::from_str_radix(mode, 8).ok())
+                .ok_or_else(corrupted)?;
+            let name = std::str::from_utf8(&data[space + 1..nul]).map_err(|_| corrupted())?;
+            let oid = ObjectId::from_bytes(&data[nul + 1..nul + 21])?;
+            tree.entries.push(Box::new(TreeEntry { name: String::from(name), mode, oid }));
+            offset = nul + 21;
+        }
+        tree.data = data;
+        Ok(tree)
+    }
+
+    pub fn get_entries(&self) -> Vec<TreeEntry> {
+        self.entries.iter()
+            .map(|entry| TreeEntry { name: String::from(entry.get_name()), mode: entry.get_mode(), oid: *entry.get_oid() })
+            .collect()
+    }
+
+    pub fn get_entry(&self, name: &str) -> Option<TreeEntry> {
+        self.get_entries().into_iter().find(|entry| entry.name == name)
+    }
 }
 
 impl TreeOrEntry for Tree {
-    fn get_oid(&self) -> &str {
+    fn get_oid(&self) -> &ObjectId {
         &self.oid
     }
 
     fn get_mode(&self) -> u32 {
-        40000
+        TREE_MODE
     }
 
     fn get_name(&self) -> &str {
@@ -100,11 +138,9 @@ impl TreeOrEntry for Tree {
             self.entries.push(Box::new(entry));
         } else {
             let dir_name = components.remove(0);
-            match self.entries.last_mut() {
-                Some(ref mut tree) if dir_name == tree.get_name() => {
-                    tree.add_entry(components, entry);
-                }
-                _ => {
+            match self.entries.iter_mut().find(|tree| tree.get_mode() == TREE_MODE && tree.get_name() == dir_name) {
+                Some(tree) => tree.add_entry(components, entry),
+                None => {
                     let mut tree = Tree::new(&dir_name);
                     tree.add_entry(components, entry);
                     self.entries.push(Box::new(tree));
@@ -117,18 +153,29 @@ impl TreeOrEntry for Tree {
         for entry in self.entries.iter_mut() {
             entry.traverse_private(function)?;
         }
+        self.entries.sort_by_cached_key(|entry| tree_order_key(entry.as_ref()));
         self.data = Vec::new();
         for entry in self.entries.iter() {
             self.data.extend_from_slice(format!("{:o}", entry.get_mode()).as_bytes());
             self.data.extend_from_slice(" ".as_bytes());
             self.data.extend_from_slice(entry.get_name().as_bytes());
             self.data.push(0);
-            self.data.extend_from_slice(&oid_to_compressed_u8_array(entry.get_oid()));
+            self.data.extend_from_slice(entry.get_oid().as_bytes());
         }
         function(self)
     }
 }
 
+/// Git sorts the entries of a tree by name as if subtrees were named with a trailing `/`, so a subtree `foo` comes after
+/// the files `foo-bar` and `foo.txt` but before `foo0`.
+fn tree_order_key(entry: &dyn TreeOrEntry) -> Vec<u8> {
+    let mut key = entry.get_name().as_bytes().to_vec();
+    if entry.get_mode() == TREE_MODE {
+        key.push(b'/');
+    }
+    key
+}
+
 impl GitObject for Tree {
     fn get_data(&self) -> &Vec<u8> {
         &self.data
@@ -138,44 +185,155 @@ impl GitObject for Tree {
         "tree"
     }
 
-    fn get_oid(&self) -> &str {
+    fn get_oid(&self) -> &ObjectId {
         &self.oid
     }
 
-    fn set_oid(&mut self, oid: String) {
+    fn set_oid(&mut self, oid: ObjectId) {
         self.oid = oid;
     }
 }
 
+pub struct TreeEntry {
+    pub name: String,
+    pub mode: u32,
+    pub oid: ObjectId,
+}
+
+impl TreeEntry {
+    pub fn is_tree(&self) -> bool {
+        self.mode == TREE_MODE
+    }
+}
+
+impl TreeOrEntry for TreeEntry {
+    fn get_oid(&self) -> &ObjectId {
+        &self.oid
+    }
+
+    fn get_mode(&self) -> u32 {
+        self.mode
+    }
+
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn add_entry(&mut self, _components: Vec<String>, _entry: Entry) {
+        panic!("The method add_entry is not implemented for TreeEntry.");
+    }
+
+    fn traverse_private(&mut self, _function: &mut FnMut(&mut Tree) -> MinigitResult<()>) -> MinigitResult<()> {
+        Ok(())
+    }
+}
+
 pub struct Commit {
     data: Vec<u8>,
-    oid: String,
+    oid: ObjectId,
+    tree: ObjectId,
+    parents: Vec<ObjectId>,
+    author: Author,
+    committer: Author,
+    message: String,
 }
 
 impl Commit {
-    pub fn new(parent: &Option<String>, author: Author, message: &str, tree_oid: &str) -> Commit {
-        Commit {
-            data: Commit::build_data(parent, &author, message, tree_oid),
-            oid: String::new(),
-        }
-    }
-
-    fn build_data(parent: &Option<String>, author: &Author, message: &str, tree_oid: &str) -> Vec<u8> {
+    pub fn new(parents: Vec<ObjectId>, author: Author, message: &str, tree_oid: &ObjectId) -> Commit {
+        let mut commit = Commit {
+            data: Vec::new(),
+            oid: ObjectId::null(),
+            tree: *tree_oid,
+            parents,
+            committer: author.clone(),
+            author,
+            message: String::from(message),
+        };
+        commit.data = commit.build_data();
+        commit
+    }
+
+    fn build_data(&self) -> Vec<u8> {
         let mut data = Vec::new();
         data.extend_from_slice("tree ".as_bytes());
-        data.extend_from_slice(tree_oid.as_bytes());
-        if parent.is_some() {
+        data.extend_from_slice(self.tree.to_hex().as_bytes());
+        for parent in self.parents.iter() {
             data.extend_from_slice("\nparent ".as_bytes());
-            data.extend_from_slice(parent.as_ref().unwrap().as_bytes());
+            data.extend_from_slice(parent.to_hex().as_bytes());
         }
         data.extend_from_slice("\nauthor ".as_bytes());
-        data.extend_from_slice(author.to_string().as_bytes());
+        data.extend_from_slice(self.author.to_string().as_bytes());
         data.extend_from_slice("\ncommitter ".as_bytes());
-        data.extend_from_slice(author.to_string().as_bytes());
+        data.extend_from_slice(self.committer.to_string().as_bytes());
         data.extend_from_slice("\n\n".as_bytes());
-        data.extend_from_slice(message.as_bytes());
+        data.extend_from_slice(self.message.as_bytes());
         data
     }
+
+    pub fn parse(data: Vec<u8>) -> MinigitResult<Commit> {
+        let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, String::from("fatal: corrupted commit object"));
+        let text = String::from_utf8_lossy(&data).into_owned();
+        let (headers, message) = match text.find("\n\n") {
+            Some(index) => (&text[..index], &text[index + 2..]),
+            None => (text.as_str(), ""),
+        };
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        for line in headers.lines() {
+            let (key, value) = match line.find(' ') {
+                Some(index) => (&line[..index], &line[index + 1..]),
+                None => (line, ""),
+            };
+            match key {
+                "tree" => tree = Some(ObjectId::from_hex(value)?),
+                "parent" => parents.push(ObjectId::from_hex(value)?),
+                "author" => author = Some(Author::parse(value)?),
+                "committer" => committer = Some(Author::parse(value)?),
+                _ => {}
+            }
+        }
+        let author = author.ok_or_else(corrupted)?;
+        Ok(Commit {
+            oid: ObjectId::null(),
+            tree: tree.ok_or_else(corrupted)?,
+            parents,
+            committer: committer.unwrap_or_else(|| author.clone()),
+            author,
+            message: String::from(message),
+            data,
+        })
+    }
+
+    pub fn get_tree(&self) -> &ObjectId {
+        &self.tree
+    }
+
+    pub fn get_parents(&self) -> &Vec<ObjectId> {
+        &self.parents
+    }
+
+    /// Forgets the parents of a commit at the boundary of a shallow history. Its data is kept, and so is its id.
+    pub fn remove_parents(&mut self) {
+        self.parents.clear();
+    }
+
+    pub fn get_author(&self) -> &Author {
+        &self.author
+    }
+
+    pub fn get_committer(&self) -> &Author {
+        &self.committer
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_title_line(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
 }
 
 impl GitObject for Commit {
@@ -187,11 +345,126 @@ impl GitObject for Commit {
         "commit"
     }
 
-    fn get_oid(&self) -> &str {
+    fn get_oid(&self) -> &ObjectId {
+        &self.oid
+    }
+
+    fn set_oid(&mut self, oid: ObjectId) {
+        self.oid = oid;
+    }
+}
+
+pub struct Tag {
+    data: Vec<u8>,
+    oid: ObjectId,
+    object: ObjectId,
+    object_type: String,
+    tag_name: String,
+    tagger: Option<Author>,
+    message: String,
+}
+
+impl Tag {
+    pub fn new(object: &ObjectId, object_type: &str, tag_name: &str, tagger: Author, message: &str) -> Tag {
+        let mut tag = Tag {
+            data: Vec::new(),
+            oid: ObjectId::null(),
+            object: *object,
+            object_type: String::from(object_type),
+            tag_name: String::from(tag_name),
+            tagger: Some(tagger),
+            message: String::from(message),
+        };
+        tag.data = tag.build_data();
+        tag
+    }
+
+    fn build_data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(format!("object {}\ntype {}\ntag {}\n", self.object, self.object_type, self.tag_name).as_bytes());
+        if let Some(tagger) = &self.tagger {
+            data.extend_from_slice(format!("tagger {}\n", tagger.to_string()).as_bytes());
+        }
+        data.extend_from_slice("\n".as_bytes());
+        data.extend_from_slice(self.message.as_bytes());
+        data
+    }
+
+    pub fn parse(data: Vec<u8>) -> MinigitResult<Tag> {
+        let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, String::from("fatal: corrupted tag object"));
+        let text = String::from_utf8_lossy(&data).into_owned();
+        let (headers, message) = match text.find("\n\n") {
+            Some(index) => (&text[..index], &text[index + 2..]),
+            None => (text.as_str(), ""),
+        };
+        let mut object = None;
+        let mut object_type = None;
+        let mut tag_name = None;
+        let mut tagger = None;
+        for line in headers.lines() {
+            let (key, value) = match line.find(' ') {
+                Some(index) => (&line[..index], &line[index + 1..]),
+                None => (line, ""),
+            };
+            match key {
+                "object" => object = Some(ObjectId::from_hex(value)?),
+                "type" => object_type = Some(String::from(value)),
+                "tag" => tag_name = Some(String::from(value)),
+                "tagger" => tagger = Some(Author::parse(value)?),
+                _ => {}
+            }
+        }
+        Ok(Tag {
+            oid: ObjectId::null(),
+            object: object.ok_or_else(corrupted)?,
+            object_type: object_type.ok_or_else(corrupted)?,
+            tag_name: tag_name.ok_or_else(corrupted)?,
+            // Some old tags have no tagger.
+            tagger,
+            message: String::from(message),
+            data,
+        })
+    }
+
+    pub fn get_object(&self) -> &ObjectId {
+        &self.object
+    }
+
+    pub fn get_object_type(&self) -> &str {
+        &self.object_type
+    }
+
+    pub fn get_tag_name(&self) -> &str {
+        &self.tag_name
+    }
+
+    pub fn get_tagger(&self) -> Option<&Author> {
+        self.tagger.as_ref()
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn get_title_line(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+}
+
+impl GitObject for Tag {
+    fn get_data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    fn get_type(&self) -> &str {
+        "tag"
+    }
+
+    fn get_oid(&self) -> &ObjectId {
         &self.oid
     }
 
-    fn set_oid(&mut self, oid: String) {
+    fn set_oid(&mut self, oid: ObjectId) {
         self.oid = oid;
     }
 }
@@ -199,19 +472,24 @@ impl GitObject for Commit {
 pub struct Entry {
     path: PathBuf,
     path_as_str: String,
-    oid: String,
+    oid: ObjectId,
     metadata: MinigitMetadata,
     flags: u16,
 }
 
 impl Entry {
-    pub fn new(path: &Path, oid: &str, metadata: MinigitMetadata) -> Entry {
+    pub fn new(path: &Path, oid: ObjectId, metadata: MinigitMetadata) -> Entry {
+        Entry::new_unmerged(path, oid, metadata, 0)
+    }
+
+    /// An entry at a given stage, which is 0 unless the file has merge conflicts.
+    pub fn new_unmerged(path: &Path, oid: ObjectId, metadata: MinigitMetadata, stage: u8) -> Entry {
         let path_as_str = String::from(path.to_str().unwrap());
-        let flags = min(path_as_str.len(), 0xfff) as u16;
+        let flags = ((stage as u16 & 0x3) << 12) | min(path_as_str.len(), 0xfff) as u16;
         Entry {
             path: PathBuf::from(path),
             path_as_str,
-            oid: String::from(oid),
+            oid,
             metadata,
             flags,
         }
@@ -225,6 +503,10 @@ impl Entry {
         &self.path_as_str
     }
 
+    pub fn get_oid(&self) -> &ObjectId {
+        &self.oid
+    }
+
     pub fn get_data(&self) -> Vec<u8> {
         let mut data: Vec<u8> = Vec::new();
         data.extend_from_slice(&u32_to_u8_array_big_endian(self.metadata.ctime));
@@ -237,7 +519,7 @@ impl Entry {
         data.extend_from_slice(&u32_to_u8_array_big_endian(self.metadata.uid));
         data.extend_from_slice(&u32_to_u8_array_big_endian(self.metadata.gid));
         data.extend_from_slice(&u32_to_u8_array_big_endian(self.metadata.size));
-        data.extend_from_slice(&oid_to_compressed_u8_array(&self.oid));
+        data.extend_from_slice(self.oid.as_bytes());
         data.extend_from_slice(&u16_to_u8_array_big_endian(self.flags));
         data.extend_from_slice(self.path_as_str.as_bytes());
         data.push(0);
@@ -250,10 +532,14 @@ impl Entry {
     pub fn get_mode(&self) -> u32 {
         self.metadata.mode
     }
+
+    pub fn get_stage(&self) -> u8 {
+        ((self.flags >> 12) & 0x3) as u8
+    }
 }
 
 impl TreeOrEntry for Entry {
-    fn get_oid(&self) -> &str {
+    fn get_oid(&self) -> &ObjectId {
         &self.oid
     }
 
@@ -274,25 +560,111 @@ impl TreeOrEntry for Entry {
     }
 }
 
+#[derive(Clone)]
 pub struct Author {
     name: String,
     email: String,
-    timestamp: DateTime<Local>,
+    timestamp: DateTime<FixedOffset>,
 }
 
 impl Author {
     pub fn new(name: &str, email: &str, timestamp: DateTime<Local>) -> Author {
+        let timestamp = timestamp.with_timezone(&timestamp.offset().fix());
         Author { name: String::from(name), email: String::from(email), timestamp }
     }
 
+    pub fn parse(input: &str) -> MinigitResult<Author> {
+        let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, format!("fatal: invalid author/committer line '{}'", input));
+        let email_start = input.rfind('<').ok_or_else(corrupted)?;
+        let email_end = input.rfind('>').filter(|index| *index > email_start).ok_or_else(corrupted)?;
+        let mut date_parts = input[email_end + 1..].split_whitespace();
+        let seconds: i64 = date_parts.next().and_then(|seconds| seconds.parse().ok()).ok_or_else(corrupted)?;
+        let offset = date_parts.next().and_then(parse_timezone_offset).unwrap_or(0);
+        let timestamp = FixedOffset::east_opt(offset)
+            .and_then(|timezone| timezone.timestamp_opt(seconds, 0).single())
+            .ok_or_else(corrupted)?;
+        Ok(Author {
+            name: String::from(input[..email_start].trim()),
+            email: String::from(&input[email_start + 1..email_end]),
+            timestamp,
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn get_timestamp(&self) -> &DateTime<FixedOffset> {
+        &self.timestamp
+    }
+
     pub fn to_string(&self) -> String {
         let seconds = &self.timestamp.timestamp();
-        let offset = &self.timestamp.offset().local_minus_utc();
-        format!("{} <{}> {} {:+03}{:02}",
+        format!("{} <{}> {} {}",
                 &self.name,
                 &self.email,
                 seconds,
-                offset / 3600,
-                offset / 60 % 60)
+                format_timezone_offset(self.timestamp.offset().local_minus_utc()))
     }
-}
\ No newline at end of file
+}
+
+/// Formats an offset in seconds east of UTC the way git does, e.g. `+0200` or `-0330`.
+pub fn format_timezone_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    format!("{}{:02}{:02}", sign, offset / 3600, offset / 60 % 60)
+}
+
+fn parse_timezone_offset(input: &str) -> Option<i32> {
+    if input.len() != 5 {
+        return None;
+    }
+    let sign = match &input[..1] {
+        "+" => 1,
+        "-" => -1,
+        _ => return None,
+    };
+    let hours: i32 = input[1..3].parse().ok()?;
+    let minutes: i32 = input[3..5].parse().ok()?;
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::database::Database;
+
+    use super::*;
+
+    fn build_tree_oid(files: &[(&str, &str)]) -> (ObjectId, Vec<u8>) {
+        let database = Database::new(PathBuf::new());
+        let entries = files.iter().map(|(path, content)| {
+            let oid = database.hash_object(&Blob::new(content.as_bytes().to_vec()));
+            Entry::new(Path::new(path), oid, MinigitMetadata::from_mode(0o100644))
+        }).collect();
+        let mut tree = Tree::build(entries);
+        tree.traverse(&mut |tree| {
+            tree.set_oid(database.hash_object(tree));
+            Ok(())
+        }).unwrap();
+        (*GitObject::get_oid(&tree), tree.get_data().clone())
+    }
+
+    #[test]
+    fn test_tree_entries_in_git_order() {
+        let files = [("foo0", "d\n"), ("foo/baz/x", "e\n"), ("foo/baz.txt", "f\n"), ("foo/bar.txt", "b\n"), ("foo.txt", "a\n"), ("foo-bar", "c\n")];
+        // Hashes given by `git write-tree` for the same files.
+        let (oid, data) = build_tree_oid(&files);
+        assert_eq!("4e2003fb6134d2d3275725bb0bcb4fec6082e63e", oid.to_hex());
+        let names: Vec<String> = Tree::parse(data).unwrap().get_entries().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(vec!("foo-bar", "foo.txt", "foo", "foo0"), names);
+
+        let (subtree_oid, _) = build_tree_oid(&[("baz/x", "e\n"), ("baz.txt", "f\n"), ("bar.txt", "b\n")]);
+        assert_eq!("bd376fcdea0f30bf189f0c44cc573159bcfd1a49", subtree_oid.to_hex());
+    }
+}
@@ -5,14 +5,16 @@ use std::path::{Path, PathBuf};
 use crypto::digest::Digest;
 use crypto::sha1::Sha1;
 
-use crate::{oid_to_compressed_u8_array, u32_to_u8_array_big_endian, u8_array_to_u16_big_endian, u8_array_to_u32_big_endian, uncompress_u8_array_to_oid};
+use crate::{u32_to_u8_array_big_endian, u8_array_to_u16_big_endian, u8_array_to_u32_big_endian};
 use crate::gitobject::Entry;
 use crate::lockfile::Lockfile;
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::{OID_RAW_LEN, ObjectId};
 use crate::workspace::MinigitMetadata;
 
 pub struct Index {
-    entries: BTreeMap<String, Entry>,
+    /// The entries by path and stage: 0 for a merged file, 1 to 3 for the base, ours and theirs versions of an unmerged one.
+    entries: BTreeMap<(String, u8), Entry>,
     parents: HashMap<String, HashSet<String>>,
     path: PathBuf,
     lockfile: Option<Lockfile>,
@@ -60,7 +62,7 @@ impl Index {
         self.clear();
         let data = match fs::read(&self.path) {
             Ok(data) => data,
-            Err(e) => return Err(MinigitError::new(format!("Error reading file {}: {}", self.path.display(), e))),
+            Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading file {}: {}", self.path.display(), e), e)),
         };
         let mut offset = 0;
 
@@ -74,9 +76,29 @@ impl Index {
         self.verify_hash(offset, &data)
     }
 
+    pub fn get_entries(&self) -> Vec<&Entry> {
+        self.entries.values().collect()
+    }
+
+    pub fn get_entry(&self, path: &Path) -> Option<&Entry> {
+        self.get_stage_entry(path, 0)
+    }
+
+    pub fn get_stage_entry(&self, path: &Path, stage: u8) -> Option<&Entry> {
+        self.entries.get(&(String::from(path.to_str().unwrap()), stage))
+    }
+
+    pub fn is_unmerged(&self, path: &Path) -> bool {
+        (1..=3).any(|stage| self.get_stage_entry(path, stage).is_some())
+    }
+
+    pub fn has_unmerged_entries(&self) -> bool {
+        self.entries.keys().any(|(_, stage)| *stage > 0)
+    }
+
     pub fn is_path_tracked(&self, path: &Path) -> bool {
         let path = format!("{}", path.display());
-        self.entries.contains_key(&path) || self.parents.contains_key(&path)
+        (0..=3).any(|stage| self.entries.contains_key(&(path.clone(), stage))) || self.parents.contains_key(&path)
     }
 
     fn clear(&mut self) {
@@ -88,13 +110,13 @@ impl Index {
         let signature = self.get_slice(&data, offset, 4)?;
         if signature != "DIRC".as_bytes() {
             match std::str::from_utf8(signature) {
-                Ok(signature) => return Err(MinigitError::new(format!("Index signature: expected 'DIRC', got {}", signature))),
-                Err(_) => return Err(MinigitError::new(format!("Index signature: expected 'DIRC', got incorrect utf8 bytes {:?}", signature))),
+                Ok(signature) => return Err(MinigitError::with_kind(ErrorKind::Corrupt, format!("Index signature: expected 'DIRC', got {}", signature))),
+                Err(_) => return Err(MinigitError::with_kind(ErrorKind::Corrupt, format!("Index signature: expected 'DIRC', got incorrect utf8 bytes {:?}", signature))),
             }
         }
         let version = u8_array_to_u32_big_endian(self.get_slice(&data, offset, 4)?);
         if version != 2 {
-            return Err(MinigitError::new(format!("Index version: expected 2, got {}", version)));
+            return Err(MinigitError::with_kind(ErrorKind::Corrupt, format!("Index version: expected 2, got {}", version)));
         }
         let count = u8_array_to_u32_big_endian(self.get_slice(&data, offset, 4)?);
         Ok(count)
@@ -102,7 +124,7 @@ impl Index {
 
     fn get_slice<'a>(&self, data: &'a Vec<u8>, offset: &mut usize, size: usize) -> MinigitResult<&'a [u8]> {
         if data.len() < *offset + size {
-            return Err(MinigitError::new(format!("{}", FATAL_INDEX_TOO_SHORT_MESSAGE)));
+            return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_TOO_SHORT_MESSAGE)));
         }
         let old_offset = *offset;
         *offset = *offset + size;
@@ -122,35 +144,34 @@ impl Index {
             gid: u8_array_to_u32_big_endian(self.get_slice(&data, offset, 4)?),
             size: u8_array_to_u32_big_endian(self.get_slice(&data, offset, 4)?),
         };
-        let oid = uncompress_u8_array_to_oid(self.get_slice(&data, offset, 20)?);
-        // Unused atm
-        let _flags = u8_array_to_u16_big_endian(self.get_slice(&data, offset, 2)?);
+        let oid = ObjectId::from_bytes(self.get_slice(&data, offset, OID_RAW_LEN)?)?;
+        let flags = u8_array_to_u16_big_endian(self.get_slice(&data, offset, 2)?);
         let (path_size, padding_size) = self.get_entry_size(&data, *offset, 2, 8)?;
         let path_bytes = self.get_slice(&data, offset, path_size)?;
         *offset += padding_size;
         let path_as_str = match std::str::from_utf8(path_bytes) {
             Ok(path) => path,
-            Err(_) => return Err(MinigitError::new(format!("{}", FATAL_INDEX_CORRUPTED_MESSAGE))),
+            Err(_) => return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_CORRUPTED_MESSAGE))),
         };
-        Ok(Entry::new(Path::new(path_as_str), &oid, metadata))
+        Ok(Entry::new_unmerged(Path::new(path_as_str), oid, metadata, ((flags >> 12) & 0x3) as u8))
     }
 
     fn get_entry_size(&self, data: &Vec<u8>, offset: usize, entry_min_size: usize, entry_block_size: usize) -> MinigitResult<(usize, usize)> {
         let mut entry_size = entry_min_size;
         if data.len() < offset + entry_size {
-            return Err(MinigitError::new(format!("{}", FATAL_INDEX_TOO_SHORT_MESSAGE)));
+            return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_TOO_SHORT_MESSAGE)));
         }
         while data[offset + entry_size - 1] != 0 {
             entry_size += entry_block_size;
             if data.len() < offset + entry_size {
-                return Err(MinigitError::new(format!("{}", FATAL_INDEX_TOO_SHORT_MESSAGE)));
+                return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_TOO_SHORT_MESSAGE)));
             }
         }
         let mut path_size = entry_size;
         while data[offset + path_size - 1] == 0 {
             path_size -= 1;
             if path_size == 0 {
-                return Err(MinigitError::new(format!("{}", FATAL_INDEX_CORRUPTED_MESSAGE)));
+                return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_CORRUPTED_MESSAGE)));
             }
         }
         let padding_size = entry_size - path_size;
@@ -159,25 +180,62 @@ impl Index {
 
     fn verify_hash(&self, offset: usize, data: &Vec<u8>) -> MinigitResult<()> {
         if data.len() < offset + 20 {
-            return Err(MinigitError::new(format!("{}", FATAL_INDEX_TOO_SHORT_MESSAGE)));
+            return Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_TOO_SHORT_MESSAGE)));
         }
         let mut hasher = Sha1::new();
         hasher.input(&data[..data.len() - 20]);
-        let expected_hash = oid_to_compressed_u8_array(&hasher.result_str());
+        let expected_hash = ObjectId::from_sha1(&mut hasher);
         let actual_hash = &data[data.len() - 20..];
-        match expected_hash == actual_hash {
+        match expected_hash.as_bytes() == actual_hash {
             true => Ok(()),
-            false => Err(MinigitError::new(format!("{}", FATAL_INDEX_CORRUPTED_MESSAGE)))
+            false => Err(MinigitError::with_kind(ErrorKind::Corrupt, String::from(FATAL_INDEX_CORRUPTED_MESSAGE)))
         }
     }
 
-    pub fn add(&mut self, path: &Path, oid: &str, metadata: MinigitMetadata) {
+    pub fn add(&mut self, path: &Path, oid: ObjectId, metadata: MinigitMetadata) {
         let entry = Entry::new(path, oid, metadata);
         self.discard_conflicts(&entry);
         self.insert_entry(entry);
         self.changed = true;
     }
 
+    /// Removes the entry of a file, or every entry inside a directory.
+    pub fn remove(&mut self, path: &Path) {
+        let path_as_str = path.to_str().unwrap();
+        if let Some(children_paths) = self.parents.get(path_as_str) {
+            for children_path in children_paths.clone().iter() {
+                self.remove_entry(children_path);
+            }
+        }
+        self.remove_entry(path_as_str);
+    }
+
+    /// Removes every stage of a path.
+    fn remove_entry(&mut self, path_as_str: &str) {
+        let removed: Vec<Entry> = (0..=3).filter_map(|stage| self.entries.remove(&(String::from(path_as_str), stage))).collect();
+        if removed.is_empty() {
+            return;
+        }
+        let mut ancestors = Path::new(path_as_str).ancestors();
+        ancestors.next();
+        for ancestor in ancestors {
+            let ancestor_as_str = ancestor.to_str().unwrap();
+            if let Some(children_paths) = self.parents.get_mut(ancestor_as_str) {
+                children_paths.remove(path_as_str);
+                if children_paths.is_empty() {
+                    self.parents.remove(ancestor_as_str);
+                }
+            }
+        }
+        self.changed = true;
+    }
+
+    pub fn remove_all(&mut self) {
+        self.entries = BTreeMap::new();
+        self.parents = HashMap::new();
+        self.changed = true;
+    }
+
     fn insert_entry(&mut self, entry: Entry) {
         let path_as_str = String::from(entry.get_path_as_str());
         let mut ancestors = entry.get_path().ancestors();
@@ -189,7 +247,15 @@ impl Index {
             }
             self.parents.get_mut(ancestor_as_str).unwrap().insert(path_as_str.clone());
         }
-        self.entries.insert(path_as_str, entry);
+        self.entries.insert((path_as_str, entry.get_stage()), entry);
+    }
+
+    /// Records one version of an unmerged file, as a merge would.
+    #[cfg(test)]
+    pub fn add_unmerged(&mut self, path: &Path, stage: u8, oid: ObjectId, metadata: MinigitMetadata) {
+        self.entries.remove(&(String::from(path.to_str().unwrap()), 0));
+        self.insert_entry(Entry::new_unmerged(path, oid, metadata, stage));
+        self.changed = true;
     }
 
     fn discard_conflicts(&mut self, entry: &Entry) {
@@ -198,14 +264,9 @@ impl Index {
             if ancestor_as_str == "" {
                 break;
             }
-            self.entries.remove(ancestor_as_str);
-        }
-        if let Some(children_paths) = self.parents.get_mut(entry.get_path_as_str()) {
-            for children_path in children_paths.iter() {
-                self.entries.remove(children_path);
-            }
-            children_paths.clear();
+            self.remove_entry(ancestor_as_str);
         }
+        self.remove(entry.get_path());
     }
 
     pub fn write_updates(&mut self) -> MinigitResult<bool> {
@@ -223,8 +284,8 @@ impl Index {
             self.write(&mut lockfile, &mut hasher, &entry.get_data())?;
         }
 
-        let index_oid = hasher.result_str();
-        lockfile.write(&oid_to_compressed_u8_array(&index_oid))?;
+        let index_oid = ObjectId::from_sha1(&mut hasher);
+        lockfile.write(index_oid.as_bytes())?;
         lockfile.commit()?;
         self.changed = true;
 
@@ -263,11 +324,8 @@ mod tests {
         let workspace = Workspace::new(Path::new("."));
         for path in paths {
             let metadata = workspace.get_metadata(Path::new("Cargo.lock")).unwrap();
-            let oid: String = iter::repeat(())
-                .map(|_| rng.sample(Alphanumeric))
-                .take(20)
-                .collect();
-            index.add(Path::new(path), &oid, metadata);
+            let oid = ObjectId::from_raw(rng.gen());
+            index.add(Path::new(path), oid, metadata);
         }
         index
     }
@@ -307,6 +365,38 @@ mod tests {
         assert_eq!(vec!("alice.txt", "nested"), actual_paths);
     }
 
+    #[test]
+    fn test_remove_entry() {
+        let mut index = prepare_test_context(&["alice.txt", "nested/bob.txt", "nested/inner/claire.txt"]);
+        index.remove(Path::new("nested/inner/claire.txt"));
+        index.remove(Path::new("missing.txt"));
+        assert!(!index.is_path_tracked(Path::new("nested/inner")));
+        assert!(index.is_path_tracked(Path::new("nested")));
+        let actual_paths: Vec<&str> = index.get_entries().into_iter().map(|entry| entry.get_path_as_str()).collect();
+        assert_eq!(vec!("alice.txt", "nested/bob.txt"), actual_paths);
+    }
+
+    #[test]
+    fn test_remove_directory() {
+        let mut index = prepare_test_context(&["alice.txt", "nested/bob.txt", "nested/inner/claire.txt"]);
+        index.remove(Path::new("nested"));
+        assert!(!index.is_path_tracked(Path::new("nested")));
+        assert!(!index.is_path_tracked(Path::new("nested/inner")));
+        let actual_paths: Vec<&str> = index.get_entries().into_iter().map(|entry| entry.get_path_as_str()).collect();
+        assert_eq!(vec!("alice.txt"), actual_paths);
+    }
+
+    #[test]
+    fn test_replaced_directory_is_no_longer_tracked() {
+        let index = prepare_test_context(&["nested/bob.txt", "nested/inner/claire.txt", "nested/inner"]);
+        assert!(index.is_path_tracked(Path::new("nested/inner")));
+        assert!(index.get_entry(Path::new("nested/inner/claire.txt")).is_none());
+        assert!(index.get_entry(Path::new("nested/inner")).is_some());
+        let mut index = prepare_test_context(&["nested/bob.txt", "nested"]);
+        index.remove(Path::new("nested"));
+        assert!(!index.is_path_tracked(Path::new("nested")));
+    }
+
     #[test]
     fn test_add_replace_complex_directory_by_file() {
         let index = prepare_test_context(&["alice.txt", "nested/bob.txt", "nested/inner/claire.txt", "nested"]);
@@ -0,0 +1,250 @@
+//! A reimplementation of git which reads and writes repositories in the same format.
+//!
+//! A [`Repository`](repository::Repository) gives access to the parts of a repository: its object
+//! [`Database`](database::Database), its [`Index`](index::Index), its [`Refs`](refs::Refs) and its
+//! [`Workspace`](workspace::Workspace). The commands of the `minigit` binary are run with [`command::execute`], which
+//! takes the arguments, environment and standard streams in a [`Runtime`](command::Runtime):
+//!
+//! ```no_run
+//! use minigit::command::{execute, Runtime};
+//!
+//! let mut runtime = Runtime::default();
+//! runtime.args = vec!(String::from("minigit"), String::from("status"));
+//! execute(&mut runtime).unwrap();
+//! ```
+//!
+//! Every fallible operation returns a [`MinigitResult`](minigiterror::MinigitResult), whose error message is the one
+//! git would print.
+
+extern crate backtrace;
+extern crate chrono;
+extern crate crypto;
+extern crate flate2;
+extern crate rand;
+
+/// Paths and metadata of the files of the working tree.
+pub mod workspace;
+/// Reading and writing of `.git/config` style files.
+pub mod config;
+/// The object store under `.git/objects`.
+pub mod database;
+/// Blobs, trees, commits and tags, and the entries of the index.
+pub mod gitobject;
+/// Reading, updating and listing refs, including transactions of several updates.
+pub mod refs;
+/// Entries of the reflogs under `.git/logs`.
+pub mod reflog;
+mod lockfile;
+/// The staging area stored in `.git/index`.
+pub mod index;
+/// Lazily opened parts of a repository.
+pub mod repository;
+/// Parsing and resolution of revision expressions like `HEAD~2^{tree}`.
+pub mod revision;
+mod revlist;
+mod mergebase;
+mod approxidate;
+/// The error returned by every fallible operation.
+pub mod minigiterror;
+/// Object ids and their hexadecimal form.
+pub mod objectid;
+/// The `.git/packed-refs` file.
+pub mod packedrefs;
+mod pathspec;
+mod attributes;
+mod diff;
+/// The commands of the `minigit` binary and the runtime they use.
+pub mod command;
+
+fn u32_to_u8_array_big_endian(number: u32) -> [u8; 4] {
+    [
+        (number >> 24 & 0xff) as u8,
+        (number >> 16 & 0xff) as u8,
+        (number >> 8 & 0xff) as u8,
+        (number & 0xff) as u8]
+}
+
+fn u8_array_to_u32_big_endian(input: &[u8]) -> u32 {
+    let mut result = 0;
+    result += (input[0] as u32) << 24;
+    result += (input[1] as u32) << 16;
+    result += (input[2] as u32) << 8;
+    result += input[3] as u32;
+    result
+}
+
+fn u16_to_u8_array_big_endian(number: u16) -> [u8; 2] {
+    [
+        (number >> 8 & 0xff) as u8,
+        (number & 0xff) as u8]
+}
+
+fn u8_array_to_u16_big_endian(input: &[u8]) -> u16 {
+    let mut result = 0;
+    result += (input[0] as u16) << 8;
+    result += input[1] as u16;
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, panic};
+    use std::io::Cursor;
+    use std::iter;
+    use std::path::{Path, PathBuf};
+
+    use chrono::{Local, TimeZone};
+    use rand::distributions::Alphanumeric;
+    use rand::Rng;
+
+    use crate::command::{execute, Runtime};
+    use crate::gitobject::{Author, Blob, Commit, Entry, GitObject, Tag, Tree};
+    use crate::minigiterror::MinigitResult;
+    use crate::objectid::ObjectId;
+    use crate::repository::Repository;
+    use crate::workspace::MinigitMetadata;
+
+    fn before_test() -> String {
+        let mut rng = rand::thread_rng();
+        let repo_name: String = iter::repeat(())
+            .map(|_| rng.sample(Alphanumeric))
+            .take(20)
+            .collect();
+        let repo_path = format!("{}", std::env::temp_dir().join("minigit_test").join(repo_name).display());
+        fs::create_dir_all(&repo_path).unwrap();
+        let mut runtime = Runtime::default();
+        runtime.dir = PathBuf::from(&repo_path);
+        runtime.args = vec!(String::from("minigit"), String::from("init"), String::from("-q"), repo_path.clone());
+        execute(&mut runtime).unwrap();
+        repo_path
+    }
+
+    fn after_test(repo_path: String) {
+        fs::remove_dir_all(repo_path).unwrap();
+    }
+
+    pub fn run_test<T>(test: T) -> () where T: FnOnce(&str) -> () + panic::UnwindSafe {
+        let repo_path = before_test();
+        let result = panic::catch_unwind(|| {
+            test(&repo_path)
+        });
+        after_test(repo_path);
+        assert!(result.is_ok())
+    }
+
+    pub fn execute_and_get_result(repo_path: &str, args: Vec<String>) -> MinigitResult<()> {
+        let mut runtime = Runtime::default();
+        runtime.dir = PathBuf::from(repo_path);
+        runtime.args = args;
+        execute(&mut runtime)
+    }
+
+    pub fn execute_and_expect_success(repo_path: &str, args: Vec<String>) {
+        if let Err(e) = execute_and_get_result(repo_path, args) {
+            panic!("Command terminated with an error, when success was expected: {}", e.message)
+        }
+    }
+
+    pub fn execute_and_expect_success_message(repo_path: &str, args: Vec<String>, expected_stdout: String) {
+        let mut stdout = String::new();
+        let stdout_cursor = unsafe {
+            Cursor::new(stdout.as_mut_vec())
+        };
+        {
+            let mut runtime = Runtime::default();
+            runtime.dir = PathBuf::from(repo_path);
+            runtime.args = args;
+            runtime.stdout = Box::new(stdout_cursor);
+            if let Err(e) = execute(&mut runtime) {
+                panic!("Command terminated with an error, when success was expected: {}", e.message)
+            }
+        }
+        assert_eq!(expected_stdout, stdout);
+    }
+
+    pub fn execute_and_get_stdout(repo_path: &str, args: Vec<String>) -> String {
+        let mut stdout: Vec<u8> = Vec::new();
+        {
+            let mut runtime = Runtime::default();
+            runtime.dir = PathBuf::from(repo_path);
+            runtime.args = args;
+            runtime.stdout = Box::new(&mut stdout);
+            if let Err(e) = execute(&mut runtime) {
+                panic!("Command terminated with an error, when success was expected: {}", e.message)
+            }
+        }
+        String::from_utf8(stdout).unwrap()
+    }
+
+    pub fn commit(repo_path: &str, message: &str) -> ObjectId {
+        let mut runtime = Runtime::default();
+        runtime.dir = PathBuf::from(repo_path);
+        runtime.args = vec!(String::new(), String::from("commit"));
+        runtime.env.insert(String::from("GIT_AUTHOR_NAME"), String::from("A U Thor"));
+        runtime.env.insert(String::from("GIT_AUTHOR_EMAIL"), String::from("author@example.com"));
+        runtime.stdin = Box::new(Cursor::new(String::from(message).into_bytes()));
+        runtime.stdout = Box::new(std::io::sink());
+        if let Err(e) = execute(&mut runtime) {
+            panic!("Commit terminated with an error, when success was expected: {}", e.message)
+        }
+        Repository::new(PathBuf::from(repo_path)).refs().read_head().unwrap().unwrap()
+    }
+
+    /// Stores a commit with the given files directly in the database, without touching the index or HEAD.
+    pub fn write_commit(repo_path: &str, parents: Vec<ObjectId>, message: &str, timestamp: i64, files: &[(&str, &str)]) -> ObjectId {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let mut files = files.to_vec();
+        files.sort();
+        let mut entries = Vec::new();
+        for (path, content) in files {
+            let mut blob = Blob::new(content.as_bytes().to_vec());
+            repository.database().store(&mut blob).unwrap();
+            let metadata = MinigitMetadata { ctime: 0, ctime_nsec: 0, mtime: 0, mtime_nsec: 0, dev: 0, ino: 0, mode: 0o100644, uid: 0, gid: 0, size: 0 };
+            entries.push(Entry::new(Path::new(path), *blob.get_oid(), metadata));
+        }
+        let mut tree = Tree::build(entries);
+        tree.traverse(&mut |tree| repository.database().store(tree)).unwrap();
+        let author = Author::new("A U Thor", "author@example.com", Local.timestamp(timestamp, 0));
+        let mut commit = Commit::new(parents, author, message, tree.get_oid());
+        repository.database().store(&mut commit).unwrap();
+        *commit.get_oid()
+    }
+
+    /// Stores an annotated tag of a commit directly in the database and points `refs/tags/<name>` to it.
+    pub fn write_tag(repo_path: &str, name: &str, target: ObjectId, message: &str, timestamp: i64) -> ObjectId {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let tagger = Author::new("C O Mitter", "committer@example.com", Local.timestamp(timestamp, 0));
+        let mut tag = Tag::new(&target, "commit", name, tagger, message);
+        repository.database().store(&mut tag).unwrap();
+        let path = PathBuf::from(repo_path).join(".git/refs/tags").join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, format!("{}\n", tag.get_oid())).unwrap();
+        *tag.get_oid()
+    }
+
+    pub fn execute_and_expect_error(repo_path: &str, args: Vec<String>) {
+        if let Ok(()) = execute_and_get_result(repo_path, args) {
+            panic!("Command executed succesfully, but en error was expected");
+        }
+    }
+
+    pub fn execute_and_expect_error_message(repo_path: &str, args: Vec<String>, expected_error_message: String) {
+        let mut runtime = Runtime::default();
+        runtime.dir = PathBuf::from(repo_path);
+        runtime.args = args;
+        match execute(&mut runtime) {
+            Ok(()) => panic!("Command executed succesfully, but en error was expected"),
+            Err(err) => assert_eq!(expected_error_message, err.message),
+        }
+    }
+
+    pub fn assert_index(repo_path: &str, expected_entries: Vec<(u32, String)>) {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let index = repository.index_take().unwrap();
+        let actual_entries: Vec<(u32, String)> = index.load_and_get_entries().unwrap()
+            .into_iter().map(|value| (value.get_mode(), String::from(value.get_path_as_str())))
+            .collect();
+        assert_eq!(expected_entries, actual_entries);
+    }
+}
\ No newline at end of file
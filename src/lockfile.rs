@@ -1,8 +1,8 @@
 use std::fs::{File, OpenOptions, rename};
-use std::io::Write;
+use std::io::{self, Write};
 use std::path::PathBuf;
 
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
 
 pub struct Lockfile {
     target_file_path: PathBuf,
@@ -14,17 +14,26 @@ pub struct Lockfile {
 impl Lockfile {
     pub fn new(path: PathBuf) -> MinigitResult<Lockfile> {
         let target_file_path = path;
-        let lock_file_path = target_file_path.with_extension("lock");
+        // `with_extension` would replace a dot-separated suffix like the one of `v1.0`.
+        let mut lock_file_name = target_file_path.file_name().unwrap().to_os_string();
+        lock_file_name.push(".lock");
+        let lock_file_path = target_file_path.with_file_name(lock_file_name);
         match OpenOptions::new().write(true).create_new(true).open(&lock_file_path) {
             Ok(lock_file) => Ok(Lockfile { target_file_path, lock_file, lock_file_path, commit_has_been_called: false }),
-            Err(e) => Err(MinigitError::new(String::from(format!("Unable to create '{}': {}", lock_file_path.display(), e)))),
+            Err(e) => {
+                let kind = match e.kind() {
+                    io::ErrorKind::AlreadyExists => ErrorKind::LockHeld,
+                    _ => ErrorKind::Io,
+                };
+                Err(MinigitError::with_source(kind, format!("Unable to create '{}': {}", lock_file_path.display(), e), e))
+            }
         }
     }
 
     pub fn write(&mut self, value: &[u8]) -> MinigitResult<()> {
-        match &self.lock_file.write_all(value) {
+        match self.lock_file.write_all(value) {
             Ok(_) => Ok(()),
-            Err(e) => Err(MinigitError::new(String::from(format!("Error writing to {}: {}", &self.lock_file_path.display(), e)))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error writing to {}: {}", &self.lock_file_path.display(), e), e)),
         }
     }
 
@@ -36,7 +45,8 @@ impl Lockfile {
         self.commit_has_been_called = true;
         match rename(&self.lock_file_path, &self.target_file_path) {
             Ok(_) => Ok(()),
-            Err(e) => Err(MinigitError::new(String::from(format!("Error renaming {} to {}: {}", &self.lock_file_path.display(), &self.target_file_path.display(), e)))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error renaming {} to {}: {}", &self.lock_file_path.display(),
+                                                                          &self.target_file_path.display(), e), e)),
         }
     }
 }
@@ -0,0 +1,246 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::gitobject::Commit;
+use crate::minigiterror::MinigitResult;
+use crate::objectid::ObjectId;
+use crate::repository::Repository;
+
+const PARENT1: u8 = 1;
+const PARENT2: u8 = 1 << 1;
+const STALE: u8 = 1 << 2;
+const RESULT: u8 = 1 << 3;
+
+/// Computes common ancestors of commits.
+///
+/// Commits are visited by decreasing generation number (the length of the longest path to a root commit)
+/// rather than by date alone, so that clock skew between commits cannot produce wrong results.
+pub struct MergeBase<'a> {
+    repository: &'a mut Repository,
+    commits: HashMap<ObjectId, Commit>,
+    generations: HashMap<ObjectId, u64>,
+}
+
+impl<'a> MergeBase<'a> {
+    pub fn new(repository: &'a mut Repository) -> MergeBase<'a> {
+        MergeBase { repository, commits: HashMap::new(), generations: HashMap::new() }
+    }
+
+    /// Returns all best common ancestors of `one` and any of `others`, like `git merge-base --all`.
+    pub fn find(&mut self, one: ObjectId, others: &[ObjectId]) -> MinigitResult<Vec<ObjectId>> {
+        if others.contains(&one) {
+            return Ok(vec!(one));
+        }
+        let candidates = self.paint_down_to_common(one, others)?;
+        self.remove_redundant(candidates)
+    }
+
+    /// Returns the common ancestors usable for an n-way merge of all the given commits.
+    pub fn find_octopus(&mut self, oids: &[ObjectId]) -> MinigitResult<Vec<ObjectId>> {
+        let mut result: Vec<ObjectId> = oids.iter().take(1).cloned().collect();
+        for oid in oids.iter().skip(1) {
+            let mut bases = Vec::new();
+            for base in result {
+                for new_base in self.find(base, &[*oid])? {
+                    if !bases.contains(&new_base) {
+                        bases.push(new_base);
+                    }
+                }
+            }
+            result = bases;
+        }
+        Ok(result)
+    }
+
+    /// Filters out the commits which can be reached from another one of the list.
+    pub fn independent(&mut self, oids: &[ObjectId]) -> MinigitResult<Vec<ObjectId>> {
+        let mut unique = Vec::new();
+        for oid in oids {
+            if !unique.contains(oid) {
+                unique.push(*oid);
+            }
+        }
+        self.remove_redundant(unique)
+    }
+
+    pub fn is_ancestor(&mut self, ancestor: ObjectId, descendant: ObjectId) -> MinigitResult<bool> {
+        let min_generation = self.generation(ancestor)?;
+        let mut visited = HashSet::new();
+        let mut stack = vec!(descendant);
+        while let Some(oid) = stack.pop() {
+            if oid == ancestor {
+                return Ok(true);
+            }
+            if !visited.insert(oid) || self.generation(oid)? <= min_generation {
+                continue;
+            }
+            stack.extend(self.load_commit(&oid)?.get_parents().iter().cloned());
+        }
+        Ok(false)
+    }
+
+    fn paint_down_to_common(&mut self, one: ObjectId, others: &[ObjectId]) -> MinigitResult<Vec<ObjectId>> {
+        let mut flags: HashMap<ObjectId, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        let mut queued_count = 0;
+        let mut result = Vec::new();
+
+        flags.insert(one, PARENT1);
+        queue.push(self.queue_key(one, &mut queued_count)?);
+        for other in others {
+            *flags.entry(*other).or_insert(0) |= PARENT2;
+            queue.push(self.queue_key(*other, &mut queued_count)?);
+        }
+
+        while queue.iter().any(|(_, _, _, oid)| flags[oid] & STALE == 0) {
+            let (_, _, _, oid) = queue.pop().unwrap();
+            let mut commit_flags = flags[&oid] & (PARENT1 | PARENT2 | STALE);
+            if commit_flags == PARENT1 | PARENT2 {
+                if flags[&oid] & RESULT == 0 {
+                    *flags.get_mut(&oid).unwrap() |= RESULT;
+                    result.push(oid);
+                }
+                commit_flags |= STALE;
+            }
+            for parent in self.load_commit(&oid)?.get_parents().clone() {
+                let parent_flags = flags.entry(parent).or_insert(0);
+                if *parent_flags & commit_flags == commit_flags {
+                    continue;
+                }
+                *parent_flags |= commit_flags;
+                queue.push(self.queue_key(parent, &mut queued_count)?);
+            }
+        }
+        Ok(result.into_iter().filter(|oid| flags[oid] & STALE == 0).collect())
+    }
+
+    fn remove_redundant(&mut self, candidates: Vec<ObjectId>) -> MinigitResult<Vec<ObjectId>> {
+        let mut result = Vec::new();
+        for (index, candidate) in candidates.iter().enumerate() {
+            let mut redundant = false;
+            for (other_index, other) in candidates.iter().enumerate() {
+                if index != other_index && self.is_ancestor(*candidate, *other)? {
+                    redundant = true;
+                    break;
+                }
+            }
+            if !redundant {
+                result.push(*candidate);
+            }
+        }
+        Ok(result)
+    }
+
+    fn queue_key(&mut self, oid: ObjectId, queued_count: &mut usize) -> MinigitResult<(u64, i64, Reverse<usize>, ObjectId)> {
+        let generation = self.generation(oid)?;
+        let timestamp = self.load_commit(&oid)?.get_committer().get_timestamp().timestamp();
+        *queued_count += 1;
+        Ok((generation, timestamp, Reverse(*queued_count), oid))
+    }
+
+    fn generation(&mut self, oid: ObjectId) -> MinigitResult<u64> {
+        let mut stack = vec!(oid);
+        while let Some(top) = stack.last().cloned() {
+            if self.generations.contains_key(&top) {
+                stack.pop();
+                continue;
+            }
+            let parents = self.load_commit(&top)?.get_parents().clone();
+            let missing: Vec<ObjectId> = parents.iter().filter(|parent| !self.generations.contains_key(parent)).cloned().collect();
+            if missing.is_empty() {
+                let generation = 1 + parents.iter().map(|parent| self.generations[parent]).max().unwrap_or(0);
+                self.generations.insert(top, generation);
+                stack.pop();
+            } else {
+                stack.extend(missing);
+            }
+        }
+        Ok(self.generations[&oid])
+    }
+
+    fn load_commit(&mut self, oid: &ObjectId) -> MinigitResult<&Commit> {
+        if !self.commits.contains_key(oid) {
+            let commit = self.repository.database().load_commit(oid)?;
+            self.commits.insert(*oid, commit);
+        }
+        Ok(&self.commits[oid])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::tests::write_commit;
+
+    use super::*;
+
+    //   a - b - c - d ----- g
+    //        \   \         /
+    //         \   e ----- f
+    //          \         /
+    //           h ----- i
+    fn create_history(repo_path: &str) -> HashMap<&'static str, ObjectId> {
+        let mut commits = HashMap::new();
+        let a = write_commit(repo_path, vec!(), "a", 1000, &[]);
+        let b = write_commit(repo_path, vec!(a), "b", 2000, &[]);
+        let c = write_commit(repo_path, vec!(b), "c", 3000, &[]);
+        let d = write_commit(repo_path, vec!(c), "d", 4000, &[]);
+        // Clock skew: e is older than its parent.
+        let e = write_commit(repo_path, vec!(c), "e", 500, &[]);
+        let h = write_commit(repo_path, vec!(b), "h", 2500, &[]);
+        let i = write_commit(repo_path, vec!(h), "i", 2600, &[]);
+        let f = write_commit(repo_path, vec!(e, i), "f", 6000, &[]);
+        let g = write_commit(repo_path, vec!(d, f), "g", 7000, &[]);
+        for (name, oid) in [("a", a), ("b", b), ("c", c), ("d", d), ("e", e), ("f", f), ("g", g), ("h", h), ("i", i)].iter() {
+            commits.insert(*name, *oid);
+        }
+        commits
+    }
+
+    #[test]
+    fn test_find_merge_base() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut merge_base = MergeBase::new(&mut repository);
+            assert_eq!(vec!(commits["c"]), merge_base.find(commits["d"], &[commits["e"]]).unwrap());
+            assert_eq!(vec!(commits["c"]), merge_base.find(commits["d"], &[commits["f"]]).unwrap());
+            assert_eq!(vec!(commits["b"]), merge_base.find(commits["d"], &[commits["i"]]).unwrap());
+            assert_eq!(vec!(commits["d"]), merge_base.find(commits["d"], &[commits["g"]]).unwrap());
+            assert_eq!(vec!(commits["c"]), merge_base.find(commits["d"], &[commits["e"], commits["i"]]).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_find_multiple_best_common_ancestors() {
+        crate::tests::run_test(|repo_path| {
+            let a = write_commit(repo_path, vec!(), "a", 1000, &[]);
+            let b = write_commit(repo_path, vec!(a), "b", 2000, &[]);
+            let c = write_commit(repo_path, vec!(a), "c", 2000, &[]);
+            let x = write_commit(repo_path, vec!(b, c), "x", 3000, &[]);
+            let y = write_commit(repo_path, vec!(c, b), "y", 3000, &[]);
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut merge_base = MergeBase::new(&mut repository);
+            let mut bases = merge_base.find(x, &[y]).unwrap();
+            bases.sort();
+            let mut expected = vec!(b, c);
+            expected.sort();
+            assert_eq!(expected, bases);
+        });
+    }
+
+    #[test]
+    fn test_octopus_independent_and_is_ancestor() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let mut merge_base = MergeBase::new(&mut repository);
+            assert_eq!(vec!(commits["b"]), merge_base.find_octopus(&[commits["d"], commits["e"], commits["i"]]).unwrap());
+            assert_eq!(vec!(commits["d"], commits["i"]), merge_base.independent(&[commits["d"], commits["c"], commits["i"], commits["d"]]).unwrap());
+            assert!(merge_base.is_ancestor(commits["e"], commits["g"]).unwrap());
+            assert!(merge_base.is_ancestor(commits["a"], commits["a"]).unwrap());
+            assert!(!merge_base.is_ancestor(commits["d"], commits["f"]).unwrap());
+        });
+    }
+}
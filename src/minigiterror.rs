@@ -1,25 +1,122 @@
+use std::error::Error;
 use std::fmt;
 
 use backtrace::Backtrace;
 
 pub type MinigitResult<T> = Result<T, MinigitError>;
 
+/// What went wrong, which decides the exit status of the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The command could not do what was asked, like the `error:` messages of git.
+    Failed,
+    /// The command line is invalid, like an unknown option or a missing argument.
+    Usage,
+    /// The directory is not inside a repository.
+    NotARepository,
+    /// A lock file already exists, so another process may be updating the same file.
+    LockHeld,
+    /// The index, an object or another file of the repository cannot be parsed.
+    Corrupt,
+    /// Reading or writing a file failed.
+    Io,
+    /// Any other error stopping the command, like the `fatal:` messages of git.
+    Fatal,
+}
+
+impl ErrorKind {
+    /// The exit status git uses: 129 for usage errors, 1 when a command fails, and 128 when it dies.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 129,
+            ErrorKind::Failed => 1,
+            _ => 128,
+        }
+    }
+}
+
 pub struct MinigitError {
+    pub kind: ErrorKind,
     pub message: String,
     pub backtrace: Backtrace,
+    source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl MinigitError {
+    /// Creates an error of a command which could not do what was asked, like the `error:` messages of git.
     pub fn new(message: String) -> MinigitError {
+        MinigitError::with_kind(ErrorKind::Failed, message)
+    }
+
+    /// Creates an error stopping the command, like the `fatal:` messages of git.
+    pub fn fatal(message: String) -> MinigitError {
+        MinigitError::with_kind(ErrorKind::Fatal, message)
+    }
+
+    /// Creates an error for an invalid command line, whose message usually ends with the usage of the command.
+    pub fn usage(message: String) -> MinigitError {
+        MinigitError::with_kind(ErrorKind::Usage, message)
+    }
+
+    pub fn with_kind(kind: ErrorKind, message: String) -> MinigitError {
         MinigitError {
+            kind,
             message,
             backtrace: Backtrace::new(),
+            source: None,
         }
     }
+
+    /// Creates an error caused by another one, like an I/O or decompression error, returned by `source()`.
+    pub fn with_source<E: Error + Send + Sync + 'static>(kind: ErrorKind, message: String, source: E) -> MinigitError {
+        MinigitError { source: Some(Box::new(source)), ..MinigitError::with_kind(kind, message) }
+    }
 }
 
 impl fmt::Debug for MinigitError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.message, f)
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for MinigitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for MinigitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| source.as_ref() as &(dyn Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    #[test]
+    fn test_kinds_and_exit_codes() {
+        let usage = MinigitError::usage(String::from("error: unknown option `x'\nusage: minigit rm [<options>] [--] <file>..."));
+        assert_eq!(ErrorKind::Usage, usage.kind);
+        assert_eq!(129, usage.kind.exit_code());
+        let fatal = MinigitError::fatal(String::from("fatal: bad revision 'x'"));
+        assert_eq!(ErrorKind::Fatal, fatal.kind);
+        assert_eq!(128, fatal.kind.exit_code());
+        // The kind never depends on the message.
+        assert_eq!(ErrorKind::Failed, MinigitError::new(String::from("fatal: usage: x")).kind);
+        assert_eq!(1, MinigitError::new(String::from("error: pathspec 'x' did not match any file(s)")).kind.exit_code());
+        assert_eq!(128, MinigitError::with_kind(ErrorKind::LockHeld, String::new()).kind.exit_code());
+    }
+
+    #[test]
+    fn test_source() {
+        let error = MinigitError::with_source(ErrorKind::Io, String::from("fatal: unable to read a"),
+                                              io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert_eq!("fatal: unable to read a", error.to_string());
+        assert_eq!("missing", error.source().unwrap().to_string());
+        assert!(MinigitError::new(String::from("error")).source().is_none());
+    }
+}
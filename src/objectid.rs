@@ -0,0 +1,149 @@
+use std::fmt;
+
+use crypto::digest::Digest;
+use crypto::sha1::Sha1;
+
+use crate::minigiterror::{MinigitError, MinigitResult};
+
+pub const OID_RAW_LEN: usize = 20;
+pub const OID_HEX_LEN: usize = 40;
+pub const OID_MIN_ABBREV_LEN: usize = 4;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectId {
+    bytes: [u8; OID_RAW_LEN],
+}
+
+impl ObjectId {
+    pub fn null() -> ObjectId {
+        ObjectId { bytes: [0; OID_RAW_LEN] }
+    }
+
+    pub fn from_raw(bytes: [u8; OID_RAW_LEN]) -> ObjectId {
+        ObjectId { bytes }
+    }
+
+    pub fn from_bytes(input: &[u8]) -> MinigitResult<ObjectId> {
+        if input.len() != OID_RAW_LEN {
+            return Err(MinigitError::fatal(format!("fatal: invalid object id: expected {} bytes, got {}", OID_RAW_LEN, input.len())));
+        }
+        let mut bytes = [0; OID_RAW_LEN];
+        bytes.copy_from_slice(input);
+        Ok(ObjectId { bytes })
+    }
+
+    pub fn from_hex(hex: &str) -> MinigitResult<ObjectId> {
+        if hex.len() != OID_HEX_LEN || !ObjectId::is_hex(hex) {
+            return Err(MinigitError::fatal(format!("fatal: invalid object id '{}'", hex)));
+        }
+        let mut bytes = [0; OID_RAW_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        Ok(ObjectId { bytes })
+    }
+
+    pub fn from_sha1(hasher: &mut Sha1) -> ObjectId {
+        let mut bytes = [0; OID_RAW_LEN];
+        hasher.result(&mut bytes);
+        ObjectId { bytes }
+    }
+
+    /// Returns true if `input` could be a (possibly abbreviated) hexadecimal object id.
+    pub fn is_hex_prefix(input: &str) -> bool {
+        input.len() >= OID_MIN_ABBREV_LEN && input.len() <= OID_HEX_LEN && ObjectId::is_hex(input)
+    }
+
+    fn is_hex(input: &str) -> bool {
+        input.bytes().all(|byte| byte.is_ascii_hexdigit())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; OID_RAW_LEN] {
+        &self.bytes
+    }
+
+    pub fn is_null(self) -> bool {
+        self.bytes.iter().all(|byte| *byte == 0)
+    }
+
+    pub fn to_hex(self) -> String {
+        let mut result = String::with_capacity(OID_HEX_LEN);
+        for byte in self.bytes.iter() {
+            result.push_str(&format!("{:02x}", byte));
+        }
+        result
+    }
+
+    pub fn abbreviate(self, len: usize) -> String {
+        let mut hex = self.to_hex();
+        hex.truncate(len.clamp(OID_MIN_ABBREV_LEN, OID_HEX_LEN));
+        hex
+    }
+
+    pub fn matches_prefix(self, prefix: &str) -> bool {
+        prefix.len() <= OID_HEX_LEN && self.to_hex().starts_with(&prefix.to_ascii_lowercase())
+    }
+}
+
+impl fmt::Display for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for ObjectId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ObjectId({})", self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_OID: &str = "5e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689";
+
+    #[test]
+    fn test_hex_round_trip() {
+        let oid = ObjectId::from_hex(HELLO_OID).unwrap();
+        assert_eq!(HELLO_OID, oid.to_hex());
+        assert_eq!(HELLO_OID, format!("{}", oid));
+        assert_eq!(oid, ObjectId::from_bytes(oid.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn test_uppercase_hex_is_normalized() {
+        let oid = ObjectId::from_hex(&HELLO_OID.to_uppercase()).unwrap();
+        assert_eq!(HELLO_OID, oid.to_hex());
+    }
+
+    #[test]
+    fn test_reject_invalid_hex() {
+        assert!(ObjectId::from_hex("5e1c309d").is_err());
+        assert!(ObjectId::from_hex(&format!("{}0", HELLO_OID)).is_err());
+        assert!(ObjectId::from_hex("zz1c309dae7f45e0f39b1bf3ac3cd9db12e7d689").is_err());
+        assert!(ObjectId::from_hex("+e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689").is_err());
+        assert!(ObjectId::from_hex("é1c309dae7f45e0f39b1bf3ac3cd9db12e7d68").is_err());
+        assert!(ObjectId::from_bytes(&[0; 19]).is_err());
+    }
+
+    #[test]
+    fn test_abbreviate_and_match_prefix() {
+        let oid = ObjectId::from_hex(HELLO_OID).unwrap();
+        assert_eq!("5e1c309", oid.abbreviate(7));
+        assert_eq!("5e1c", oid.abbreviate(1));
+        assert_eq!(HELLO_OID, oid.abbreviate(100));
+        assert!(oid.matches_prefix("5E1C30"));
+        assert!(!oid.matches_prefix("5e1d"));
+        assert!(ObjectId::is_hex_prefix("5e1c"));
+        assert!(!ObjectId::is_hex_prefix("5e1"));
+        assert!(!ObjectId::is_hex_prefix("master"));
+    }
+
+    #[test]
+    fn test_null() {
+        assert!(ObjectId::null().is_null());
+        assert_eq!("0".repeat(OID_HEX_LEN), ObjectId::null().to_hex());
+        assert!(!ObjectId::from_hex(HELLO_OID).unwrap().is_null());
+    }
+}
@@ -0,0 +1,164 @@
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
+
+const HEADER_PREFIX: &str = "# pack-refs with:";
+const SORTED_TRAIT: &str = "sorted";
+/// The traits of a file where every annotated tag has its peeled value.
+const PEELED_TRAITS: [&str; 3] = ["peeled", "fully-peeled", SORTED_TRAIT];
+
+pub struct PackedRef {
+    pub name: String,
+    pub oid: ObjectId,
+    /// For an annotated tag, the object the tag points to once fully peeled.
+    pub peeled: Option<ObjectId>,
+}
+
+/// The content of `.git/packed-refs`: one `<oid> <name>` line per ref, optionally followed by a `^<oid>` line
+/// giving the peeled value of a tag, after a header listing the traits of the file.
+pub struct PackedRefs {
+    traits: Vec<String>,
+    refs: Vec<PackedRef>,
+}
+
+impl Default for PackedRefs {
+    fn default() -> Self {
+        PackedRefs::new()
+    }
+}
+
+impl PackedRefs {
+    pub fn new() -> PackedRefs {
+        PackedRefs { traits: vec!(String::from(SORTED_TRAIT)), refs: Vec::new() }
+    }
+
+    pub fn parse(content: &str) -> MinigitResult<PackedRefs> {
+        let mut packed_refs = PackedRefs { traits: Vec::new(), refs: Vec::new() };
+        for line in content.lines() {
+            let unexpected_line = || MinigitError::with_kind(ErrorKind::Corrupt, format!("fatal: unexpected line in packed-refs: '{}'", line));
+            if let Some(traits) = line.strip_prefix(HEADER_PREFIX) {
+                packed_refs.traits = traits.split_whitespace().map(String::from).collect();
+            } else if line.starts_with('#') || line.is_empty() {
+                continue;
+            } else if let Some(peeled) = line.strip_prefix('^') {
+                match packed_refs.refs.last_mut() {
+                    Some(last) if last.peeled.is_none() => last.peeled = Some(ObjectId::from_hex(peeled)?),
+                    _ => return Err(unexpected_line()),
+                }
+            } else {
+                if line.len() < OID_HEX_LEN + 2 || line.as_bytes()[OID_HEX_LEN] != b' ' {
+                    return Err(unexpected_line());
+                }
+                let oid = ObjectId::from_hex(&line[..OID_HEX_LEN])?;
+                packed_refs.refs.push(PackedRef { name: String::from(&line[OID_HEX_LEN + 1..]), oid, peeled: None });
+            }
+        }
+        if !packed_refs.has_trait(SORTED_TRAIT) {
+            packed_refs.refs.sort_by(|left, right| left.name.cmp(&right.name));
+            packed_refs.traits.push(String::from(SORTED_TRAIT));
+        }
+        Ok(packed_refs)
+    }
+
+    pub fn has_trait(&self, name: &str) -> bool {
+        self.traits.iter().any(|packed_trait| packed_trait == name)
+    }
+
+    /// Sets the peeled value of every ref, `peel` returning `None` for the objects which are not annotated tags.
+    pub fn peel_refs(&mut self, peel: &dyn Fn(&ObjectId) -> MinigitResult<Option<ObjectId>>) -> MinigitResult<()> {
+        for packed_ref in self.refs.iter_mut() {
+            packed_ref.peeled = peel(&packed_ref.oid)?;
+        }
+        self.traits = PEELED_TRAITS.iter().map(|packed_trait| String::from(*packed_trait)).collect();
+        Ok(())
+    }
+
+    pub fn get_refs(&self) -> &[PackedRef] {
+        &self.refs
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PackedRef> {
+        self.find(name).ok().map(|index| &self.refs[index])
+    }
+
+    /// Adds a ref, replacing the packed ref with the same name if there is one.
+    pub fn insert(&mut self, packed_ref: PackedRef) {
+        match self.find(&packed_ref.name) {
+            Ok(index) => self.refs[index] = packed_ref,
+            Err(index) => self.refs.insert(index, packed_ref),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        match self.find(name) {
+            Ok(index) => {
+                self.refs.remove(index);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut content = format!("{} {} \n", HEADER_PREFIX, self.traits.join(" "));
+        for packed_ref in self.refs.iter() {
+            content.push_str(&format!("{} {}\n", packed_ref.oid, packed_ref.name));
+            if let Some(peeled) = packed_ref.peeled {
+                content.push_str(&format!("^{}\n", peeled));
+            }
+        }
+        content
+    }
+
+    fn find(&self, name: &str) -> Result<usize, usize> {
+        self.refs.binary_search_by(|packed_ref| packed_ref.name.as_str().cmp(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER_OID: &str = "5e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689";
+    const TAG_OID: &str = "8b137891791fe96927ad78e64b0aad7bded08bdc";
+
+    #[test]
+    fn test_parse_and_serialize() {
+        let content = format!("# pack-refs with: peeled fully-peeled sorted \n\
+                               {} refs/heads/master\n\
+                               {} refs/tags/v1.0\n\
+                               ^{}\n", MASTER_OID, TAG_OID, MASTER_OID);
+        let packed_refs = PackedRefs::parse(&content).unwrap();
+        assert!(packed_refs.has_trait("fully-peeled"));
+        assert_eq!(2, packed_refs.get_refs().len());
+        assert_eq!(MASTER_OID, packed_refs.get("refs/heads/master").unwrap().oid.to_hex());
+        assert_eq!(None, packed_refs.get("refs/heads/master").unwrap().peeled);
+        assert_eq!(Some(ObjectId::from_hex(MASTER_OID).unwrap()), packed_refs.get("refs/tags/v1.0").unwrap().peeled);
+        assert!(packed_refs.get("refs/heads/missing").is_none());
+        assert_eq!(content, packed_refs.serialize());
+    }
+
+    #[test]
+    fn test_unsorted_file_is_sorted() {
+        let content = format!("{} refs/tags/v1.0\n{} refs/heads/master\n", TAG_OID, MASTER_OID);
+        let mut packed_refs = PackedRefs::parse(&content).unwrap();
+        assert_eq!("refs/heads/master", packed_refs.get_refs()[0].name);
+        assert!(packed_refs.get("refs/tags/v1.0").is_some());
+        assert!(packed_refs.remove("refs/tags/v1.0"));
+        assert!(!packed_refs.remove("refs/tags/v1.0"));
+        packed_refs.insert(PackedRef { name: String::from("refs/heads/a"), oid: ObjectId::from_hex(TAG_OID).unwrap(), peeled: None });
+        assert_eq!(format!("# pack-refs with: sorted \n{} refs/heads/a\n{} refs/heads/master\n", TAG_OID, MASTER_OID), packed_refs.serialize());
+
+        let tag_oid = ObjectId::from_hex(TAG_OID).unwrap();
+        let master_oid = ObjectId::from_hex(MASTER_OID).unwrap();
+        packed_refs.peel_refs(&|oid| Ok(if *oid == tag_oid { Some(master_oid) } else { None })).unwrap();
+        assert_eq!(format!("# pack-refs with: peeled fully-peeled sorted \n{} refs/heads/a\n^{}\n{} refs/heads/master\n", TAG_OID, MASTER_OID, MASTER_OID),
+                   packed_refs.serialize());
+    }
+
+    #[test]
+    fn test_reject_malformed_lines() {
+        assert!(PackedRefs::parse("garbage\n").is_err());
+        assert!(PackedRefs::parse(&format!("^{}\n", MASTER_OID)).is_err());
+        assert!(PackedRefs::parse(&format!("{}refs/heads/master\n", MASTER_OID)).is_err());
+    }
+}
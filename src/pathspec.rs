@@ -0,0 +1,360 @@
+use std::path::{Path, PathBuf};
+
+use crate::attributes::{parse_state, AttributeState, Attributes};
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::workspace::Workspace;
+
+const WILDCARDS: &[char] = &['*', '?', '[', '\\'];
+
+/// The patterns selecting the paths a command operates on.
+///
+/// A pattern matches a path equal to it, the files under the directory it names, or the paths it matches as a wildcard
+/// pattern, where `*` also matches slashes. Magic signatures alter this, in their long form `:(icase,glob)pattern` or
+/// their short form `:!pattern`:
+/// * `top` (or `/`): the pattern is relative to the top of the workspace rather than to the current directory.
+/// * `exclude` (or `!` and `^`): paths matching the pattern are excluded from the other patterns.
+/// * `icase`: the match is case-insensitive.
+/// * `glob`: wildcards never match slashes except in `**`, like in `.gitignore`.
+/// * `literal`: wildcards are matched literally.
+/// * `attr:<requirements>`: paths must also have the attributes listed, as `attr`, `-attr`, `attr=value` or `!attr`.
+pub struct Pathspec {
+    items: Vec<PathspecItem>,
+    attributes: Option<Attributes>,
+}
+
+struct PathspecItem {
+    original: String,
+    pattern: String,
+    /// The pattern ended with a slash, so it only matches the content of directories.
+    directory: bool,
+    wildcard: bool,
+    exclude: bool,
+    icase: bool,
+    glob: bool,
+    requirements: Vec<(String, AttributeState)>,
+}
+
+#[derive(PartialEq)]
+enum MatchKind {
+    Exact,
+    LeadingDirectory,
+    Wildcard,
+}
+
+impl Pathspec {
+    pub fn parse(specs: &[String], workspace: &Workspace) -> MinigitResult<Pathspec> {
+        let items = specs.iter()
+            .map(|spec| PathspecItem::parse(spec, workspace))
+            .collect::<MinigitResult<Vec<PathspecItem>>>()?;
+        let attributes = match items.iter().any(|item| !item.requirements.is_empty()) {
+            true => {
+                let files: Vec<PathBuf> = workspace.list_files_from_path(Path::new(""))?.into_iter()
+                    .filter(|file| file.file_name().is_some_and(|name| name == ".gitattributes"))
+                    .collect();
+                Some(Attributes::load(workspace.get_path(), &files)?)
+            }
+            false => None,
+        };
+        Ok(Pathspec { items, attributes })
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        self.match_path(path).is_some()
+    }
+
+    /// Returns the patterns which match none of the paths, the exclusions aside.
+    pub fn find_unmatched<'a, I: IntoIterator<Item = &'a Path>>(&self, paths: I) -> Vec<&str> {
+        let mut matched = vec![false; self.items.len()];
+        for path in paths {
+            if self.is_excluded(path) {
+                continue;
+            }
+            for (index, item) in self.items.iter().enumerate() {
+                if !item.exclude && !matched[index] && self.match_item(item, path).is_some() {
+                    matched[index] = true;
+                }
+            }
+        }
+        self.items.iter().zip(matched)
+            .filter(|(item, matched)| !item.exclude && !matched)
+            .map(|(item, _)| item.original.as_str())
+            .collect()
+    }
+
+    /// Returns the pattern naming a directory which contains the path, when no pattern matches the path itself.
+    pub fn find_recursive_match(&self, path: &Path) -> Option<&str> {
+        match self.match_path(path)? {
+            (Some(item), MatchKind::LeadingDirectory) => Some(item.original.as_str()),
+            (None, _) => Some("."),
+            _ => None,
+        }
+    }
+
+    /// Returns the best pattern matching a path, which is none when the pathspec only contains exclusions.
+    fn match_path(&self, path: &Path) -> Option<(Option<&PathspecItem>, MatchKind)> {
+        if self.is_excluded(path) {
+            return None;
+        }
+        if self.items.iter().all(|item| item.exclude) {
+            return Some((None, MatchKind::LeadingDirectory));
+        }
+        let mut best = None;
+        for item in self.items.iter().filter(|item| !item.exclude) {
+            match self.match_item(item, path) {
+                Some(MatchKind::LeadingDirectory) if best.is_none() => best = Some((Some(item), MatchKind::LeadingDirectory)),
+                Some(MatchKind::LeadingDirectory) | None => {}
+                Some(kind) => return Some((Some(item), kind)),
+            }
+        }
+        best
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        self.items.iter().any(|item| item.exclude && self.match_item(item, path).is_some())
+    }
+
+    fn match_item(&self, item: &PathspecItem, path: &Path) -> Option<MatchKind> {
+        let path = path.to_string_lossy();
+        let kind = item.match_path(&path)?;
+        let attributes = self.attributes.as_ref();
+        match item.requirements.iter().all(|(name, state)| attributes.map(|attributes| attributes.get(&path, name)).as_ref() == Some(state)) {
+            true => Some(kind),
+            false => None,
+        }
+    }
+}
+
+impl PathspecItem {
+    fn parse(spec: &str, workspace: &Workspace) -> MinigitResult<PathspecItem> {
+        let mut item = PathspecItem {
+            original: String::from(spec),
+            pattern: String::new(),
+            directory: false,
+            wildcard: false,
+            exclude: false,
+            icase: false,
+            glob: false,
+            requirements: Vec::new(),
+        };
+        let mut literal = false;
+        let mut top = false;
+        let path = if let Some(rest) = spec.strip_prefix(":(") {
+            let end = rest.find(')')
+                .ok_or_else(|| MinigitError::fatal(format!("fatal: Missing ')' at the end of pathspec magic in '{}'", spec)))?;
+            for word in rest[..end].split(',') {
+                match word {
+                    "top" => top = true,
+                    "exclude" => item.exclude = true,
+                    "icase" => item.icase = true,
+                    "glob" => item.glob = true,
+                    "literal" => literal = true,
+                    word if word.starts_with("attr:") => item.requirements.extend(parse_requirements(&word["attr:".len()..])?),
+                    word => return Err(MinigitError::fatal(format!("fatal: Invalid pathspec magic '{}' in '{}'", word, spec))),
+                }
+            }
+            &rest[end + 1..]
+        } else if let Some(rest) = spec.strip_prefix(':') {
+            let end = rest.find(|c| !['/', '!', '^'].contains(&c)).unwrap_or(rest.len());
+            top = rest[..end].contains('/');
+            item.exclude = rest[..end].contains(['!', '^']);
+            rest[end..].strip_prefix(':').unwrap_or(&rest[end..])
+        } else {
+            spec
+        };
+        if literal && item.glob {
+            return Err(MinigitError::fatal(format!("fatal: {}: 'literal' and 'glob' are incompatible", spec)));
+        }
+        item.pattern = workspace.resolve_path(path, top)
+            .ok_or_else(|| MinigitError::fatal(format!("fatal: {}: '{}' is outside repository at '{}'", spec, path, workspace.get_path().display())))?
+            .to_string_lossy().into_owned();
+        item.directory = path.ends_with('/') && !item.pattern.is_empty();
+        item.wildcard = !literal && item.pattern.contains(WILDCARDS);
+        if item.icase {
+            item.pattern = item.pattern.to_lowercase();
+        }
+        Ok(item)
+    }
+
+    fn match_path(&self, path: &str) -> Option<MatchKind> {
+        if self.pattern.is_empty() {
+            return Some(MatchKind::LeadingDirectory);
+        }
+        let lowercase_path;
+        let path = match self.icase {
+            true => {
+                lowercase_path = path.to_lowercase();
+                lowercase_path.as_str()
+            }
+            false => path,
+        };
+        if path == self.pattern && !self.directory {
+            return Some(MatchKind::Exact);
+        }
+        if path.strip_prefix(self.pattern.as_str()).is_some_and(|rest| rest.starts_with('/')) {
+            return Some(MatchKind::LeadingDirectory);
+        }
+        if !self.wildcard {
+            return None;
+        }
+        // A pattern matching a directory matches all the files inside it.
+        let mut candidates: Vec<&str> = path.match_indices('/').map(|(index, _)| &path[..index]).collect();
+        if !self.directory {
+            candidates.push(path);
+        }
+        match candidates.into_iter().any(|candidate| wildmatch(self.pattern.as_bytes(), candidate.as_bytes(), self.glob)) {
+            true => Some(MatchKind::Wildcard),
+            false => None,
+        }
+    }
+}
+
+fn parse_requirements(value: &str) -> MinigitResult<Vec<(String, AttributeState)>> {
+    let requirements: Vec<(String, AttributeState)> = value.split_whitespace().map(parse_state).collect();
+    match requirements.is_empty() || requirements.iter().any(|(name, _)| name.is_empty()) {
+        true => Err(MinigitError::fatal(String::from("fatal: attr spec must not be empty"))),
+        false => Ok(requirements),
+    }
+}
+
+/// Matches a text against a wildcard pattern with `*`, `?`, `[...]` and `\` escapes. In `pathname` mode, wildcards
+/// don't match slashes, except `**` between slashes which matches any number of directories.
+pub fn wildmatch(pattern: &[u8], text: &[u8], pathname: bool) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') && pathname => {
+            let rest = &pattern[2..];
+            // `**/` also matches no directory at all.
+            if rest.first() == Some(&b'/') && wildmatch(&rest[1..], text, pathname) {
+                return true;
+            }
+            (0..=text.len()).any(|skip| wildmatch(rest, &text[skip..], pathname))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            (0..=text.len())
+                .take_while(|skip| !pathname || *skip == 0 || text[skip - 1] != b'/')
+                .any(|skip| wildmatch(rest, &text[skip..], pathname))
+        }
+        Some(b'?') => match text.first() {
+            Some(b'/') if pathname => false,
+            Some(_) => wildmatch(&pattern[1..], &text[1..], pathname),
+            None => false,
+        },
+        Some(b'[') => match (text.first(), match_class(&pattern[1..], text.first().copied())) {
+            (Some(b'/'), _) if pathname => false,
+            (Some(_), Some((true, length))) => wildmatch(&pattern[1 + length..], &text[1..], pathname),
+            (_, Some(_)) => false,
+            // An unterminated class is matched literally.
+            (_, None) => text.first() == Some(&b'[') && wildmatch(&pattern[1..], &text[1..], pathname),
+        },
+        Some(b'\\') if pattern.len() > 1 => text.first() == Some(&pattern[1]) && wildmatch(&pattern[2..], &text[1..], pathname),
+        Some(c) => text.first() == Some(c) && wildmatch(&pattern[1..], &text[1..], pathname),
+    }
+}
+
+/// Matches a character against the class after a `[`, returning whether it matched and the length of the class.
+fn match_class(class: &[u8], c: Option<u8>) -> Option<(bool, usize)> {
+    let negated = matches!(class.first(), Some(b'!') | Some(b'^'));
+    let mut index = if negated { 1 } else { 0 };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let start = *class.get(index)?;
+        if start == b']' && !first {
+            return Some((matched != negated, index + 1));
+        }
+        first = false;
+        if class.get(index + 1) == Some(&b'-') && class.get(index + 2).is_some_and(|end| *end != b']') {
+            let end = class[index + 2];
+            matched |= c.is_some_and(|c| start <= c && c <= end);
+            index += 3;
+        } else {
+            matched |= c == Some(start);
+            index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::tests::*;
+
+    use super::*;
+
+    fn pathspec(repo_path: &str, specs: &[&str]) -> MinigitResult<Pathspec> {
+        let specs: Vec<String> = specs.iter().map(|spec| String::from(*spec)).collect();
+        Pathspec::parse(&specs, &Workspace::new(Path::new(repo_path)))
+    }
+
+    fn matching(pathspec: &Pathspec, paths: &[&'static str]) -> Vec<&'static str> {
+        paths.iter().filter(|path| pathspec.matches(Path::new(path))).copied().collect()
+    }
+
+    const PATHS: [&str; 6] = ["README.md", "src/main.rs", "src/command/add.rs", "vendor/lib.rs", "docs/a.txt", "Cargo.toml"];
+
+    #[test]
+    fn test_wildmatch() {
+        assert!(wildmatch(b"*.rs", b"src/main.rs", false));
+        assert!(!wildmatch(b"*.rs", b"src/main.rs", true));
+        assert!(wildmatch(b"src/**/*.rs", b"src/main.rs", true));
+        assert!(wildmatch(b"src/**/*.rs", b"src/command/add.rs", true));
+        assert!(wildmatch(b"**/add.rs", b"src/command/add.rs", true));
+        assert!(wildmatch(b"src/**", b"src/command/add.rs", true));
+        assert!(wildmatch(b"[a-c]?[!x].txt", b"b_y.txt", true));
+        assert!(!wildmatch(b"[a-c]?[!x].txt", b"b_x.txt", true));
+        assert!(wildmatch(b"\\*.txt", b"*.txt", true));
+        assert!(!wildmatch(b"\\*.txt", b"a.txt", true));
+    }
+
+    #[test]
+    fn test_match_paths() {
+        run_test(|repo_path| {
+            assert_eq!(vec!("src/main.rs", "src/command/add.rs"), matching(&pathspec(repo_path, &["src"]).unwrap(), &PATHS));
+            assert_eq!(vec!("src/main.rs", "src/command/add.rs"), matching(&pathspec(repo_path, &["./src/"]).unwrap(), &PATHS));
+            assert_eq!(vec!("src/main.rs", "src/command/add.rs", "vendor/lib.rs"), matching(&pathspec(repo_path, &["*.rs"]).unwrap(), &PATHS));
+            assert_eq!(vec!("src/main.rs"), matching(&pathspec(repo_path, &[":(glob)src/*.rs"]).unwrap(), &PATHS));
+            assert_eq!(vec!("src/main.rs", "src/command/add.rs"), matching(&pathspec(repo_path, &[":(glob)**/*.rs", ":!vendor"]).unwrap(), &PATHS));
+            assert_eq!(vec!("README.md", "docs/a.txt", "Cargo.toml"), matching(&pathspec(repo_path, &[":^*.rs"]).unwrap(), &PATHS));
+            assert_eq!(vec!("README.md"), matching(&pathspec(repo_path, &[":(icase)readme.MD"]).unwrap(), &PATHS));
+            assert_eq!(Vec::<&str>::new(), matching(&pathspec(repo_path, &[":(literal)*.rs"]).unwrap(), &PATHS));
+            assert_eq!(vec!("Cargo.toml"), matching(&pathspec(repo_path, &[":/Cargo.toml"]).unwrap(), &PATHS));
+            assert_eq!(vec!("docs/a.txt"), matching(&pathspec(repo_path, &["d?cs"]).unwrap(), &PATHS));
+            let absolute_path = format!("{}/src/main.rs", Workspace::new(Path::new(repo_path)).get_path().display());
+            assert_eq!(vec!("src/main.rs"), matching(&pathspec(repo_path, &[&absolute_path]).unwrap(), &PATHS));
+        });
+    }
+
+    #[test]
+    fn test_unmatched_and_recursive_patterns() {
+        run_test(|repo_path| {
+            let pathspec = pathspec(repo_path, &["src", "*.md", "missing", ":!vendor"]).unwrap();
+            assert_eq!(vec!("missing"), pathspec.find_unmatched(PATHS.iter().map(Path::new)));
+            assert_eq!(Some("src"), pathspec.find_recursive_match(Path::new("src/main.rs")));
+            assert_eq!(None, pathspec.find_recursive_match(Path::new("README.md")));
+        });
+    }
+
+    #[test]
+    fn test_attribute_magic() {
+        run_test(|repo_path| {
+            fs::write(PathBuf::from(repo_path).join(".gitattributes"), "*.rs text\nvendor/** -text\n").unwrap();
+            assert_eq!(vec!("src/main.rs", "src/command/add.rs"), matching(&pathspec(repo_path, &[":(attr:text)"]).unwrap(), &PATHS));
+            assert_eq!(vec!("vendor/lib.rs"), matching(&pathspec(repo_path, &[":(attr:-text)"]).unwrap(), &PATHS));
+            assert_eq!(vec!("README.md", "docs/a.txt", "Cargo.toml"), matching(&pathspec(repo_path, &[":(attr:!text)"]).unwrap(), &PATHS));
+        });
+    }
+
+    #[test]
+    fn test_invalid_pathspecs() {
+        run_test(|repo_path| {
+            assert_eq!("fatal: Invalid pathspec magic 'bad' in ':(bad)a'", pathspec(repo_path, &[":(bad)a"]).err().unwrap().message);
+            assert_eq!("fatal: Missing ')' at the end of pathspec magic in ':(top'", pathspec(repo_path, &[":(top"]).err().unwrap().message);
+            assert_eq!("fatal: :(glob,literal)a: 'literal' and 'glob' are incompatible",
+                       pathspec(repo_path, &[":(glob,literal)a"]).err().unwrap().message);
+            assert_eq!("fatal: attr spec must not be empty", pathspec(repo_path, &[":(attr:)a"]).err().unwrap().message);
+            assert!(pathspec(repo_path, &["../outside"]).err().unwrap().message.starts_with("fatal: ../outside: '../outside' is outside repository"));
+        });
+    }
+}
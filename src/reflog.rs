@@ -0,0 +1,71 @@
+use crate::gitobject::Author;
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
+
+/// One line of a reflog file: `<old> <new> <name> <<email>> <timestamp> <tz>\t<message>`.
+#[derive(Clone)]
+pub struct ReflogEntry {
+    pub old_oid: ObjectId,
+    pub new_oid: ObjectId,
+    pub committer: Author,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    pub fn new(old_oid: Option<ObjectId>, new_oid: ObjectId, committer: &Author, message: &str) -> ReflogEntry {
+        ReflogEntry {
+            old_oid: old_oid.unwrap_or_else(ObjectId::null),
+            new_oid,
+            committer: committer.clone(),
+            // A reflog message is a single line.
+            message: message.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<&str>>().join(" "),
+        }
+    }
+
+    pub fn parse(line: &str) -> MinigitResult<ReflogEntry> {
+        let corrupted = || MinigitError::with_kind(ErrorKind::Corrupt, format!("fatal: corrupted reflog entry '{}'", line));
+        if line.len() < 2 * OID_HEX_LEN + 2 {
+            return Err(corrupted());
+        }
+        let (header, message) = match line.find('\t') {
+            Some(index) => (&line[..index], &line[index + 1..]),
+            None => (line, ""),
+        };
+        let old_oid = ObjectId::from_hex(header.get(..OID_HEX_LEN).ok_or_else(corrupted)?)?;
+        let new_oid = ObjectId::from_hex(header.get(OID_HEX_LEN + 1..2 * OID_HEX_LEN + 1).ok_or_else(corrupted)?)?;
+        let committer = Author::parse(header.get(2 * OID_HEX_LEN + 2..).ok_or_else(corrupted)?)?;
+        Ok(ReflogEntry { old_oid, new_oid, committer, message: String::from(message) })
+    }
+
+    pub fn get_timestamp(&self) -> i64 {
+        self.committer.get_timestamp().timestamp()
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{} {} {}\t{}\n", self.old_oid, self.new_oid, self.committer.to_string(), self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format() {
+        let line = "0000000000000000000000000000000000000000 5e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689 \
+                    A U Thor <author@example.com> 1500000000 -0130\tcommit (initial): first\n";
+        let entry = ReflogEntry::parse(line.trim_end_matches('\n')).unwrap();
+        assert!(entry.old_oid.is_null());
+        assert_eq!("5e1c309dae7f45e0f39b1bf3ac3cd9db12e7d689", entry.new_oid.to_hex());
+        assert_eq!("A U Thor", entry.committer.get_name());
+        assert_eq!(1_500_000_000, entry.get_timestamp());
+        assert_eq!("commit (initial): first", entry.message);
+        assert_eq!(line, entry.to_line());
+    }
+
+    #[test]
+    fn test_reject_corrupted_entries() {
+        assert!(ReflogEntry::parse("garbage").is_err());
+        assert!(ReflogEntry::parse(&format!("{} {} nobody\tmessage", "0".repeat(40), "1".repeat(40))).is_err());
+    }
+}
@@ -1,39 +1,653 @@
 use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 
+use crate::gitobject::Author;
 use crate::lockfile::Lockfile;
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
+use crate::packedrefs::{PackedRef, PackedRefs};
+use crate::reflog::ReflogEntry;
 
 pub struct Refs {
     path: PathBuf,
 }
 
+pub const HEAD: &str = "HEAD";
+const PACKED_REFS: &str = "packed-refs";
+const SYMREF_PREFIX: &str = "ref: ";
+const MAX_SYMREF_DEPTH: usize = 5;
+// Same order as git's `ref_rev_parse_rules`.
+const REF_LOOKUP_ORDER: [(&str, &str); 6] = [
+    ("", ""),
+    ("refs/", ""),
+    ("refs/tags/", ""),
+    ("refs/heads/", ""),
+    ("refs/remotes/", ""),
+    ("refs/remotes/", "/HEAD"),
+];
+
 impl Refs {
     pub fn new(path: PathBuf) -> Refs {
         Refs { path }
     }
 
-    pub fn read_head(&self) -> MinigitResult<Option<String>> {
-        let head_path = self.get_head_path();
-        if !head_path.exists() {
+    pub fn read_head(&self) -> MinigitResult<Option<ObjectId>> {
+        self.resolve_ref(HEAD)
+    }
+
+    pub fn transaction(&self, identity: &Author) -> RefTransaction<'_> {
+        RefTransaction { refs: self, identity: identity.clone(), deref: true, updates: Vec::new() }
+    }
+
+    /// Returns the full name of the ref a short name like `master` or `v1.0` refers to,
+    /// using git's lookup order.
+    pub fn expand_ref_name(&self, name: &str) -> MinigitResult<Option<String>> {
+        if !is_valid_ref_name(name) {
             return Ok(None);
         }
-        match fs::read_to_string(head_path) {
-            Ok(head) => Ok(Some(head)),
-            Err(e) => Err(MinigitError::new(String::from(format!("Error reading HEAD: {}", e)))),
+        let packed_refs = self.read_packed_refs()?;
+        Ok(REF_LOOKUP_ORDER.iter()
+            .filter(|(prefix, _)| !prefix.is_empty() || name.starts_with("refs/") || is_pseudo_ref_name(name))
+            .map(|(prefix, suffix)| format!("{}{}{}", prefix, name, suffix))
+            .find(|full_name| self.path.join(full_name).is_file() || packed_refs.get(full_name).is_some()))
+    }
+
+    /// Returns the shortest name that cannot be confused with another existing ref, like `%(refname:short)`.
+    pub fn short_name(&self, full_name: &str) -> MinigitResult<String> {
+        let packed_refs = self.read_packed_refs()?;
+        for (index, (prefix, suffix)) in REF_LOOKUP_ORDER.iter().enumerate().skip(1).rev() {
+            let short_name = match full_name.strip_prefix(prefix).and_then(|name| name.strip_suffix(suffix)) {
+                Some(short_name) if !short_name.is_empty() => short_name,
+                _ => continue,
+            };
+            let is_ambiguous = REF_LOOKUP_ORDER[..index].iter()
+                .map(|(prefix, suffix)| format!("{}{}{}", prefix, short_name, suffix))
+                .any(|other_name| self.path.join(&other_name).is_file() || packed_refs.get(&other_name).is_some());
+            if !is_ambiguous {
+                return Ok(String::from(short_name));
+            }
+        }
+        Ok(String::from(full_name))
+    }
+
+    pub fn read_ref(&self, name: &str) -> MinigitResult<Option<ObjectId>> {
+        match self.expand_ref_name(name)? {
+            Some(full_name) => self.resolve_ref(&full_name),
+            None => Ok(None),
         }
     }
 
-    pub fn update_head(&self, oid: &str) -> MinigitResult<()> {
-        let head_path = self.get_head_path();
-        let mut head_lockfile = Lockfile::new(head_path)?;
-        head_lockfile.write_str(oid)?;
-        head_lockfile.commit()?;
+    /// Reads the ref with the given full name, following symbolic refs.
+    pub fn resolve_ref(&self, full_name: &str) -> MinigitResult<Option<ObjectId>> {
+        let mut full_name = String::from(full_name);
+        for _ in 0..MAX_SYMREF_DEPTH {
+            match self.read_ref_file(&full_name)? {
+                Some(ref content) if content.starts_with(SYMREF_PREFIX) => {
+                    full_name = String::from(&content[SYMREF_PREFIX.len()..]);
+                }
+                Some(content) => return Ok(Some(ObjectId::from_hex(&content)?)),
+                None => return Ok(None),
+            }
+        }
+        Err(MinigitError::fatal(format!("fatal: too many levels of symbolic refs at '{}'", full_name)))
+    }
+
+    /// Lists the refs whose full name starts with `prefix` (e.g. `refs/heads/`), sorted by name,
+    /// with the object they point to.
+    pub fn list_refs(&self, prefix: &str) -> MinigitResult<Vec<(String, ObjectId)>> {
+        let mut names = Vec::new();
+        if let Err(e) = self.list_ref_names(&self.path.join("refs"), &mut names) {
+            return Err(MinigitError::new(format!("Error listing refs: {}", e)));
+        }
+        names.retain(|name| name.starts_with(prefix));
+        // Loose refs take precedence over packed ones.
+        let packed_refs = self.read_packed_refs()?;
+        let mut refs = Vec::new();
+        for name in names {
+            if let Some(oid) = self.resolve_ref(&name)? {
+                refs.push((name, oid));
+            }
+        }
+        for packed_ref in packed_refs.get_refs().iter().filter(|packed_ref| packed_ref.name.starts_with(prefix)) {
+            if !refs.iter().any(|(name, _)| *name == packed_ref.name) {
+                refs.push((packed_ref.name.clone(), packed_ref.oid));
+            }
+        }
+        refs.sort_by(|(left, _), (right, _)| left.cmp(right));
+        Ok(refs)
+    }
+
+    pub fn read_packed_refs(&self) -> MinigitResult<PackedRefs> {
+        let packed_refs_path = self.path.join(PACKED_REFS);
+        if !packed_refs_path.is_file() {
+            return Ok(PackedRefs::new());
+        }
+        match fs::read_to_string(&packed_refs_path) {
+            Ok(content) => PackedRefs::parse(&content),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", packed_refs_path.display(), e), e)),
+        }
+    }
+
+    /// Moves the loose refs into `packed-refs`: every ref with `all`, otherwise only the tags
+    /// and the refs which were already packed. Unless `prune` is false, the loose files are then deleted.
+    /// `peel` gives the object an annotated tag points to, and `None` for other objects.
+    pub fn pack_refs(&self, all: bool, prune: bool, peel: &dyn Fn(&ObjectId) -> MinigitResult<Option<ObjectId>>) -> MinigitResult<()> {
+        let mut lockfile = Lockfile::new(self.path.join(PACKED_REFS))?;
+        let mut packed_refs = self.read_packed_refs()?;
+
+        let mut names = Vec::new();
+        if let Err(e) = self.list_ref_names(&self.path.join("refs"), &mut names) {
+            return Err(MinigitError::new(format!("Error listing refs: {}", e)));
+        }
+        let mut packed_loose_refs = Vec::new();
+        for name in names {
+            if !all && !name.starts_with("refs/tags/") && packed_refs.get(&name).is_none() {
+                continue;
+            }
+            match self.read_loose_ref_file(&name)? {
+                Some(ref content) if content.starts_with(SYMREF_PREFIX) => continue,
+                Some(content) => {
+                    let oid = ObjectId::from_hex(&content)?;
+                    packed_refs.insert(PackedRef { name: name.clone(), oid, peeled: None });
+                    packed_loose_refs.push((name, oid));
+                }
+                None => continue,
+            }
+        }
+        packed_refs.peel_refs(peel)?;
+        lockfile.write_str(&packed_refs.serialize())?;
+        lockfile.commit()?;
+
+        if prune {
+            for (name, oid) in packed_loose_refs {
+                let ref_path = self.path.join(&name);
+                // Keep the loose ref if it has been updated in the meantime.
+                let ref_lockfile = Lockfile::new(ref_path.clone())?;
+                let unchanged = self.read_loose_ref_file(&name)? == Some(oid.to_hex());
+                if unchanged {
+                    self.remove_file(&ref_path)?;
+                }
+                drop(ref_lockfile);
+                if unchanged {
+                    self.remove_empty_parent_dirs(&ref_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list_ref_names(&self, dir_path: &Path, names: &mut Vec<String>) -> std::io::Result<()> {
+        if !dir_path.is_dir() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(dir_path)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.list_ref_names(&path, names)?;
+            } else if let Ok(name) = path.strip_prefix(&self.path) {
+                let name = name.to_string_lossy().into_owned();
+                if is_valid_ref_name(&name) {
+                    names.push(name);
+                }
+            }
+        }
         Ok(())
     }
 
-    fn get_head_path(&self) -> PathBuf {
-        self.path.join(Path::new("HEAD"))
+    /// Returns the full name of the branch HEAD points to, or `HEAD` if it is detached.
+    pub fn current_ref(&self) -> MinigitResult<String> {
+        match self.read_ref_file(HEAD)? {
+            Some(ref content) if content.starts_with(SYMREF_PREFIX) => Ok(String::from(&content[SYMREF_PREFIX.len()..])),
+            _ => Ok(String::from(HEAD)),
+        }
     }
-}
\ No newline at end of file
+
+    /// Returns the full name of the ref at the end of the chain of symbolic refs starting at `full_name`.
+    fn resolve_symref_name(&self, full_name: &str) -> MinigitResult<String> {
+        let mut full_name = String::from(full_name);
+        for _ in 0..MAX_SYMREF_DEPTH {
+            match self.read_ref_file(&full_name)? {
+                Some(ref content) if content.starts_with(SYMREF_PREFIX) => {
+                    full_name = String::from(&content[SYMREF_PREFIX.len()..]);
+                }
+                _ => return Ok(full_name),
+            }
+        }
+        Err(MinigitError::fatal(format!("fatal: too many levels of symbolic refs at '{}'", full_name)))
+    }
+
+    pub fn reflog_exists(&self, full_name: &str) -> bool {
+        self.get_reflog_path(full_name).is_file()
+    }
+
+    /// Lists the full names of the refs which have a reflog, HEAD first.
+    pub fn list_reflogs(&self) -> MinigitResult<Vec<String>> {
+        let mut names = Vec::new();
+        if let Err(e) = self.list_ref_names(&self.path.join("logs").join("refs"), &mut names) {
+            return Err(MinigitError::new(format!("Error listing reflogs: {}", e)));
+        }
+        let mut names: Vec<String> = names.iter().map(|name| String::from(name.trim_start_matches("logs/"))).collect();
+        names.sort();
+        if self.reflog_exists(HEAD) {
+            names.insert(0, String::from(HEAD));
+        }
+        Ok(names)
+    }
+
+    /// Reads the reflog of the ref with the given full name, oldest entry first.
+    pub fn read_reflog(&self, full_name: &str) -> MinigitResult<Vec<ReflogEntry>> {
+        let log_path = self.get_reflog_path(full_name);
+        if !log_path.is_file() {
+            return Ok(Vec::new());
+        }
+        let content = match fs::read_to_string(&log_path) {
+            Ok(content) => content,
+            Err(e) => return Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", log_path.display(), e), e)),
+        };
+        content.lines()
+            .filter(|line| !line.is_empty())
+            .map(ReflogEntry::parse)
+            .collect()
+    }
+
+    /// Replaces the whole reflog of a ref, e.g. after some entries have been expired.
+    pub fn write_reflog(&self, full_name: &str, entries: &[ReflogEntry]) -> MinigitResult<()> {
+        let log_path = self.get_reflog_path(full_name);
+        self.create_parent_dir(&log_path)?;
+        let mut lockfile = Lockfile::new(log_path)?;
+        for entry in entries {
+            lockfile.write_str(&entry.to_line())?;
+        }
+        lockfile.commit()
+    }
+
+    /// Like git with `core.logAllRefUpdates=true`, only branches, remote-tracking branches and HEAD get a reflog
+    /// unless one has been created explicitly.
+    fn should_log(&self, full_name: &str) -> bool {
+        full_name == HEAD || ["refs/heads/", "refs/remotes/", "refs/notes/"].iter().any(|prefix| full_name.starts_with(prefix))
+            || self.reflog_exists(full_name)
+    }
+
+    fn append_reflog(&self, full_name: &str, entry: &ReflogEntry) -> MinigitResult<()> {
+        let log_path = self.get_reflog_path(full_name);
+        self.create_parent_dir(&log_path)?;
+        let result = OpenOptions::new().create(true).append(true).open(&log_path)
+            .and_then(|mut file| file.write_all(entry.to_line().as_bytes()));
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error writing to {}: {}", log_path.display(), e), e)),
+        }
+    }
+
+    /// Deletes a loose ref and its reflog, then releases the lock taken on the ref.
+    fn delete_ref_files(&self, full_name: &str, lockfile: Lockfile) -> MinigitResult<()> {
+        let paths = [self.path.join(full_name), self.get_reflog_path(full_name)];
+        for path in paths.iter().filter(|path| path.is_file()) {
+            self.remove_file(path)?;
+        }
+        drop(lockfile);
+        for path in paths.iter() {
+            self.remove_empty_parent_dirs(path)?;
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> MinigitResult<()> {
+        match fs::remove_file(path) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error deleting {}: {}", path.display(), e), e)),
+        }
+    }
+
+    /// Removes the directories left empty by the deletion of `path`, keeping top-level ones like `refs/heads`.
+    fn remove_empty_parent_dirs(&self, path: &Path) -> MinigitResult<()> {
+        let mut dir = path.parent();
+        while let Some(dir_path) = dir {
+            let depth = match dir_path.strip_prefix(&self.path) {
+                Ok(relative_path) => relative_path.components().count(),
+                Err(_) => return Ok(()),
+            };
+            let is_empty = match fs::read_dir(dir_path) {
+                Ok(mut entries) => entries.next().is_none(),
+                Err(_) => return Ok(()),
+            };
+            if depth <= 2 || (depth == 3 && dir_path.starts_with(self.path.join("logs"))) || !is_empty {
+                return Ok(());
+            }
+            if let Err(e) = fs::remove_dir(dir_path) {
+                return Err(MinigitError::with_source(ErrorKind::Io, format!("Error deleting {}: {}", dir_path.display(), e), e));
+            }
+            dir = dir_path.parent();
+        }
+        Ok(())
+    }
+
+    fn create_parent_dir(&self, path: &Path) -> MinigitResult<()> {
+        match fs::create_dir_all(path.parent().unwrap()) {
+            Ok(()) => Ok(()),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error creating directory for {}: {}", path.display(), e), e)),
+        }
+    }
+
+    /// Reads the content of a ref, from its own file or else from `packed-refs`.
+    fn read_ref_file(&self, full_name: &str) -> MinigitResult<Option<String>> {
+        match self.read_loose_ref_file(full_name)? {
+            Some(content) => Ok(Some(content)),
+            None if full_name.starts_with("refs/") => {
+                Ok(self.read_packed_refs()?.get(full_name).map(|packed_ref| packed_ref.oid.to_hex()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn read_loose_ref_file(&self, full_name: &str) -> MinigitResult<Option<String>> {
+        let ref_path = self.path.join(full_name);
+        if !ref_path.is_file() {
+            return Ok(None);
+        }
+        match fs::read_to_string(&ref_path) {
+            Ok(content) => Ok(Some(String::from(content.trim()))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Error reading {}: {}", full_name, e), e)),
+        }
+    }
+
+    fn get_reflog_path(&self, full_name: &str) -> PathBuf {
+        self.path.join("logs").join(full_name)
+    }
+}
+
+struct RefUpdate {
+    full_name: String,
+    /// `None` only verifies the ref, the null id deletes it.
+    new_oid: Option<ObjectId>,
+    /// `None` skips the check, the null id requires the ref not to exist.
+    old_oid: Option<ObjectId>,
+    message: String,
+    deref: bool,
+}
+
+/// A set of ref updates applied all together or not at all.
+///
+/// Every ref is locked before any old value is checked, and nothing is written unless all the checks pass,
+/// so a concurrent update of one of the refs makes the whole transaction fail instead of being overwritten.
+pub struct RefTransaction<'a> {
+    refs: &'a Refs,
+    identity: Author,
+    deref: bool,
+    updates: Vec<RefUpdate>,
+}
+
+impl<'a> RefTransaction<'a> {
+    /// Sets whether the updates queued afterwards go through symbolic refs, like HEAD to the current branch.
+    pub fn set_deref(&mut self, deref: bool) {
+        self.deref = deref;
+    }
+
+    pub fn update(&mut self, full_name: &str, new_oid: ObjectId, old_oid: Option<ObjectId>, message: &str) {
+        self.queue(full_name, Some(new_oid), old_oid, message);
+    }
+
+    pub fn create(&mut self, full_name: &str, new_oid: ObjectId, message: &str) {
+        self.queue(full_name, Some(new_oid), Some(ObjectId::null()), message);
+    }
+
+    pub fn delete(&mut self, full_name: &str, old_oid: Option<ObjectId>, message: &str) {
+        self.queue(full_name, Some(ObjectId::null()), old_oid, message);
+    }
+
+    pub fn verify(&mut self, full_name: &str, old_oid: Option<ObjectId>) {
+        self.queue(full_name, None, old_oid, "");
+    }
+
+    fn queue(&mut self, full_name: &str, new_oid: Option<ObjectId>, old_oid: Option<ObjectId>, message: &str) {
+        self.updates.push(RefUpdate { full_name: String::from(full_name), new_oid, old_oid, message: String::from(message), deref: self.deref });
+    }
+
+    pub fn commit(self) -> MinigitResult<()> {
+        let mut targets: Vec<String> = Vec::new();
+        for update in self.updates.iter() {
+            let target = match update.deref {
+                true => self.refs.resolve_symref_name(&update.full_name)?,
+                false => update.full_name.clone(),
+            };
+            if !is_valid_ref_name(&target) || !(target.starts_with("refs/") || is_pseudo_ref_name(&target)) {
+                return Err(MinigitError::fatal(format!("fatal: refusing to update ref with bad name '{}'", target)));
+            }
+            if targets.contains(&target) {
+                return Err(MinigitError::fatal(format!("fatal: multiple updates for ref '{}' not allowed", target)));
+            }
+            targets.push(target);
+        }
+
+        // The locks are released by their destructor if anything below fails.
+        let mut lockfiles = Vec::new();
+        for (update, target) in self.updates.iter().zip(targets.iter()) {
+            let ref_path = self.refs.path.join(target);
+            self.refs.create_parent_dir(&ref_path)?;
+            match Lockfile::new(ref_path) {
+                Ok(lockfile) => lockfiles.push(lockfile),
+                Err(e) => return Err(MinigitError::with_kind(e.kind, format!("fatal: cannot lock ref '{}': {}", update.full_name, e.message))),
+            }
+        }
+        // Deleting a packed ref means rewriting `packed-refs`, which must be locked as well.
+        let mut packed_refs = self.refs.read_packed_refs()?;
+        let mut packed_refs_lockfile = None;
+        let packed_deletions: Vec<&String> = self.updates.iter().zip(targets.iter())
+            .filter(|(update, target)| update.new_oid.is_some_and(ObjectId::is_null) && packed_refs.get(target).is_some())
+            .map(|(_, target)| target)
+            .collect();
+        if !packed_deletions.is_empty() {
+            match Lockfile::new(self.refs.path.join(PACKED_REFS)) {
+                Ok(lockfile) => packed_refs_lockfile = Some(lockfile),
+                Err(e) => return Err(MinigitError::with_kind(e.kind, format!("fatal: unable to lock packed-refs: {}", e.message))),
+            }
+            // Read again now that nobody else can change it.
+            packed_refs = self.refs.read_packed_refs()?;
+        }
+
+        let mut current_oids = Vec::new();
+        for (update, target) in self.updates.iter().zip(targets.iter()) {
+            let current_oid = self.refs.resolve_ref(target)?;
+            check_old_oid(update, current_oid)?;
+            current_oids.push(current_oid);
+        }
+
+        if let Some(mut lockfile) = packed_refs_lockfile {
+            for target in packed_deletions {
+                packed_refs.remove(target);
+            }
+            lockfile.write_str(&packed_refs.serialize())?;
+            lockfile.commit()?;
+        }
+
+        let head_target = self.refs.resolve_symref_name(HEAD)?;
+        for (((update, target), mut lockfile), current_oid) in self.updates.iter().zip(targets.iter()).zip(lockfiles).zip(current_oids) {
+            let new_oid = match update.new_oid {
+                Some(new_oid) => new_oid,
+                None => continue,
+            };
+            if new_oid.is_null() {
+                self.refs.delete_ref_files(target, lockfile)?;
+                continue;
+            }
+            lockfile.write_str(&format!("{}\n", new_oid))?;
+            lockfile.commit()?;
+            let entry = ReflogEntry::new(current_oid, new_oid, &self.identity, &update.message);
+            if self.refs.should_log(target) {
+                self.refs.append_reflog(target, &entry)?;
+            }
+            if target != HEAD && *target == head_target {
+                self.refs.append_reflog(HEAD, &entry)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_old_oid(update: &RefUpdate, current_oid: Option<ObjectId>) -> MinigitResult<()> {
+    let error = |reason: String| Err(MinigitError::fatal(format!("fatal: cannot lock ref '{}': {}", update.full_name, reason)));
+    match (update.old_oid, current_oid) {
+        (None, _) => Ok(()),
+        (Some(old_oid), None) if old_oid.is_null() => Ok(()),
+        (Some(old_oid), Some(_)) if old_oid.is_null() => error(String::from("reference already exists")),
+        (Some(_), None) => error(format!("unable to resolve reference '{}'", update.full_name)),
+        (Some(old_oid), Some(current_oid)) if old_oid != current_oid => error(format!("is at {} but expected {}", current_oid, old_oid)),
+        _ => Ok(()),
+    }
+}
+
+/// Names like `HEAD` or `ORIG_HEAD` which live directly in the git directory.
+fn is_pseudo_ref_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+/// Checks a ref name against the rules of `git check-ref-format`.
+pub fn is_valid_ref_name(name: &str) -> bool {
+    if name.is_empty() || name == "@" || name.ends_with('/') || name.ends_with('.') || name.ends_with(".lock") {
+        return false;
+    }
+    if name.contains("..") || name.contains("@{") || name.contains("//") {
+        return false;
+    }
+    if name.chars().any(|c| c.is_ascii_control() || " ~^:?*[\\".contains(c)) {
+        return false;
+    }
+    name.split('/').all(|component| !component.is_empty() && !component.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Local;
+
+    use crate::repository::Repository;
+    use crate::tests::*;
+
+    use super::*;
+
+    fn identity() -> Author {
+        Author::new("A U Thor", "author@example.com", Local::now())
+    }
+
+    #[test]
+    fn test_transaction_applies_all_updates() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            let refs = Refs::new(PathBuf::from(repo_path).join(".git"));
+            let mut transaction = refs.transaction(&identity());
+            transaction.create("refs/heads/topic", first, "branch: created");
+            transaction.update("refs/tags/v1", second, None, "tag");
+            transaction.update(HEAD, first, Some(second), "reset: moving to first");
+            transaction.commit().unwrap();
+            assert_eq!(Some(first), refs.resolve_ref("refs/heads/topic").unwrap());
+            assert_eq!(Some(second), refs.resolve_ref("refs/tags/v1").unwrap());
+            assert_eq!(Some(first), refs.resolve_ref("refs/heads/master").unwrap());
+            assert_eq!("reset: moving to first", refs.read_reflog(HEAD).unwrap().last().unwrap().message);
+
+            let mut transaction = refs.transaction(&identity());
+            transaction.delete("refs/heads/topic", Some(first), "");
+            transaction.commit().unwrap();
+            assert_eq!(None, refs.resolve_ref("refs/heads/topic").unwrap());
+            assert!(!refs.reflog_exists("refs/heads/topic"));
+        });
+    }
+
+    #[test]
+    fn test_transaction_is_rolled_back_when_a_check_fails() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            let refs = Refs::new(PathBuf::from(repo_path).join(".git"));
+            let mut transaction = refs.transaction(&identity());
+            transaction.create("refs/heads/topic", first, "");
+            transaction.update(HEAD, second, Some(first), "");
+            let error = transaction.commit().err().unwrap();
+            assert_eq!(format!("fatal: cannot lock ref 'HEAD': is at {} but expected {}", second, first), error.message);
+            assert_eq!(None, refs.resolve_ref("refs/heads/topic").unwrap());
+            assert!(!PathBuf::from(repo_path).join(".git/refs/heads/topic.lock").exists());
+            assert!(!PathBuf::from(repo_path).join(".git/refs/heads/master.lock").exists());
+
+            let mut transaction = refs.transaction(&identity());
+            transaction.create("refs/heads/master", first, "");
+            assert_eq!("fatal: cannot lock ref 'refs/heads/master': reference already exists", transaction.commit().err().unwrap().message);
+
+            let mut transaction = refs.transaction(&identity());
+            transaction.verify("refs/heads/missing", Some(first));
+            assert_eq!("fatal: cannot lock ref 'refs/heads/missing': unable to resolve reference 'refs/heads/missing'",
+                       transaction.commit().err().unwrap().message);
+        });
+    }
+
+    #[test]
+    fn test_transaction_rejects_duplicates_bad_names_and_locked_refs() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let mut repository = Repository::new(PathBuf::from(repo_path));
+            let refs = repository.refs();
+            let mut transaction = refs.transaction(&identity());
+            transaction.update(HEAD, first, None, "");
+            transaction.update("refs/heads/master", first, None, "");
+            assert_eq!("fatal: multiple updates for ref 'refs/heads/master' not allowed", transaction.commit().err().unwrap().message);
+
+            let mut transaction = refs.transaction(&identity());
+            transaction.update("refs/heads/a..b", first, None, "");
+            assert_eq!("fatal: refusing to update ref with bad name 'refs/heads/a..b'", transaction.commit().err().unwrap().message);
+
+            let _lock = Lockfile::new(PathBuf::from(repo_path).join(".git/refs/heads/master")).unwrap();
+            let mut transaction = refs.transaction(&identity());
+            transaction.update(HEAD, first, None, "");
+            assert!(transaction.commit().err().unwrap().message.starts_with("fatal: cannot lock ref 'HEAD': Unable to create"));
+        });
+    }
+
+    #[test]
+    fn test_no_deref_updates_head_itself() {
+        run_test(|repo_path| {
+            let first = commit(repo_path, "first");
+            let second = commit(repo_path, "second");
+            let refs = Refs::new(PathBuf::from(repo_path).join(".git"));
+            let mut transaction = refs.transaction(&identity());
+            transaction.set_deref(false);
+            transaction.update(HEAD, first, Some(second), "checkout");
+            transaction.commit().unwrap();
+            assert_eq!(String::from(HEAD), refs.current_ref().unwrap());
+            assert_eq!(Some(first), refs.read_head().unwrap());
+            assert_eq!(Some(second), refs.resolve_ref("refs/heads/master").unwrap());
+        });
+    }
+
+    #[test]
+    fn test_short_names() {
+        run_test(|repo_path| {
+            let oid = commit(repo_path, "first");
+            let refs = Refs::new(PathBuf::from(repo_path).join(".git"));
+            let mut transaction = refs.transaction(&identity());
+            transaction.create("refs/tags/v1.0", oid, "");
+            transaction.create("refs/tags/master", oid, "");
+            transaction.create("refs/remotes/origin/HEAD", oid, "");
+            transaction.commit().unwrap();
+            assert_eq!("v1.0", refs.short_name("refs/tags/v1.0").unwrap());
+            assert_eq!("origin", refs.short_name("refs/remotes/origin/HEAD").unwrap());
+            assert_eq!("origin/main", refs.short_name("refs/remotes/origin/main").unwrap());
+            assert_eq!("heads/master", refs.short_name("refs/heads/master").unwrap());
+            assert_eq!(HEAD, refs.short_name(HEAD).unwrap());
+        });
+    }
+
+    #[test]
+    fn test_valid_ref_names() {
+        assert!(is_valid_ref_name("master"));
+        assert!(is_valid_ref_name("refs/heads/feature/one"));
+        assert!(is_valid_ref_name("v1.0"));
+    }
+
+    #[test]
+    fn test_invalid_ref_names() {
+        for name in ["", "@", "a..b", "a/../b", ".hidden", "a/.b", "a/", "a.lock", "a b", "a~1", "a^", "a:b", "a@{1}", "a//b", "a\\b"].iter() {
+            assert!(!is_valid_ref_name(name), "{} should be invalid", name);
+        }
+    }
+}
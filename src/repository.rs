@@ -1,13 +1,26 @@
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 
+use crate::config::Config;
 use crate::database::Database;
 use crate::index::Index;
-use crate::minigiterror::MinigitResult;
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
+use crate::objectid::ObjectId;
 use crate::refs::Refs;
 use crate::workspace::Workspace;
 
 pub struct Repository {
     path: PathBuf,
+    /// The top of the workspace, which a bare repository doesn't have.
+    work_tree: Option<PathBuf>,
+    /// The directory the command runs from, relative to the top of the workspace.
+    prefix: PathBuf,
+    index_path: PathBuf,
+    objects_path: PathBuf,
+    config: Option<Config>,
     database: Option<Database>,
     index: Option<Index>,
     refs: Option<Refs>,
@@ -16,12 +29,18 @@ pub struct Repository {
 
 impl Repository {
     pub fn new(path: PathBuf) -> Repository {
-        let path = match path.ends_with(".git") {
+        // A path which is already a git directory is kept, otherwise it is the workspace.
+        let path = match path.ends_with(".git") || (path.join("HEAD").is_file() && path.join("objects").is_dir()) {
             true => path,
             false => path.join(".git"),
         };
         Repository {
+            work_tree: path.parent().map(Path::to_path_buf),
+            prefix: PathBuf::new(),
+            index_path: path.join("index"),
+            objects_path: path.join("objects"),
             path,
+            config: None,
             database: None,
             index: None,
             refs: None,
@@ -29,16 +48,96 @@ impl Repository {
         }
     }
 
+    /// Finds the repository of a command run from a directory, like git does. The git directory is given by `GIT_DIR`,
+    /// or else is the first `.git` found from the directory up, without crossing a filesystem boundary, unless
+    /// `GIT_DISCOVERY_ACROSS_FILESYSTEM` is set, nor entering the directories of `GIT_CEILING_DIRECTORIES`. The workspace
+    /// is given by `GIT_WORK_TREE` or `core.worktree`, and the index and the object database can be moved with
+    /// `GIT_INDEX_FILE` and `GIT_OBJECT_DIRECTORY`.
+    pub fn discover(dir: &Path, env: &HashMap<String, String>) -> MinigitResult<Repository> {
+        let dir = dir.canonicalize()
+            .map_err(|e| MinigitError::with_source(ErrorKind::Io, format!("fatal: cannot change to '{}': {}", dir.display(), e), e))?;
+        let (path, top) = match env.get("GIT_DIR") {
+            Some(git_dir) => {
+                let path = dir.join(git_dir);
+                let path = read_gitdir_file(&path).unwrap_or(path);
+                if !is_git_directory(&path) {
+                    return Err(MinigitError::with_kind(ErrorKind::NotARepository, format!("fatal: not a git repository: '{}'", git_dir)));
+                }
+                // Without a workspace given, the current directory is its top.
+                (path, Some(dir.clone()))
+            }
+            None => find_git_directory(&dir, env)?,
+        };
+        let config = Config::load(path.join("config"))?;
+        let bare = config.get("core.bare").is_some_and(is_true);
+        let work_tree = match (env.get("GIT_WORK_TREE"), config.get("core.worktree")) {
+            (Some(work_tree), _) => Some(dir.join(work_tree)),
+            (None, Some(work_tree)) => Some(path.join(work_tree)),
+            (None, None) if bare => None,
+            (None, None) => top,
+        };
+        let work_tree = match work_tree {
+            Some(work_tree) => Some(work_tree.canonicalize().map_err(|e| MinigitError::with_source(
+                ErrorKind::Fatal, format!("fatal: Invalid path '{}': {}", work_tree.display(), e), e))?),
+            None => None,
+        };
+        Ok(Repository {
+            prefix: work_tree.as_ref().and_then(|work_tree| dir.strip_prefix(work_tree).ok()).map(Path::to_path_buf).unwrap_or_default(),
+            work_tree,
+            index_path: env.get("GIT_INDEX_FILE").map_or_else(|| path.join("index"), |index_file| dir.join(index_file)),
+            objects_path: env.get("GIT_OBJECT_DIRECTORY").map_or_else(|| path.join("objects"), |objects| dir.join(objects)),
+            path,
+            config: Some(config),
+            database: None,
+            index: None,
+            refs: None,
+            workspace: None,
+        })
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.work_tree.is_none()
+    }
+
+    pub fn get_objects_path(&self) -> &Path {
+        &self.objects_path
+    }
+
+    /// Opens the repository of the submodule checked out at a path of the workspace. Its `.git` is either a directory or
+    /// a file pointing to the git directory with a `gitdir:` line.
+    pub fn submodule(&self, path: &Path) -> Repository {
+        let work_tree = self.work_tree.as_ref().expect("a bare repository has no submodule").join(path);
+        let git_path = work_tree.join(".git");
+        let repository = match read_gitdir_file(&git_path) {
+            Some(git_dir) => Repository::new(git_dir),
+            None => Repository::new(git_path),
+        };
+        Repository { work_tree: Some(work_tree), ..repository }
+    }
+
+    pub fn config(&mut self) -> MinigitResult<&Config> {
+        if self.config.is_none() {
+            self.config = Some(Config::load(self.path.join("config"))?);
+        }
+        Ok(self.config.as_ref().unwrap())
+    }
+
     pub fn database(&mut self) -> &mut Database {
         if self.database.is_none() {
-            self.database = Some(Database::new(self.path.join("objects")));
+            let mut database = Database::new(self.objects_path.clone());
+            database.set_shallow(read_shallow_file(&self.path.join("shallow")));
+            self.database = Some(database);
         }
         self.database.as_mut().unwrap()
     }
 
     pub fn index(&mut self) -> MinigitResult<&mut Index> {
         if self.index.is_none() {
-            self.index = Some(Index::new(self.path.join("index"))?);
+            self.index = Some(Index::new(self.index_path.clone())?);
         }
         Ok(self.index.as_mut().unwrap())
     }
@@ -57,8 +156,77 @@ impl Repository {
 
     pub fn workspace(&mut self) -> &mut Workspace {
         if self.workspace.is_none() {
-            self.workspace = Some(Workspace::new(self.path.parent().unwrap()));
+            let work_tree = self.work_tree.as_ref().expect("the commands of a bare repository don't use a workspace");
+            self.workspace = Some(Workspace::new(work_tree).with_prefix(&self.prefix));
         }
         self.workspace.as_mut().unwrap()
     }
-}
\ No newline at end of file
+}
+/// Whether a directory looks like a git directory, with a `HEAD` and an object database.
+fn is_git_directory(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir()
+}
+
+/// Reads a `.git` file pointing to the git directory with a `gitdir:` line, the path being relative to the file.
+fn read_gitdir_file(path: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(path).ok()?;
+    let git_dir = content.strip_prefix("gitdir:")?.trim();
+    Some(path.parent()?.join(git_dir))
+}
+
+/// Looks for `.git` from a directory up, returning the git directory and the top of its workspace. A directory which is
+/// itself a git directory is a bare repository, without workspace.
+fn find_git_directory(dir: &Path, env: &HashMap<String, String>) -> MinigitResult<(PathBuf, Option<PathBuf>)> {
+    let ceilings: Vec<PathBuf> = env.get("GIT_CEILING_DIRECTORIES").map_or("", String::as_str).split(':')
+        .filter(|ceiling| Path::new(ceiling).is_absolute())
+        .map(|ceiling| Path::new(ceiling).canonicalize().unwrap_or_else(|_| PathBuf::from(ceiling)))
+        .collect();
+    let across_filesystems = env.get("GIT_DISCOVERY_ACROSS_FILESYSTEM").is_some_and(|value| is_true(value));
+    let device = device_of(dir);
+    let mut current = dir;
+    loop {
+        let dot_git = current.join(".git");
+        if dot_git.is_dir() && is_git_directory(&dot_git) {
+            return Ok((dot_git, Some(current.to_path_buf())));
+        }
+        if dot_git.is_file() {
+            return match read_gitdir_file(&dot_git) {
+                Some(git_dir) if is_git_directory(&git_dir) => Ok((git_dir, Some(current.to_path_buf()))),
+                _ => Err(MinigitError::with_kind(ErrorKind::NotARepository, format!("fatal: not a git repository: {}", dot_git.display()))),
+            };
+        }
+        if is_git_directory(current) {
+            return Ok((current.to_path_buf(), None));
+        }
+        let parent = match current.parent() {
+            Some(parent) if !ceilings.iter().any(|ceiling| ceiling == parent) => parent,
+            _ => break,
+        };
+        if !across_filesystems && device_of(parent) != device {
+            return Err(MinigitError::with_kind(ErrorKind::NotARepository, format!(
+                "fatal: not a git repository (or any parent up to mount point {})\n\
+                 Stopping at filesystem boundary (GIT_DISCOVERY_ACROSS_FILESYSTEM not set).", current.display())));
+        }
+        current = parent;
+    }
+    Err(MinigitError::with_kind(ErrorKind::NotARepository, String::from("fatal: not a git repository (or any of the parent directories): .git")))
+}
+
+/// Reads the ids of the commits whose parents were not fetched by a shallow clone, one per line.
+fn read_shallow_file(path: &Path) -> HashSet<ObjectId> {
+    fs::read_to_string(path).unwrap_or_default().lines().filter_map(|line| ObjectId::from_hex(line.trim()).ok()).collect()
+}
+
+fn is_true(value: &str) -> bool {
+    ["true", "yes", "on", "1"].contains(&value.to_ascii_lowercase().as_str())
+}
+
+#[cfg(unix)]
+fn device_of(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,392 @@
+use chrono::Local;
+
+use crate::approxidate;
+use crate::database::DatabaseObject;
+use crate::gitobject::Entry;
+use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::objectid::{OID_HEX_LEN, ObjectId};
+use crate::refs::HEAD;
+use crate::repository::Repository;
+
+const PEEL_TYPES: [&str; 6] = ["", "object", "commit", "tree", "blob", "tag"];
+const MIN_ABBREV_LEN: usize = 7;
+
+#[derive(Debug, PartialEq)]
+enum RevisionNode {
+    Ref(String),
+    Parent(Box<RevisionNode>, usize),
+    Ancestor(Box<RevisionNode>, usize),
+    Peel(Box<RevisionNode>, String),
+    Reflog(Option<String>, ReflogSelector),
+    TreePath(Box<RevisionNode>, String, String),
+    IndexPath(u8, String),
+}
+
+/// Selects an entry of a reflog, either by position (`@{2}`) or by date (`@{yesterday}`).
+#[derive(Debug, PartialEq)]
+enum ReflogSelector {
+    Index(usize),
+    Date(i64),
+}
+
+/// A revision expression such as `HEAD~2`, `master^{tree}`, `v1.0:src/main.rs` or `@{1}`,
+/// following the syntax described in gitrevisions(7).
+pub struct Revision {
+    expression: String,
+    node: RevisionNode,
+}
+
+impl Revision {
+    pub fn parse(expression: &str) -> MinigitResult<Revision> {
+        match parse_node(expression) {
+            Some(node) => Ok(Revision { expression: String::from(expression), node }),
+            None => Err(unknown_revision_error(expression)),
+        }
+    }
+
+    pub fn get_expression(&self) -> &str {
+        &self.expression
+    }
+
+    /// Resolves the expression to the id of the object it designates, whatever its type.
+    pub fn resolve(&self, repository: &mut Repository) -> MinigitResult<ObjectId> {
+        match self.resolve_node(&self.node, repository)? {
+            Some(oid) => Ok(oid),
+            None => Err(unknown_revision_error(&self.expression)),
+        }
+    }
+
+    /// Resolves the expression, then dereferences the result until an object of the given type is found.
+    pub fn resolve_to(&self, repository: &mut Repository, object_type: &str) -> MinigitResult<ObjectId> {
+        let oid = self.resolve(repository)?;
+        self.peel(repository, oid, object_type)
+    }
+
+    pub fn resolve_commit(&self, repository: &mut Repository) -> MinigitResult<ObjectId> {
+        self.resolve_to(repository, "commit")
+    }
+
+    fn resolve_node(&self, node: &RevisionNode, repository: &mut Repository) -> MinigitResult<Option<ObjectId>> {
+        match node {
+            RevisionNode::Ref(name) => self.resolve_name(name, repository),
+            RevisionNode::Parent(inner, n) => {
+                let oid = match self.resolve_commit_node(inner, repository)? {
+                    Some(oid) => oid,
+                    None => return Ok(None),
+                };
+                if *n == 0 {
+                    return Ok(Some(oid));
+                }
+                Ok(repository.database().load_commit(&oid)?.get_parents().get(n - 1).cloned())
+            }
+            RevisionNode::Ancestor(inner, n) => {
+                let mut oid = match self.resolve_commit_node(inner, repository)? {
+                    Some(oid) => oid,
+                    None => return Ok(None),
+                };
+                for _ in 0..*n {
+                    oid = match repository.database().load_commit(&oid)?.get_parents().first() {
+                        Some(parent) => *parent,
+                        None => return Ok(None),
+                    };
+                }
+                Ok(Some(oid))
+            }
+            RevisionNode::Peel(inner, object_type) => {
+                match self.resolve_node(inner, repository)? {
+                    Some(oid) => Ok(Some(self.peel(repository, oid, object_type)?)),
+                    None => Ok(None),
+                }
+            }
+            RevisionNode::Reflog(name, selector) => self.resolve_reflog(name, selector, repository),
+            RevisionNode::TreePath(inner, revision, path) => {
+                let tree_oid = match self.resolve_node(inner, repository)? {
+                    Some(oid) => self.peel(repository, oid, "tree")?,
+                    None => return Ok(None),
+                };
+                match lookup_tree_path(repository, tree_oid, path)? {
+                    Some(oid) => Ok(Some(oid)),
+                    None => Err(MinigitError::fatal(format!("fatal: path '{}' does not exist in '{}'", path, revision))),
+                }
+            }
+            RevisionNode::IndexPath(stage, path) => {
+                let entries = repository.index_take()?.load_and_get_entries()?;
+                let entries: Vec<&Entry> = entries.iter().filter(|entry| entry.get_path_as_str() == path).collect();
+                match (entries.iter().find(|entry| entry.get_stage() == *stage), entries.first()) {
+                    (Some(entry), _) => Ok(Some(*entry.get_oid())),
+                    (None, Some(entry)) => Err(MinigitError::fatal(format!("fatal: path '{}' is in the index, but not at stage {}\n\
+                                                                          hint: Did you mean ':{}:{}'?", path, stage, entry.get_stage(), path))),
+                    (None, None) => Err(MinigitError::fatal(format!("fatal: path '{}' does not exist (neither on disk nor in the index)", path))),
+                }
+            }
+        }
+    }
+
+    fn resolve_commit_node(&self, node: &RevisionNode, repository: &mut Repository) -> MinigitResult<Option<ObjectId>> {
+        match self.resolve_node(node, repository)? {
+            Some(oid) => Ok(Some(self.peel(repository, oid, "commit")?)),
+            None => Ok(None),
+        }
+    }
+
+    fn resolve_name(&self, name: &str, repository: &mut Repository) -> MinigitResult<Option<ObjectId>> {
+        if name.len() == OID_HEX_LEN && ObjectId::is_hex_prefix(name) {
+            return Ok(Some(ObjectId::from_hex(name)?));
+        }
+        if let Some(oid) = repository.refs().read_ref(name)? {
+            return Ok(Some(oid));
+        }
+        if !ObjectId::is_hex_prefix(name) {
+            return Ok(None);
+        }
+        let candidates = repository.database().prefix_match(name)?;
+        match candidates.len() {
+            0 => Ok(None),
+            1 => Ok(Some(candidates[0])),
+            _ => Err(self.ambiguous_error(name, &candidates, repository)),
+        }
+    }
+
+    fn resolve_reflog(&self, name: &Option<String>, selector: &ReflogSelector, repository: &mut Repository) -> MinigitResult<Option<ObjectId>> {
+        let full_name = match name {
+            Some(name) => match repository.refs().expand_ref_name(name)? {
+                Some(full_name) => full_name,
+                None => return Ok(None),
+            },
+            None => repository.refs().current_ref()?,
+        };
+        let entries = repository.refs().read_reflog(&full_name)?;
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        match selector {
+            ReflogSelector::Index(n) => match entries.iter().rev().nth(*n) {
+                Some(entry) => Ok(Some(entry.new_oid)),
+                None => {
+                    let short_name = name.clone().unwrap_or_else(|| String::from(full_name.trim_start_matches("refs/heads/")));
+                    Err(MinigitError::fatal(format!("fatal: log for '{}' only has {} entries", short_name, entries.len())))
+                }
+            },
+            ReflogSelector::Date(date) => match entries.iter().rev().find(|entry| entry.get_timestamp() <= *date) {
+                Some(entry) => Ok(Some(entry.new_oid)),
+                // The date is older than the whole log: use the value the ref had before its first recorded update.
+                None => match entries[0].old_oid.is_null() {
+                    true => Ok(Some(entries[0].new_oid)),
+                    false => Ok(Some(entries[0].old_oid)),
+                },
+            },
+        }
+    }
+
+    fn peel(&self, repository: &mut Repository, oid: ObjectId, object_type: &str) -> MinigitResult<ObjectId> {
+        let mut oid = oid;
+        loop {
+            let actual_type = repository.database().read_type(&oid)?;
+            if actual_type == object_type || object_type == "object" || (object_type.is_empty() && actual_type != "tag") {
+                return Ok(oid);
+            }
+            match (actual_type.as_str(), object_type) {
+                ("tag", _) => oid = *repository.database().load_tag(&oid)?.get_object(),
+                ("commit", "tree") => oid = *repository.database().load_commit(&oid)?.get_tree(),
+                _ => return Err(MinigitError::new(format!("error: {}: expected {} type, but the object dereferences to {} type",
+                                                          self.expression, object_type, actual_type))),
+            }
+        }
+    }
+
+    fn ambiguous_error(&self, name: &str, candidates: &[ObjectId], repository: &mut Repository) -> MinigitError {
+        let abbrev_len = candidates.windows(2)
+            .map(|pair| common_prefix_len(&pair[0].to_hex(), &pair[1].to_hex()) + 1)
+            .fold(MIN_ABBREV_LEN, usize::max);
+        let mut message = format!("error: short object ID {} is ambiguous\nhint: The candidates are:", name);
+        for oid in candidates {
+            let description = match repository.database().load(oid) {
+                Ok(DatabaseObject::Commit(commit)) => {
+                    format!("commit {} - {}", commit.get_committer().get_timestamp().format("%Y-%m-%d"), commit.get_title_line())
+                }
+                Ok(DatabaseObject::Tag(tag)) => format!("tag {}", tag.get_tag_name()),
+                Ok(object) => String::from(object.get_type()),
+                Err(_) => String::from("unknown"),
+            };
+            message.push_str(&format!("\nhint:   {} {}", oid.abbreviate(abbrev_len), description));
+        }
+        message.push('\n');
+        message.push_str(&unknown_revision_error(&self.expression).message);
+        MinigitError::fatal(message)
+    }
+}
+
+/// Finds the object at `path` inside the tree `tree_oid`; an empty path designates the tree itself.
+pub fn lookup_tree_path(repository: &mut Repository, tree_oid: ObjectId, path: &str) -> MinigitResult<Option<ObjectId>> {
+    let mut oid = tree_oid;
+    for component in path.split('/').filter(|component| !component.is_empty()) {
+        if repository.database().read_type(&oid)? != "tree" {
+            return Ok(None);
+        }
+        match repository.database().load_tree(&oid)?.get_entry(component) {
+            Some(entry) => oid = entry.oid,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(oid))
+}
+
+fn unknown_revision_error(expression: &str) -> MinigitError {
+    MinigitError::fatal(format!("fatal: ambiguous argument '{}': unknown revision or path not in the working tree.\n\
+    Use '--' to separate paths from revisions, like this:\n\
+    'git <command> [<revision>...] -- [<file>...]'", expression))
+}
+
+fn common_prefix_len(left: &str, right: &str) -> usize {
+    left.chars().zip(right.chars()).take_while(|(l, r)| l == r).count()
+}
+
+fn parse_node(expression: &str) -> Option<RevisionNode> {
+    if let Some(path) = expression.strip_prefix(':') {
+        // An index path can name a stage of an unmerged path, as `:<stage>:<path>`, the default being stage 0.
+        let (stage, path) = match path.as_bytes() {
+            [stage @ b'0'..=b'3', b':', ..] => (stage - b'0', &path[2..]),
+            _ => (0, path),
+        };
+        return match path.is_empty() {
+            true => None,
+            false => Some(RevisionNode::IndexPath(stage, String::from(path))),
+        };
+    }
+    if let Some(index) = find_path_separator(expression) {
+        let revision = &expression[..index];
+        let inner = parse_suffixes(revision)?;
+        return Some(RevisionNode::TreePath(Box::new(inner), String::from(revision), String::from(&expression[index + 1..])));
+    }
+    parse_suffixes(expression)
+}
+
+fn parse_suffixes(expression: &str) -> Option<RevisionNode> {
+    let base_end = expression.char_indices()
+        .find(|(index, c)| *c == '^' || *c == '~' || expression[*index..].starts_with("@{"))
+        .map(|(index, _)| index)
+        .unwrap_or_else(|| expression.len());
+    let base = &expression[..base_end];
+    let mut rest = &expression[base_end..];
+
+    let mut node = if rest.starts_with("@{") {
+        let close = rest.find('}')?;
+        let selector = parse_reflog_selector(&rest[2..close])?;
+        rest = &rest[close + 1..];
+        RevisionNode::Reflog(if base.is_empty() { None } else { Some(String::from(base)) }, selector)
+    } else if base == "@" {
+        RevisionNode::Ref(String::from(HEAD))
+    } else if base.is_empty() {
+        return None;
+    } else {
+        RevisionNode::Ref(String::from(base))
+    };
+
+    while !rest.is_empty() {
+        if rest.starts_with("^{") {
+            let close = rest.find('}')?;
+            let object_type = &rest[2..close];
+            if !PEEL_TYPES.contains(&object_type) {
+                return None;
+            }
+            node = RevisionNode::Peel(Box::new(node), String::from(object_type));
+            rest = &rest[close + 1..];
+        } else if let Some(suffix) = rest.strip_prefix('^') {
+            let (n, remaining) = parse_number(suffix)?;
+            node = RevisionNode::Parent(Box::new(node), n);
+            rest = remaining;
+        } else if let Some(suffix) = rest.strip_prefix('~') {
+            let (n, remaining) = parse_number(suffix)?;
+            node = RevisionNode::Ancestor(Box::new(node), n);
+            rest = remaining;
+        } else {
+            return None;
+        }
+    }
+    Some(node)
+}
+
+/// Finds the `:` separating a revision from a path, ignoring the ones inside braces like in `@{2017-07-14 02:40:00}`.
+fn find_path_separator(expression: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in expression.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            ':' if depth == 0 => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_reflog_selector(input: &str) -> Option<ReflogSelector> {
+    if !input.is_empty() && input.chars().all(|c| c.is_ascii_digit()) {
+        return Some(ReflogSelector::Index(input.parse().ok()?));
+    }
+    approxidate::parse(input, Local::now()).map(ReflogSelector::Date)
+}
+
+fn parse_number(input: &str) -> Option<(usize, &str)> {
+    let digits_len = input.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits_len == 0 {
+        return Some((1, input));
+    }
+    Some((input[..digits_len].parse().ok()?, &input[digits_len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::RevisionNode::*;
+
+    fn reference(name: &str) -> Box<RevisionNode> {
+        Box::new(Ref(String::from(name)))
+    }
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(Some(Ref(String::from("master"))), parse_node("master"));
+        assert_eq!(Some(Ref(String::from("HEAD"))), parse_node("@"));
+        assert_eq!(None, parse_node(""));
+    }
+
+    #[test]
+    fn test_parse_parents_and_ancestors() {
+        assert_eq!(Some(Parent(reference("HEAD"), 1)), parse_node("HEAD^"));
+        assert_eq!(Some(Parent(reference("HEAD"), 2)), parse_node("HEAD^2"));
+        assert_eq!(Some(Ancestor(reference("HEAD"), 3)), parse_node("HEAD~3"));
+        assert_eq!(Some(Parent(Box::new(Ancestor(reference("master"), 1)), 1)), parse_node("master~^"));
+        assert_eq!(None, parse_node("HEAD~x"));
+    }
+
+    #[test]
+    fn test_parse_peel() {
+        assert_eq!(Some(Peel(reference("HEAD"), String::from("tree"))), parse_node("HEAD^{tree}"));
+        assert_eq!(Some(Peel(reference("v1"), String::new())), parse_node("v1^{}"));
+        assert_eq!(None, parse_node("HEAD^{unknown}"));
+        assert_eq!(None, parse_node("HEAD^{tree"));
+    }
+
+    #[test]
+    fn test_parse_reflog() {
+        assert_eq!(Some(Reflog(None, ReflogSelector::Index(2))), parse_node("@{2}"));
+        assert_eq!(Some(Parent(Box::new(Reflog(Some(String::from("master")), ReflogSelector::Index(1))), 1)), parse_node("master@{1}^"));
+        assert_eq!(Some(Reflog(Some(String::from("master")), ReflogSelector::Date(1_500_000_000))), parse_node("master@{1500000000 +0000}"));
+        assert_eq!(Some(TreePath(Box::new(Reflog(None, ReflogSelector::Date(1_500_000_000))), String::from("@{2017-07-14T02:40:00+00:00}"), String::from("a.txt"))),
+                   parse_node("@{2017-07-14T02:40:00+00:00}:a.txt"));
+        assert_eq!(None, parse_node("master@{one}"));
+        assert_eq!(None, parse_node("master@{}"));
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        assert_eq!(Some(IndexPath(0, String::from("src/main.rs"))), parse_node(":src/main.rs"));
+        assert_eq!(Some(IndexPath(0, String::from("a.txt"))), parse_node(":0:a.txt"));
+        assert_eq!(Some(IndexPath(3, String::from("a.txt"))), parse_node(":3:a.txt"));
+        assert_eq!(Some(IndexPath(0, String::from("4:a.txt"))), parse_node(":4:a.txt"));
+        assert_eq!(Some(TreePath(Box::new(Ancestor(reference("HEAD"), 1)), String::from("HEAD~"), String::from("a/b.txt"))),
+                   parse_node("HEAD~:a/b.txt"));
+        assert_eq!(Some(TreePath(reference("HEAD"), String::from("HEAD"), String::new())), parse_node("HEAD:"));
+    }
+}
@@ -0,0 +1,442 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::gitobject::Commit;
+use crate::minigiterror::MinigitResult;
+use crate::objectid::ObjectId;
+use crate::pathspec::Pathspec;
+use crate::refs::HEAD;
+use crate::repository::Repository;
+use crate::revision::Revision;
+
+const UNINTERESTING: u8 = 1;
+const LEFT: u8 = 1 << 1;
+const RIGHT: u8 = 1 << 2;
+const PROPAGATED_FLAGS: u8 = UNINTERESTING | LEFT | RIGHT;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Order {
+    #[default]
+    Default,
+    Date,
+    Topo,
+}
+
+/// Options controlling which commits a `RevList` selects and in which order.
+/// Author and message patterns are matched as fixed strings.
+#[derive(Default)]
+pub struct RevListOptions {
+    pub first_parent: bool,
+    pub order: Order,
+    pub reverse: bool,
+    pub max_count: Option<usize>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub authors: Vec<String>,
+    pub grep: Vec<String>,
+    pub all_match: bool,
+    pub ignore_case: bool,
+    /// The paths limiting the history to the commits changing them.
+    pub paths: Option<Pathspec>,
+}
+
+/// Walks the commit graph from a set of included and excluded starting points,
+/// implementing git's revision set semantics (`A..B`, `A...B`, `^A`, `--not`, `--all`).
+pub struct RevList<'a> {
+    repository: &'a mut Repository,
+    options: RevListOptions,
+    commits: HashMap<ObjectId, Commit>,
+    flags: HashMap<ObjectId, u8>,
+    parents: HashMap<ObjectId, Vec<ObjectId>>,
+    treesame: HashSet<ObjectId>,
+    queue: BinaryHeap<(i64, Reverse<usize>, ObjectId)>,
+    queued_count: usize,
+    limited: bool,
+    walked: Vec<ObjectId>,
+}
+
+impl<'a> RevList<'a> {
+    pub fn new(repository: &'a mut Repository, options: RevListOptions) -> RevList<'a> {
+        RevList {
+            repository,
+            options,
+            commits: HashMap::new(),
+            flags: HashMap::new(),
+            parents: HashMap::new(),
+            treesame: HashSet::new(),
+            queue: BinaryHeap::new(),
+            queued_count: 0,
+            limited: false,
+            walked: Vec::new(),
+        }
+    }
+
+    /// Adds starting points as they are given on the command line.
+    pub fn add_revision_args(&mut self, args: &[String]) -> MinigitResult<()> {
+        let mut negated = false;
+        for arg in args {
+            match arg.as_str() {
+                "--not" => negated = !negated,
+                "--all" => self.add_all(negated)?,
+                arg => self.add_revision_arg(arg, negated)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn add_revision_arg(&mut self, arg: &str, negated: bool) -> MinigitResult<()> {
+        let (interesting, uninteresting) = match negated {
+            true => (UNINTERESTING, 0),
+            false => (0, UNINTERESTING),
+        };
+        if let Some(index) = arg.find("...") {
+            let left = self.resolve_or_head(&arg[..index])?;
+            let right = self.resolve_or_head(&arg[index + 3..])?;
+            let (left_flags, right_flags) = match negated {
+                true => (UNINTERESTING, UNINTERESTING),
+                false => (LEFT, RIGHT),
+            };
+            self.add_start(left, left_flags)?;
+            self.add_start(right, right_flags)
+        } else if let Some(index) = arg.find("..") {
+            let left = self.resolve_or_head(&arg[..index])?;
+            let right = self.resolve_or_head(&arg[index + 2..])?;
+            self.add_start(left, uninteresting)?;
+            self.add_start(right, interesting)
+        } else if let Some(revision) = arg.strip_prefix('^') {
+            let oid = Revision::parse(revision)?.resolve_commit(self.repository)?;
+            self.add_start(oid, uninteresting)
+        } else {
+            let oid = Revision::parse(arg)?.resolve_commit(self.repository)?;
+            self.add_start(oid, interesting)
+        }
+    }
+
+    fn add_all(&mut self, negated: bool) -> MinigitResult<()> {
+        let mut oids: Vec<ObjectId> = self.repository.refs().list_refs("refs/")?
+            .into_iter().map(|(_, oid)| oid).collect();
+        oids.extend(self.repository.refs().read_head()?);
+        for oid in oids {
+            // Refs may point to trees or blobs, which have no history to walk.
+            if self.repository.database().read_type(&oid)? == "commit" {
+                self.add_start(oid, if negated { UNINTERESTING } else { 0 })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_or_head(&mut self, revision: &str) -> MinigitResult<ObjectId> {
+        let revision = if revision.is_empty() { HEAD } else { revision };
+        Revision::parse(revision)?.resolve_commit(self.repository)
+    }
+
+    fn add_start(&mut self, oid: ObjectId, flags: u8) -> MinigitResult<()> {
+        if flags != 0 {
+            self.limited = true;
+        }
+        let current_flags = self.flags.entry(oid).or_insert(0);
+        *current_flags |= flags;
+        if *current_flags & LEFT != 0 && *current_flags & RIGHT != 0 {
+            *current_flags |= UNINTERESTING;
+        }
+        self.enqueue(oid)
+    }
+
+    fn enqueue(&mut self, oid: ObjectId) -> MinigitResult<()> {
+        let timestamp = self.load_commit(&oid)?.get_committer().get_timestamp().timestamp();
+        self.queue.push((timestamp, Reverse(self.queued_count), oid));
+        self.queued_count += 1;
+        Ok(())
+    }
+
+    fn load_commit(&mut self, oid: &ObjectId) -> MinigitResult<&Commit> {
+        if !self.commits.contains_key(oid) {
+            let commit = self.repository.database().load_commit(oid)?;
+            self.commits.insert(*oid, commit);
+        }
+        Ok(&self.commits[oid])
+    }
+
+    /// Walks the graph and returns the selected commits, ordered and filtered according to the options.
+    pub fn walk(mut self) -> MinigitResult<Vec<Commit>> {
+        self.traverse()?;
+        let selected: Vec<ObjectId> = self.walked.iter()
+            .filter(|oid| self.flags[oid] & UNINTERESTING == 0)
+            .cloned()
+            .collect();
+        let mut selected: Vec<ObjectId> = self.sort(selected).into_iter()
+            .filter(|oid| !self.treesame.contains(oid) && self.matches(&self.commits[oid]))
+            .collect();
+        if let Some(max_count) = self.options.max_count {
+            selected.truncate(max_count);
+        }
+        if self.options.reverse {
+            selected.reverse();
+        }
+        Ok(selected.iter().map(|oid| self.commits.remove(oid).unwrap()).collect())
+    }
+
+    fn traverse(&mut self) -> MinigitResult<()> {
+        let mut processed_flags: HashMap<ObjectId, u8> = HashMap::new();
+        while let Some((_, _, oid)) = self.queue.pop() {
+            let flags = self.flags[&oid];
+            if processed_flags.get(&oid) == Some(&flags) {
+                continue;
+            }
+            if processed_flags.insert(oid, flags).is_none() && flags & UNINTERESTING == 0 {
+                self.walked.push(oid);
+            }
+            for parent in self.walk_parents(oid, flags)? {
+                let known = self.flags.contains_key(&parent);
+                let old_flags = self.flags.get(&parent).cloned().unwrap_or(0);
+                let mut new_flags = old_flags | (flags & PROPAGATED_FLAGS);
+                if new_flags & LEFT != 0 && new_flags & RIGHT != 0 {
+                    new_flags |= UNINTERESTING;
+                }
+                if !known || new_flags != old_flags {
+                    self.flags.insert(parent, new_flags);
+                    self.enqueue(parent)?;
+                }
+            }
+            if self.limited && self.everybody_uninteresting() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn everybody_uninteresting(&self) -> bool {
+        self.queue.iter().all(|(_, _, oid)| self.flags[oid] & UNINTERESTING != 0)
+    }
+
+    fn walk_parents(&mut self, oid: ObjectId, flags: u8) -> MinigitResult<Vec<ObjectId>> {
+        let commit = self.load_commit(&oid)?;
+        let tree = *commit.get_tree();
+        let mut parents = commit.get_parents().clone();
+        if self.options.first_parent {
+            parents.truncate(1);
+        }
+        if self.options.paths.is_some() && flags & UNINTERESTING == 0 {
+            if parents.is_empty() && self.is_treesame(&tree, None)? {
+                self.treesame.insert(oid);
+            }
+            for parent in parents.clone() {
+                let parent_tree = *self.load_commit(&parent)?.get_tree();
+                if self.is_treesame(&tree, Some(&parent_tree))? {
+                    // Like git's default history simplification, only follow a parent the commit didn't change.
+                    self.treesame.insert(oid);
+                    parents = vec!(parent);
+                    break;
+                }
+            }
+        }
+        self.parents.insert(oid, parents.clone());
+        Ok(parents)
+    }
+
+    fn is_treesame(&mut self, tree: &ObjectId, parent_tree: Option<&ObjectId>) -> MinigitResult<bool> {
+        let files = self.limited_files(tree)?;
+        let parent_files = match parent_tree {
+            Some(parent_tree) => self.limited_files(parent_tree)?,
+            None => Vec::new(),
+        };
+        Ok(files == parent_files)
+    }
+
+    /// The files of a tree matching the paths limiting the history, with their id and mode.
+    fn limited_files(&mut self, tree: &ObjectId) -> MinigitResult<Vec<(PathBuf, ObjectId, u32)>> {
+        let files = self.repository.database().load_tree_list(tree)?;
+        let pathspec = self.options.paths.as_ref().unwrap();
+        Ok(files.into_iter().filter(|(path, _)| pathspec.matches(path)).map(|(path, entry)| (path, entry.oid, entry.mode)).collect())
+    }
+
+    fn sort(&self, oids: Vec<ObjectId>) -> Vec<ObjectId> {
+        if self.options.order == Order::Default {
+            return oids;
+        }
+        let positions: HashMap<ObjectId, usize> = oids.iter().enumerate().map(|(position, oid)| (*oid, position)).collect();
+        let mut indegrees: HashMap<ObjectId, usize> = HashMap::new();
+        for oid in oids.iter() {
+            for parent in self.parents[oid].iter().filter(|parent| positions.contains_key(parent)) {
+                *indegrees.entry(*parent).or_insert(0) += 1;
+            }
+        }
+        let tips: Vec<ObjectId> = oids.iter().filter(|oid| !indegrees.contains_key(oid)).cloned().collect();
+        let mut result = Vec::with_capacity(oids.len());
+        let mut stack: Vec<ObjectId> = tips.iter().rev().cloned().collect();
+        let mut heap: BinaryHeap<(i64, Reverse<usize>, ObjectId)> = tips.iter()
+            .map(|oid| (self.commit_timestamp(oid), Reverse(positions[oid]), *oid))
+            .collect();
+        loop {
+            let oid = match self.options.order {
+                Order::Topo => stack.pop(),
+                _ => heap.pop().map(|(_, _, oid)| oid),
+            };
+            let oid = match oid {
+                Some(oid) => oid,
+                None => break,
+            };
+            result.push(oid);
+            for parent in self.parents[&oid].iter().filter(|parent| positions.contains_key(parent)) {
+                let indegree = indegrees.get_mut(parent).unwrap();
+                *indegree -= 1;
+                if *indegree == 0 {
+                    stack.push(*parent);
+                    heap.push((self.commit_timestamp(parent), Reverse(positions[parent]), *parent));
+                }
+            }
+        }
+        result
+    }
+
+    fn commit_timestamp(&self, oid: &ObjectId) -> i64 {
+        self.commits[oid].get_committer().get_timestamp().timestamp()
+    }
+
+    fn matches(&self, commit: &Commit) -> bool {
+        let timestamp = commit.get_committer().get_timestamp().timestamp();
+        if self.options.since.is_some_and(|since| timestamp < since) || self.options.until.is_some_and(|until| timestamp > until) {
+            return false;
+        }
+        let author = commit.get_author();
+        let author = format!("{} <{}>", author.get_name(), author.get_email());
+        if !self.options.authors.is_empty() && !self.options.authors.iter().any(|pattern| self.contains(&author, pattern)) {
+            return false;
+        }
+        let message = commit.get_message();
+        match self.options.all_match {
+            true => self.options.grep.iter().all(|pattern| self.contains(message, pattern)),
+            false => self.options.grep.is_empty() || self.options.grep.iter().any(|pattern| self.contains(message, pattern)),
+        }
+    }
+
+    fn contains(&self, text: &str, pattern: &str) -> bool {
+        match self.options.ignore_case {
+            true => text.to_lowercase().contains(&pattern.to_lowercase()),
+            false => text.contains(pattern),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::gitobject::GitObject;
+    use crate::objectid::ObjectId;
+    use crate::repository::Repository;
+    use crate::tests::write_commit;
+    use crate::workspace::Workspace;
+
+    use super::*;
+
+    fn rev_list(repo_path: &str, args: &[&str], options: RevListOptions) -> Vec<ObjectId> {
+        let mut repository = Repository::new(PathBuf::from(repo_path));
+        let mut rev_list = RevList::new(&mut repository, options);
+        let args: Vec<String> = args.iter().map(|arg| String::from(*arg)).collect();
+        rev_list.add_revision_args(&args).unwrap();
+        rev_list.walk().unwrap().iter().map(|commit| *commit.get_oid()).collect()
+    }
+
+    fn pathspec(repo_path: &str, spec: &str) -> Pathspec {
+        Pathspec::parse(&[String::from(spec)], &Workspace::new(Path::new(repo_path))).unwrap()
+    }
+
+    fn set_ref(repo_path: &str, name: &str, oid: &ObjectId) {
+        let path = PathBuf::from(repo_path).join(".git").join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, format!("{}\n", oid)).unwrap();
+    }
+
+    // a - b - c ------ f   (master)
+    //      \         /
+    //       d ----- e      (topic)
+    fn create_history(repo_path: &str) -> Vec<ObjectId> {
+        let a = write_commit(repo_path, vec!(), "a", 1000, &[("file.txt", "a")]);
+        let b = write_commit(repo_path, vec!(a), "b", 2000, &[("file.txt", "b")]);
+        let c = write_commit(repo_path, vec!(b), "c", 3000, &[("file.txt", "b"), ("other.txt", "c")]);
+        let d = write_commit(repo_path, vec!(b), "d", 2500, &[("file.txt", "d")]);
+        let e = write_commit(repo_path, vec!(d), "e", 3500, &[("file.txt", "e")]);
+        let f = write_commit(repo_path, vec!(c, e), "f", 4000, &[("file.txt", "e"), ("other.txt", "c")]);
+        set_ref(repo_path, "refs/heads/master", &f);
+        set_ref(repo_path, "refs/heads/topic", &e);
+        set_ref(repo_path, "refs/heads/old", &c);
+        vec!(a, b, c, d, e, f)
+    }
+
+    #[test]
+    fn test_default_order_is_reverse_chronological() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (a, b, c, d, e, f) = (commits[0], commits[1], commits[2], commits[3], commits[4], commits[5]);
+            assert_eq!(vec!(f, e, c, d, b, a), rev_list(repo_path, &["master"], RevListOptions::default()));
+        });
+    }
+
+    #[test]
+    fn test_ranges_and_exclusions() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (c, d, e, f) = (commits[2], commits[3], commits[4], commits[5]);
+            assert_eq!(vec!(e, d), rev_list(repo_path, &["old..topic"], RevListOptions::default()));
+            assert_eq!(vec!(e, d), rev_list(repo_path, &["topic", "^old"], RevListOptions::default()));
+            assert_eq!(vec!(e, d), rev_list(repo_path, &["topic", "--not", "old"], RevListOptions::default()));
+            assert_eq!(vec!(e, c, d), rev_list(repo_path, &["old...topic"], RevListOptions::default()));
+            assert_eq!(vec!(f, c), rev_list(repo_path, &["topic..master"], RevListOptions::default()));
+            assert_eq!(Vec::<ObjectId>::new(), rev_list(repo_path, &["master..topic"], RevListOptions::default()));
+        });
+    }
+
+    #[test]
+    fn test_all_and_first_parent() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (a, b, c, f) = (commits[0], commits[1], commits[2], commits[5]);
+            std::fs::write(format!("{}/.git/HEAD", repo_path), format!("{}\n", a)).unwrap();
+            assert_eq!(6, rev_list(repo_path, &["--all"], RevListOptions::default()).len());
+            let options = RevListOptions { first_parent: true, ..RevListOptions::default() };
+            assert_eq!(vec!(f, c, b, a), rev_list(repo_path, &["master"], options));
+        });
+    }
+
+    #[test]
+    fn test_topo_and_date_order() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (a, b, c, d, e, f) = (commits[0], commits[1], commits[2], commits[3], commits[4], commits[5]);
+            let options = RevListOptions { order: Order::Topo, ..RevListOptions::default() };
+            assert_eq!(vec!(f, e, d, c, b, a), rev_list(repo_path, &["master"], options));
+            let options = RevListOptions { order: Order::Date, ..RevListOptions::default() };
+            assert_eq!(vec!(f, e, c, d, b, a), rev_list(repo_path, &["master"], options));
+            let options = RevListOptions { order: Order::Topo, reverse: true, max_count: Some(3), ..RevListOptions::default() };
+            assert_eq!(vec!(d, e, f), rev_list(repo_path, &["master"], options));
+        });
+    }
+
+    #[test]
+    fn test_filters() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (b, c, d, e) = (commits[1], commits[2], commits[3], commits[4]);
+            let options = RevListOptions { since: Some(2000), until: Some(3000), ..RevListOptions::default() };
+            assert_eq!(vec!(c, d, b), rev_list(repo_path, &["master"], options));
+            let options = RevListOptions { grep: vec!(String::from("E"), String::from("d")), ignore_case: true, ..RevListOptions::default() };
+            assert_eq!(vec!(e, d), rev_list(repo_path, &["master"], options));
+            let options = RevListOptions { authors: vec!(String::from("nobody")), ..RevListOptions::default() };
+            assert_eq!(Vec::<ObjectId>::new(), rev_list(repo_path, &["master"], options));
+        });
+    }
+
+    #[test]
+    fn test_path_limiting() {
+        crate::tests::run_test(|repo_path| {
+            let commits = create_history(repo_path);
+            let (a, b, c, d, e) = (commits[0], commits[1], commits[2], commits[3], commits[4]);
+            let options = RevListOptions { paths: Some(pathspec(repo_path, "other.txt")), ..RevListOptions::default() };
+            assert_eq!(vec!(c), rev_list(repo_path, &["master"], options));
+            let options = RevListOptions { paths: Some(pathspec(repo_path, "file.txt")), ..RevListOptions::default() };
+            assert_eq!(vec!(e, d, b, a), rev_list(repo_path, &["master"], options));
+        });
+    }
+}
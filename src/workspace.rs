@@ -4,14 +4,18 @@ use std::fs;
 #[cfg(not(unix))]
 use std::fs::Metadata;
 #[cfg(unix)]
-use std::os::unix::fs::MetadataExt;
-use std::path::Path;
-use std::path::PathBuf;
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
 
-use crate::minigiterror::{MinigitError, MinigitResult};
+use crate::gitobject::{GITLINK_MODE, SYMLINK_MODE};
+use crate::minigiterror::{ErrorKind, MinigitError, MinigitResult};
 
 pub struct Workspace {
     path: PathBuf,
+    /// The directory the command runs from, relative to the top of the workspace, which relative paths start from.
+    prefix: PathBuf,
 }
 
 pub struct MinigitMetadata {
@@ -27,9 +31,50 @@ pub struct MinigitMetadata {
     pub size: u32,
 }
 
+impl MinigitMetadata {
+    /// The metadata of a file which is not read from the workspace, like the files of a tree.
+    pub fn from_mode(mode: u32) -> MinigitMetadata {
+        MinigitMetadata { ctime: 0, ctime_nsec: 0, mtime: 0, mtime_nsec: 0, dev: 0, ino: 0, mode, uid: 0, gid: 0, size: 0 }
+    }
+}
+
 impl Workspace {
     pub fn new(path: &Path) -> Workspace {
-        Workspace { path: path.canonicalize().unwrap() }
+        Workspace { path: path.canonicalize().unwrap(), prefix: PathBuf::new() }
+    }
+
+    pub fn with_prefix(self, prefix: &Path) -> Workspace {
+        Workspace { prefix: PathBuf::from(prefix), ..self }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Makes a path given on the command line relative to the top of the workspace, resolving `.` and `..`, or returns
+    /// none when it is outside. A relative path starts from the directory the command runs from, or from the top with
+    /// `from_top`.
+    pub fn resolve_path(&self, path: &str, from_top: bool) -> Option<PathBuf> {
+        let path = Path::new(path);
+        let relative_path = match path.is_absolute() {
+            true => PathBuf::from(path.strip_prefix(&self.path).ok()?),
+            false if from_top => PathBuf::from(path),
+            false => self.prefix.join(path),
+        };
+        let mut resolved = PathBuf::new();
+        for component in relative_path.components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::ParentDir if resolved.pop() => {}
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+        Some(resolved)
     }
 
     fn normalize_path(&self, path: &Path) -> MinigitResult<PathBuf> {
@@ -37,28 +82,55 @@ impl Workspace {
             true => PathBuf::from(path),
             false => self.path.join(path),
         };
-        if !path.exists() {
-            return Err(MinigitError::new(format!("fatal: pathspec '{}' did not match any files", path.display())));
-        }
-        match path.canonicalize() {
+        let metadata = match fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Err(MinigitError::fatal(format!("fatal: pathspec '{}' did not match any files", path.display()))),
+        };
+        // A symbolic link is kept as is, as its target may be outside of the workspace.
+        let canonical_path = match metadata.file_type().is_symlink() {
+            true => path.parent().unwrap().canonicalize().map(|parent| parent.join(path.file_name().unwrap())),
+            false => path.canonicalize(),
+        };
+        match canonical_path {
             Ok(path) => Ok(path),
-            Err(e) => Err(MinigitError::new(format!("Couldn't canonicalize path {}, error: {}", path.display(), e))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Couldn't canonicalize path {}, error: {}", path.display(), e), e)),
         }
     }
 
+    /// Whether a path exists, symbolic links being never followed, even when their target is missing.
+    pub fn exists(&self, path: &Path) -> bool {
+        fs::symlink_metadata(self.path.join(path)).is_ok()
+    }
+
+    /// Whether a path is a regular file or a symbolic link, which are both tracked as files.
     pub fn is_file(&self, path: &Path) -> MinigitResult<bool> {
-        Ok(self.normalize_path(path)?.is_file())
+        Ok(!fs::symlink_metadata(self.normalize_path(path)?).is_ok_and(|metadata| metadata.is_dir()))
     }
 
     pub fn is_dir(&self, path: &Path) -> MinigitResult<bool> {
-        Ok(self.normalize_path(path)?.is_dir())
+        Ok(fs::symlink_metadata(self.normalize_path(path)?).is_ok_and(|metadata| metadata.is_dir()))
+    }
+
+    /// Whether a path is the workspace of a nested repository, tracked as a gitlink rather than as files.
+    pub fn is_gitlink(&self, path: &Path) -> bool {
+        let full_path = self.path.join(path);
+        full_path != self.path
+            && fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_dir())
+            && fs::symlink_metadata(full_path.join(".git")).is_ok()
+    }
+
+    pub fn create_dir(&self, path: &Path) -> MinigitResult<()> {
+        match fs::create_dir_all(self.path.join(path)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(MinigitError::new(format!("error: unable to create directory '{}': {}", path.display(), e))),
+        }
     }
 
     pub fn list_dir(&self, path: &Path) -> MinigitResult<Vec<PathBuf>> {
         let path = self.normalize_path(path)?;
         match self.list_dir_recurse(&path, Vec::new()) {
             Ok(files) => Ok(files),
-            Err(e) => Err(MinigitError::new(format!("Error trying to list files from path {}: {}", path.display(), e))),
+            Err(e) => Err(MinigitError::with_kind(ErrorKind::Io, format!("Error trying to list files from path {}: {}", path.display(), e))),
         }
     }
 
@@ -74,26 +146,44 @@ impl Workspace {
     }
 
     pub fn list_files_from_path(&self, path: &Path) -> MinigitResult<Vec<PathBuf>> {
+        self.list_files(path, false)
+    }
+
+    /// Lists the files like `list_files_from_path`, including the ignored ones but never the `.git` directory.
+    pub fn list_all_files_from_path(&self, path: &Path) -> MinigitResult<Vec<PathBuf>> {
+        self.list_files(path, true)
+    }
+
+    fn list_files(&self, path: &Path, include_ignored: bool) -> MinigitResult<Vec<PathBuf>> {
         let path = self.normalize_path(path)?;
-        match self.list_files_recurse(&path, Vec::new()) {
+        match self.list_files_recurse(&path, Vec::new(), include_ignored) {
             Ok(files) => Ok(files),
-            Err(e) => Err(MinigitError::new(format!("Error trying to list files from path {}: {}", path.display(), e))),
+            Err(e) => Err(MinigitError::with_kind(ErrorKind::Io, format!("Error trying to list files from path {}: {}", path.display(), e))),
         }
     }
 
-    fn list_files_recurse(&self, path: &Path, mut result: Vec<PathBuf>) -> Result<Vec<PathBuf>, Box<Error>> {
-        if path.is_file() && !self.is_file_ignored(&path) {
+    /// Symbolic links are listed as files and never traversed, even when they point to a directory. Nested repositories
+    /// are listed like files too, as gitlinks.
+    fn list_files_recurse(&self, path: &Path, mut result: Vec<PathBuf>, include_ignored: bool) -> Result<Vec<PathBuf>, Box<Error>> {
+        let is_dir = fs::symlink_metadata(path)?.is_dir();
+        let is_gitlink = is_dir && path != self.path && fs::symlink_metadata(path.join(".git")).is_ok();
+        if is_gitlink || (!is_dir && (include_ignored || !self.is_file_ignored(&path))) {
             result.push(PathBuf::from(path.strip_prefix(&self.path)?));
-        } else if path.is_dir() && !self.is_dir_ignored(&path) {
+        } else if is_dir && path.file_name() != Some(OsString::from(".git").as_os_str()) && (include_ignored || !self.is_dir_ignored(&path)) {
             for entry in fs::read_dir(&path)? {
                 let entry = entry?;
                 let path = entry.path();
-                result = self.list_files_recurse(&path, result)?;
+                result = self.list_files_recurse(&path, result, include_ignored)?;
             }
         }
         Ok(result)
     }
 
+    /// Whether a file, or one of its parent directories, is ignored.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        path.ancestors().skip(1).any(|ancestor| self.is_dir_ignored(ancestor)) || self.is_file_ignored(path)
+    }
+
     fn is_dir_ignored(&self, path: &Path) -> bool {
         let ignored_dirs = [OsString::from(".git"), OsString::from("target")];
         if let Some(filename) = path.file_name() {
@@ -120,25 +210,100 @@ impl Workspace {
         false
     }
 
+    /// Reads the content of a file, which for a symbolic link is the path of its target.
     pub fn read_file(&self, path: &Path) -> MinigitResult<Vec<u8>> {
-        match fs::read(self.path.join(path)) {
+        let full_path = self.path.join(path);
+        let content = match fs::symlink_metadata(&full_path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::read_link(&full_path).map(|target| target.to_string_lossy().into_owned().into_bytes()),
+            _ => fs::read(&full_path),
+        };
+        match content {
             Ok(bytes) => Ok(bytes),
-            Err(e) => Err(MinigitError::new(String::from(format!("error: trying to read file '{}': {}", path.display(), e)))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("error: trying to read file '{}': {}", path.display(), e), e)),
+        }
+    }
+
+    /// Writes a file, or a symbolic link to the path given as data, replacing what is in its way or in the way of its
+    /// parent directories.
+    pub fn write_file(&self, path: &Path, data: &[u8], mode: u32) -> MinigitResult<()> {
+        let error = |e: std::io::Error| MinigitError::with_source(ErrorKind::Io, format!("error: unable to write file '{}': {}", path.display(), e), e);
+        let full_path = self.path.join(path);
+        for ancestor in path.ancestors().skip(1).collect::<Vec<&Path>>().into_iter().rev() {
+            if fs::symlink_metadata(self.path.join(ancestor)).is_ok_and(|metadata| !metadata.is_dir()) {
+                fs::remove_file(self.path.join(ancestor)).map_err(error)?;
+            }
+        }
+        match fs::symlink_metadata(&full_path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&full_path).map_err(error)?,
+            // Writing through a symbolic link would change its target.
+            Ok(_) => fs::remove_file(&full_path).map_err(error)?,
+            Err(_) => {}
+        }
+        fs::create_dir_all(full_path.parent().unwrap()).map_err(error)?;
+        if mode == SYMLINK_MODE {
+            return self.write_symlink(&full_path, data).map_err(error);
+        }
+        fs::write(&full_path, data).map_err(error)?;
+        self.set_mode(&full_path, mode).map_err(error)
+    }
+
+    #[cfg(unix)]
+    fn write_symlink(&self, path: &Path, target: &[u8]) -> std::io::Result<()> {
+        std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), path)
+    }
+
+    #[cfg(not(unix))]
+    fn write_symlink(&self, path: &Path, target: &[u8]) -> std::io::Result<()> {
+        fs::write(path, target)
+    }
+
+    #[cfg(unix)]
+    fn set_mode(&self, path: &Path, mode: u32) -> std::io::Result<()> {
+        let permissions = match mode & 0o100 > 0 {
+            true => 0o755,
+            false => 0o644,
+        };
+        fs::set_permissions(path, fs::Permissions::from_mode(permissions))
+    }
+
+    #[cfg(not(unix))]
+    fn set_mode(&self, _path: &Path, _mode: u32) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Deletes a file if it exists, then the parent directories it leaves empty.
+    pub fn remove_file(&self, path: &Path) -> MinigitResult<()> {
+        let full_path = self.path.join(path);
+        if fs::symlink_metadata(&full_path).is_ok_and(|metadata| !metadata.is_dir()) {
+            if let Err(e) = fs::remove_file(&full_path) {
+                return Err(MinigitError::new(format!("error: unable to unlink '{}': {}", path.display(), e)));
+            }
+        }
+        for ancestor in path.ancestors().skip(1).filter(|ancestor| !ancestor.as_os_str().is_empty()) {
+            // Fails when the directory is not empty.
+            if fs::remove_dir(self.path.join(ancestor)).is_err() {
+                break;
+            }
         }
+        Ok(())
     }
 
     pub fn get_metadata(&self, path: &Path) -> MinigitResult<MinigitMetadata> {
-        match fs::metadata(self.path.join(path)) {
+        match fs::symlink_metadata(self.path.join(path)) {
+            Ok(metadata) if self.is_gitlink(path) => Ok(MinigitMetadata { mode: GITLINK_MODE, ..self._get_metadata(&metadata) }),
             Ok(metadata) => Ok(self._get_metadata(&metadata)),
-            Err(e) => Err(MinigitError::new(format!("Couldn't read metadata of path {}: {}", path.display(), e))),
+            Err(e) => Err(MinigitError::with_source(ErrorKind::Io, format!("Couldn't read metadata of path {}: {}", path.display(), e), e)),
         }
     }
 
     #[cfg(unix)]
     fn _get_metadata(&self, metadata: &fs::Metadata) -> MinigitMetadata {
-        let mode = match metadata.mode() & 0o100 > 0 {
-            true => 0o100755,
-            false => 0o100644
+        let mode = if metadata.file_type().is_symlink() {
+            SYMLINK_MODE
+        } else if metadata.mode() & 0o100 > 0 {
+            0o100755
+        } else {
+            0o100644
         };
         MinigitMetadata {
             ctime: metadata.ctime() as u32,
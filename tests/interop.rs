@@ -0,0 +1,239 @@
+//! Checks the repositories written by minigit with the system `git`, and the other way around. The tests are skipped
+//! when no `git` binary can be run.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A repository in a fresh directory of the system temp dir, deleted when dropped.
+struct TestRepo {
+    path: PathBuf,
+}
+
+impl TestRepo {
+    /// Returns `None` when `git` is not installed.
+    fn new() -> Option<TestRepo> {
+        if Command::new("git").arg("--version").output().is_err() {
+            eprintln!("git is not available, skipping interoperability test");
+            return None;
+        }
+        let name = format!("{}-{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::SeqCst));
+        let path = env::temp_dir().join("minigit_interop").join(name);
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        Some(TestRepo { path })
+    }
+
+    fn write(&self, name: &str, content: &str) {
+        let path = self.path.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn minigit(&self, args: &[&str]) -> String {
+        self.minigit_with_input(args, "")
+    }
+
+    fn minigit_with_input(&self, args: &[&str], input: &str) -> String {
+        run(self.command(env!("CARGO_BIN_EXE_minigit")).args(args), input)
+    }
+
+    fn git(&self, args: &[&str]) -> String {
+        run(self.command("git").args(args), "")
+    }
+
+    /// Both tools get the same identity, and git ignores the configuration of the machine.
+    fn command(&self, program: &str) -> Command {
+        let mut command = Command::new(program);
+        command.current_dir(&self.path)
+            .env("GIT_AUTHOR_NAME", "A U Thor")
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_COMMITTER_NAME", "C O Mitter")
+            .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_CONFIG_GLOBAL", "/dev/null")
+            .env("HOME", &self.path);
+        command
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+fn run(command: &mut Command, input: &str) -> String {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?} failed: {}", command, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn write_files(repo: &TestRepo) {
+    repo.write("a.txt", "a\n");
+    repo.write("foo.txt", "foo\n");
+    repo.write("foo-bar", "foo-bar\n");
+    repo.write("foo/bar.txt", "bar\n");
+    repo.write("foo/baz/qux.txt", "qux\n");
+    repo.write("foo0", "foo0\n");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        repo.write("bin/run.sh", "#!/bin/sh\n");
+        fs::set_permissions(repo.path.join("bin/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("a.txt", repo.path.join("link")).unwrap();
+    }
+}
+
+#[test]
+fn test_git_reads_minigit_commits() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    repo.minigit(&["init"]);
+    write_files(&repo);
+    repo.minigit(&["add", "."]);
+    repo.minigit_with_input(&["commit"], "First commit\n");
+    repo.write("foo/bar.txt", "changed\n");
+    repo.minigit(&["rm", "foo0"]);
+    repo.minigit(&["add", "foo"]);
+    repo.minigit_with_input(&["commit"], "Second commit\n\nWith a body.\n");
+
+    repo.git(&["fsck", "--strict", "--no-dangling"]);
+    assert_eq!("", repo.git(&["status", "--porcelain"]));
+    assert_eq!("Second commit\nFirst commit\n", repo.git(&["log", "--format=%s"]));
+    assert_eq!("A U Thor <author@example.com>\n", repo.git(&["log", "-1", "--format=%an <%ae>"]));
+    // The tree git would write from the index is the one minigit committed.
+    assert_eq!(repo.git(&["rev-parse", "HEAD^{tree}"]), repo.git(&["write-tree"]));
+    assert_eq!("changed\n", repo.git(&["cat-file", "-p", "HEAD:foo/bar.txt"]));
+    let names = repo.git(&["ls-tree", "--name-only", "HEAD"]);
+    #[cfg(unix)]
+    assert_eq!("a.txt\nbin\nfoo-bar\nfoo.txt\nfoo\nlink\n", names);
+    #[cfg(unix)]
+    assert!(repo.git(&["ls-files", "-s"]).contains("100755 "));
+    #[cfg(not(unix))]
+    assert_eq!("a.txt\nfoo-bar\nfoo.txt\nfoo\n", names);
+}
+
+#[test]
+fn test_git_reads_minigit_refs() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    repo.minigit(&["init"]);
+    write_files(&repo);
+    repo.minigit(&["add", "."]);
+    repo.minigit_with_input(&["commit"], "First commit\n");
+    repo.minigit(&["tag", "light"]);
+    repo.minigit(&["tag", "-a", "v1.0", "-m", "Version 1.0"]);
+    repo.minigit(&["pack-refs", "--all"]);
+    repo.minigit(&["tag", "loose"]);
+
+    repo.git(&["fsck", "--strict", "--no-dangling"]);
+    assert_eq!("tag\n", repo.git(&["cat-file", "-t", "v1.0"]));
+    assert_eq!(repo.git(&["rev-parse", "HEAD"]), repo.git(&["rev-parse", "v1.0^{commit}"]));
+    assert_eq!(repo.git(&["show-ref", "--head"]), repo.minigit(&["show-ref", "--head"]));
+    assert_eq!("light\nloose\nv1.0\n", repo.git(&["tag", "--list"]));
+}
+
+#[test]
+fn test_minigit_reads_git_repositories() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    repo.git(&["init", "--quiet", "--initial-branch=master"]);
+    write_files(&repo);
+    repo.git(&["add", "."]);
+    repo.git(&["commit", "--quiet", "-m", "First commit"]);
+    repo.write("foo/bar.txt", "changed\n");
+    repo.git(&["commit", "--quiet", "-a", "-m", "Second commit"]);
+    repo.git(&["tag", "-a", "v1.0", "-m", "Version 1.0"]);
+    repo.git(&["pack-refs", "--all"]);
+
+    assert_eq!("", repo.minigit(&["status"]));
+    assert_eq!(repo.git(&["rev-parse", "HEAD", "v1.0", "HEAD^{tree}"]), repo.minigit(&["rev-parse", "HEAD", "v1.0", "HEAD^{tree}"]));
+    assert_eq!(repo.git(&["rev-list", "HEAD"]), repo.minigit(&["rev-list", "HEAD"]));
+    assert_eq!(repo.git(&["show-ref"]), repo.minigit(&["show-ref"]));
+
+    // Commits made by minigit on top of git's are accepted by git.
+    repo.write("new.txt", "new\n");
+    repo.minigit(&["add", "new.txt"]);
+    assert_eq!("A  new.txt\n", repo.git(&["status", "--porcelain"]));
+    repo.minigit_with_input(&["commit"], "Third commit\n");
+    repo.git(&["fsck", "--strict", "--no-dangling"]);
+    assert_eq!("", repo.git(&["status", "--porcelain"]));
+    assert_eq!("Third commit\nSecond commit\nFirst commit\n", repo.git(&["log", "--format=%s"]));
+}
+
+#[test]
+fn test_exit_codes_match_git() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    let exit_code = |program: &str, args: &[&str]| repo.command(program).args(args).output().unwrap().status.code();
+    // Outside of a repository.
+    assert_eq!(exit_code("git", &["status"]), exit_code(env!("CARGO_BIN_EXE_minigit"), &["status"]));
+    repo.minigit(&["init"]);
+    for args in [&["rm", "--bad-option"][..], &["rev-parse", "--verify", "missing"], &["rm", "missing.txt"]].iter() {
+        assert_eq!(exit_code("git", args), exit_code(env!("CARGO_BIN_EXE_minigit"), args), "exit code of {:?}", args);
+    }
+}
+
+#[test]
+fn test_bare_repositories() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    repo.minigit(&["init", "--bare", "--quiet", "central.git"]);
+    assert_eq!("true\n", repo.git(&["-C", "central.git", "rev-parse", "--is-bare-repository"]));
+    repo.git(&["-C", "central.git", "fsck", "--strict"]);
+
+    repo.git(&["init", "--bare", "--quiet", "other.git"]);
+    let exit_code = |program: &str, args: &[&str]| repo.command(program).args(args).output().unwrap().status.code();
+    for args in [&["-C", "central.git", "status"][..], &["-C", "other.git/refs", "status"], &["-C", "other.git", "reset"]].iter() {
+        assert_eq!(exit_code("git", args), exit_code(env!("CARGO_BIN_EXE_minigit"), args), "exit code of {:?}", args);
+    }
+    assert_eq!("", repo.minigit(&["-C", "other.git", "for-each-ref"]));
+}
+
+#[test]
+fn test_git_reads_minigit_clones() {
+    let repo = match TestRepo::new() {
+        Some(repo) => repo,
+        None => return,
+    };
+    repo.git(&["init", "--quiet", "--initial-branch=main", "source"]);
+    repo.write("source/a.txt", "a\n");
+    repo.git(&["-C", "source", "add", "."]);
+    repo.git(&["-C", "source", "commit", "--quiet", "-m", "First commit"]);
+    repo.write("source/b.txt", "b\n");
+    repo.git(&["-C", "source", "add", "."]);
+    repo.git(&["-C", "source", "commit", "--quiet", "-m", "Second commit"]);
+
+    repo.minigit(&["clone", "--quiet", "source", "copy"]);
+    repo.git(&["-C", "copy", "fsck", "--strict"]);
+    assert_eq!("", repo.git(&["-C", "copy", "status", "--porcelain"]));
+    assert_eq!("main\n", repo.git(&["-C", "copy", "branch", "--show-current"]));
+    assert_eq!("origin/main\n", repo.git(&["-C", "copy", "rev-parse", "--abbrev-ref", "@{upstream}"]));
+    assert_eq!(repo.git(&["-C", "source", "rev-parse", "HEAD"]), repo.git(&["-C", "copy", "rev-parse", "origin/HEAD"]));
+
+    let url = format!("file://{}", repo.path.join("source").display());
+    repo.minigit(&["clone", "--quiet", "--depth", "1", &url, "shallow"]);
+    assert_eq!("true\n", repo.git(&["-C", "shallow", "rev-parse", "--is-shallow-repository"]));
+    assert_eq!("Second commit\n", repo.git(&["-C", "shallow", "log", "--format=%s"]));
+    repo.git(&["-C", "shallow", "fsck", "--strict"]);
+}
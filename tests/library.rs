@@ -0,0 +1,45 @@
+//! Uses minigit as a library, through its public API only.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use minigit::command::{execute, Runtime};
+use minigit::gitobject::{Blob, GitObject};
+use minigit::repository::Repository;
+use minigit::revision::Revision;
+use minigit::workspace::MinigitMetadata;
+
+#[test]
+fn test_public_api() {
+    let path = env::temp_dir().join("minigit_library").join(format!("{}", std::process::id()));
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    fs::create_dir_all(&path).unwrap();
+    let mut runtime = Runtime { dir: path.clone(), args: vec!(String::from("minigit"), String::from("init"), String::from("-q")), ..Runtime::default() };
+    execute(&mut runtime).unwrap();
+
+    let mut repository = Repository::new(path.clone());
+    let mut blob = Blob::new(b"hello\n".to_vec());
+    repository.database().store(&mut blob).unwrap();
+    assert_eq!("ce013625030ba8dba906f756967f9e9ca394464a", blob.get_oid().to_hex());
+    repository.index().unwrap().load_for_update().unwrap();
+    repository.index().unwrap().add(Path::new("hello.txt"), *blob.get_oid(), MinigitMetadata::from_mode(0o100644));
+    repository.index().unwrap().write_updates().unwrap();
+    drop(repository);
+
+    let mut runtime = Runtime { dir: path.clone(), args: vec!(String::from("minigit"), String::from("commit")), ..Runtime::default() };
+    runtime.env.insert(String::from("GIT_AUTHOR_NAME"), String::from("A U Thor"));
+    runtime.env.insert(String::from("GIT_AUTHOR_EMAIL"), String::from("author@example.com"));
+    runtime.stdin = Box::new("Add hello\n".as_bytes());
+    runtime.stdout = Box::new(std::io::sink());
+    execute(&mut runtime).unwrap();
+
+    let mut repository = Repository::new(path.clone());
+    let head = repository.refs().read_head().unwrap().unwrap();
+    assert_eq!(head, Revision::parse("HEAD").unwrap().resolve_commit(&mut repository).unwrap());
+    let blob_oid = Revision::parse("HEAD:hello.txt").unwrap().resolve(&mut repository).unwrap();
+    assert_eq!(b"hello\n".to_vec(), *repository.database().load_blob(&blob_oid).unwrap().get_data());
+    fs::remove_dir_all(&path).unwrap();
+}